@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::RuleSpec;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// Url alerts are posted to in addition to the log and the `/alerts` endpoint
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Key of HMAC-SHA256 signatures of the posted alerts, unsigned if omitted
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    /// Webhook request timeout in milliseconds
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    #[serde(default)]
+    pub rules: Vec<RuleSpec>,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("webhook_url", &self.webhook_url)
+            .field(
+                "webhook_secret",
+                &self.webhook_secret.as_ref().map(|_| "***"),
+            )
+            .field("request_timeout", &self.request_timeout)
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
+
+fn default_request_timeout() -> u64 {
+    5000
+}
@@ -0,0 +1,118 @@
+mod config;
+mod monitor;
+mod notifier;
+mod store;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::now;
+use crate::node_clients::Chain;
+use crate::service::BalanceKind;
+
+pub use config::Config;
+pub use monitor::spawn_monitors;
+pub use notifier::Notifier;
+pub use store::{AlertStore, InMemoryAlertStore};
+
+/// Limits of an asset balance of an address
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RuleSpec {
+    pub chain: Chain,
+    pub address: String,
+    /// Either a supported asset or the native token of the chain
+    pub asset_id: String,
+    /// Kind of the balance to compare, the first reported one if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<BalanceKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<u64>,
+    /// Human readable name, e.g. of the hot wallet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Rule {
+    pub id: String,
+    #[serde(flatten)]
+    pub spec: RuleSpec,
+    pub created_at: u64,
+}
+
+impl RuleSpec {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match (self.min, self.max) {
+            (None, None) => Err("at least one of `min` and `max` is required"),
+            (Some(min), Some(max)) if min > max => Err("`min` must not exceed `max`"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Rule {
+    pub fn new(spec: RuleSpec) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            spec,
+            created_at: now(),
+        }
+    }
+
+    /// Limits of the rule violated by `amount`
+    pub fn violations(&self, amount: u64) -> Vec<(Condition, u64)> {
+        let mut violations = vec![];
+
+        if let Some(min) = self.spec.min {
+            if amount < min {
+                violations.push((Condition::BelowMin, min));
+            }
+        }
+
+        if let Some(max) = self.spec.max {
+            if amount > max {
+                violations.push((Condition::AboveMax, max));
+            }
+        }
+
+        violations
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    BelowMin,
+    AboveMax,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Active,
+    Resolved,
+}
+
+/// Violation of a rule limit, active until the balance is back within the limit
+#[derive(Clone, Debug, Serialize)]
+pub struct Alert {
+    pub id: String,
+    pub rule_id: String,
+    pub chain: Chain,
+    pub address: String,
+    pub asset_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub condition: Condition,
+    pub limit: u64,
+    pub state: AlertState,
+    /// Latest observed amount
+    pub amount: u64,
+    pub raised_at: u64,
+    pub raised_height: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_height: Option<u64>,
+}
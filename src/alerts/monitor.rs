@@ -0,0 +1,190 @@
+use futures::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{error, warn};
+
+use super::{Alert, AlertState, AlertStore, Condition, Notifier, Rule};
+use crate::clock::now;
+use crate::node_clients::Chain;
+use crate::service::{find_amount, BalancesService};
+use crate::subscriptions::{self, Subscription};
+
+/// Number of addresses read concurrently
+const CONCURRENCY: usize = 16;
+
+/// Starts a background monitor per chain, which evaluates the alert rules
+/// on every new block
+pub fn spawn_monitors(
+    chains: impl IntoIterator<Item = Chain>,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    store: Arc<dyn AlertStore + Send + Sync>,
+    notifier: Arc<Notifier>,
+) {
+    for chain in chains {
+        tokio::spawn(monitor(
+            chain,
+            service.clone(),
+            store.clone(),
+            notifier.clone(),
+        ));
+    }
+}
+
+async fn monitor(
+    chain: Chain,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    store: Arc<dyn AlertStore + Send + Sync>,
+    notifier: Arc<Notifier>,
+) {
+    let blocks = match service.subscribe_blocks(chain.clone()) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let mut blocks = subscriptions::blocks_stream(blocks);
+
+    while let Some(height) = blocks.next().await {
+        let rules = match store.rules_by_chain(&chain).await {
+            Ok(rules) => rules,
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        };
+
+        let mut by_address: HashMap<String, Vec<Rule>> = HashMap::new();
+        for rule in rules {
+            by_address
+                .entry(rule.spec.address.clone())
+                .or_default()
+                .push(rule);
+        }
+
+        let service = service.as_ref().as_ref();
+        let chain = &chain;
+        let results = stream::iter(by_address)
+            .map(|(address, rules)| async move {
+                let mut asset_ids = rules
+                    .iter()
+                    .map(|rule| rule.spec.asset_id.clone())
+                    .collect::<Vec<_>>();
+                asset_ids.sort();
+                asset_ids.dedup();
+
+                // the rest of the asset ids may only refer to the native token
+                let not_supported = service
+                    .check_assets_support(chain.clone(), asset_ids.clone())
+                    .unwrap_or_default();
+                asset_ids.retain(|asset_id| !not_supported.contains(asset_id));
+
+                let subscription = Subscription {
+                    chain: chain.clone(),
+                    address: address.clone(),
+                    asset_ids,
+                };
+                let result = subscription.fetch(service).await;
+                (address, rules, result)
+            })
+            .buffer_unordered(CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        for (address, rules, result) in results {
+            let balances = match result {
+                Ok(balances) => balances,
+                Err(e) => {
+                    warn!("failed to read balances of {} on {}: {}", address, chain, e);
+                    continue;
+                }
+            };
+
+            // last known balances served while the node fails would raise
+            // or resolve alerts at a height they were not read at
+            if balances.iter().any(|balance| balance.staleness.is_some()) {
+                warn!(
+                    "balances of {} on {} are stale, its alert rules are not evaluated",
+                    address, chain
+                );
+                continue;
+            }
+
+            for rule in &rules {
+                match find_amount(&balances, &rule.spec.asset_id, rule.spec.kind.as_ref()) {
+                    Some(amount) => evaluate(store.as_ref(), &notifier, rule, amount, height).await,
+                    None => warn!(
+                        "alert rule {}: balance of asset {} is not available",
+                        rule.id, rule.spec.asset_id
+                    ),
+                }
+            }
+        }
+    }
+}
+
+async fn evaluate(
+    store: &(dyn AlertStore + Send + Sync),
+    notifier: &Arc<Notifier>,
+    rule: &Rule,
+    amount: u64,
+    height: u64,
+) {
+    let violations = rule.violations(amount);
+
+    for condition in [Condition::BelowMin, Condition::AboveMax] {
+        let active = match store.active(&rule.id, &condition).await {
+            Ok(active) => active,
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        };
+
+        let violation = violations
+            .iter()
+            .find(|(violated, _)| violated == &condition);
+
+        let (alert, changed) = match (violation, active) {
+            (Some((_, limit)), None) => (
+                Alert {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    rule_id: rule.id.clone(),
+                    chain: rule.spec.chain.clone(),
+                    address: rule.spec.address.clone(),
+                    asset_id: rule.spec.asset_id.clone(),
+                    label: rule.spec.label.clone(),
+                    condition,
+                    limit: *limit,
+                    state: AlertState::Active,
+                    amount,
+                    raised_at: now(),
+                    raised_height: height,
+                    resolved_at: None,
+                    resolved_height: None,
+                },
+                true,
+            ),
+            (Some(_), Some(alert)) => (Alert { amount, ..alert }, false),
+            (None, Some(alert)) => (
+                Alert {
+                    amount,
+                    state: AlertState::Resolved,
+                    resolved_at: Some(now()),
+                    resolved_height: Some(height),
+                    ..alert
+                },
+                true,
+            ),
+            (None, None) => continue,
+        };
+
+        if changed {
+            notifier.notify(&alert);
+        }
+
+        if let Err(e) = store.upsert(alert).await {
+            error!("{}", e);
+        }
+    }
+}
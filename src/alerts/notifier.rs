@@ -0,0 +1,82 @@
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::{Alert, AlertState, Config};
+use crate::error::Error as AppError;
+use crate::webhooks::{sign, SIGNATURE_HEADER};
+
+static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+
+/// Reports raised and resolved alerts to the log and to the webhook
+pub struct Notifier {
+    http_client: Client,
+    webhook_url: Option<String>,
+    webhook_secret: Option<String>,
+}
+
+impl Notifier {
+    pub fn try_new(config: &Config) -> Result<Self, AppError> {
+        let http_client = reqwest::Client::builder()
+            .user_agent(APP_USER_AGENT)
+            .timeout(Duration::from_millis(config.request_timeout))
+            .build()
+            .map_err(Arc::new)?;
+
+        Ok(Self {
+            http_client,
+            webhook_url: config.webhook_url.clone(),
+            webhook_secret: config.webhook_secret.clone(),
+        })
+    }
+
+    pub fn notify(self: &Arc<Self>, alert: &Alert) {
+        match alert.state {
+            AlertState::Active => warn!(
+                "alert {}: {} balance of {} on {} is {}, {:?} {}",
+                alert.id,
+                alert.asset_id,
+                alert.address,
+                alert.chain,
+                alert.amount,
+                alert.condition,
+                alert.limit
+            ),
+            AlertState::Resolved => info!(
+                "alert {} resolved: {} balance of {} on {} is {}",
+                alert.id, alert.asset_id, alert.address, alert.chain, alert.amount
+            ),
+        }
+
+        if let Some(webhook_url) = &self.webhook_url {
+            tokio::spawn(self.clone().post(webhook_url.clone(), alert.clone()));
+        }
+    }
+
+    async fn post(self: Arc<Self>, webhook_url: String, alert: Alert) {
+        let body = serde_json::to_vec(&alert).expect("Failed to serialize Alert");
+
+        let mut request = self
+            .http_client
+            .post(&webhook_url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.webhook_secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+        }
+
+        match request.body(body).send().await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!(
+                "failed to post alert {} to {}: unexpected response status {}",
+                alert.id,
+                webhook_url,
+                response.status()
+            ),
+            Err(e) => warn!(
+                "failed to post alert {} to {}: {}",
+                alert.id, webhook_url, e
+            ),
+        }
+    }
+}
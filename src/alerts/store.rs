@@ -0,0 +1,171 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+
+use super::{Alert, AlertState, Condition, Rule};
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+
+const MAX_RESOLVED_ALERTS: usize = 1000;
+
+#[async_trait::async_trait]
+pub trait AlertStore {
+    async fn insert_rule(&self, rule: Rule) -> Result<(), AppError>;
+
+    /// Removes the rule and resolves its active alerts
+    async fn remove_rule(&self, id: &str, now: u64) -> Result<Option<Rule>, AppError>;
+
+    async fn rules(&self) -> Result<Vec<Rule>, AppError>;
+
+    async fn rules_by_chain(&self, chain: &Chain) -> Result<Vec<Rule>, AppError>;
+
+    async fn active(&self, rule_id: &str, condition: &Condition)
+        -> Result<Option<Alert>, AppError>;
+
+    /// Inserts or replaces an active alert, a resolved one is moved to the history
+    async fn upsert(&self, alert: Alert) -> Result<(), AppError>;
+
+    /// Active alerts followed by the most recently resolved ones
+    async fn alerts(&self, state: Option<AlertState>) -> Result<Vec<Alert>, AppError>;
+}
+
+/// Keeps rules and alerts in memory, so rules added via API do not survive restarts
+#[derive(Debug, Default)]
+pub struct InMemoryAlertStore {
+    rules: RwLock<HashMap<String, Rule>>,
+    /// (rule id, condition) -> alert
+    active: RwLock<HashMap<(String, Condition), Alert>>,
+    resolved: RwLock<VecDeque<Alert>>,
+}
+
+impl InMemoryAlertStore {
+    fn resolve(&self, alert: Alert) {
+        let mut resolved = self.resolved.write().expect("resolved lock is poisoned");
+        resolved.push_front(alert);
+        resolved.truncate(MAX_RESOLVED_ALERTS);
+    }
+}
+
+#[async_trait::async_trait]
+impl AlertStore for InMemoryAlertStore {
+    async fn insert_rule(&self, rule: Rule) -> Result<(), AppError> {
+        self.rules
+            .write()
+            .expect("rules lock is poisoned")
+            .insert(rule.id.clone(), rule);
+        Ok(())
+    }
+
+    async fn remove_rule(&self, id: &str, now: u64) -> Result<Option<Rule>, AppError> {
+        let rule = self
+            .rules
+            .write()
+            .expect("rules lock is poisoned")
+            .remove(id);
+
+        let alerts = {
+            let mut active = self.active.write().expect("active lock is poisoned");
+            let keys = active
+                .keys()
+                .filter(|(rule_id, _)| rule_id == id)
+                .cloned()
+                .collect::<Vec<_>>();
+            keys.iter()
+                .filter_map(|key| active.remove(key))
+                .collect::<Vec<_>>()
+        };
+
+        for mut alert in alerts {
+            alert.state = AlertState::Resolved;
+            alert.resolved_at = Some(now);
+            self.resolve(alert);
+        }
+
+        Ok(rule)
+    }
+
+    async fn rules(&self) -> Result<Vec<Rule>, AppError> {
+        let mut rules = self
+            .rules
+            .read()
+            .expect("rules lock is poisoned")
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        rules.sort_by_key(|rule| rule.created_at);
+        Ok(rules)
+    }
+
+    async fn rules_by_chain(&self, chain: &Chain) -> Result<Vec<Rule>, AppError> {
+        Ok(self
+            .rules
+            .read()
+            .expect("rules lock is poisoned")
+            .values()
+            .filter(|rule| &rule.spec.chain == chain)
+            .cloned()
+            .collect())
+    }
+
+    async fn active(
+        &self,
+        rule_id: &str,
+        condition: &Condition,
+    ) -> Result<Option<Alert>, AppError> {
+        Ok(self
+            .active
+            .read()
+            .expect("active lock is poisoned")
+            .get(&(rule_id.to_string(), condition.clone()))
+            .cloned())
+    }
+
+    async fn upsert(&self, alert: Alert) -> Result<(), AppError> {
+        let key = (alert.rule_id.clone(), alert.condition.clone());
+
+        match alert.state {
+            AlertState::Active => {
+                self.active
+                    .write()
+                    .expect("active lock is poisoned")
+                    .insert(key, alert);
+            }
+            AlertState::Resolved => {
+                self.active
+                    .write()
+                    .expect("active lock is poisoned")
+                    .remove(&key);
+                self.resolve(alert);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn alerts(&self, state: Option<AlertState>) -> Result<Vec<Alert>, AppError> {
+        let mut alerts = vec![];
+
+        if state != Some(AlertState::Resolved) {
+            let mut active = self
+                .active
+                .read()
+                .expect("active lock is poisoned")
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+            active.sort_by_key(|alert| std::cmp::Reverse(alert.raised_at));
+            alerts.extend(active);
+        }
+
+        if state != Some(AlertState::Active) {
+            alerts.extend(
+                self.resolved
+                    .read()
+                    .expect("resolved lock is poisoned")
+                    .iter()
+                    .cloned(),
+            );
+        }
+
+        Ok(alerts)
+    }
+}
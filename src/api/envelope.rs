@@ -0,0 +1,22 @@
+use serde::Serialize;
+
+use crate::service::BlockRef;
+
+/// Response body together with the block the balances were read at
+#[derive(Debug, Serialize)]
+pub struct BlockEnvelope<T> {
+    #[serde(flatten)]
+    pub block: BlockRef,
+    #[serde(flatten)]
+    pub body: T,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceBody<T> {
+    pub balance: T,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalancesBody<T> {
+    pub balances: T,
+}
@@ -6,6 +6,8 @@ use std::{
     fmt::{Display, Formatter},
 };
 
+use crate::error::Error as AppError;
+
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
     #[serde(skip_serializing)]
@@ -24,20 +26,86 @@ impl ErrorResponse {
     ) -> Self {
         Self {
             status_code: StatusCode::BAD_REQUEST,
-            code: code,
+            code,
+            reason: reason.as_ref().to_string(),
+            details,
+        }
+    }
+
+    pub fn not_found(code: u16, reason: impl AsRef<str>) -> Self {
+        Self {
+            status_code: StatusCode::NOT_FOUND,
+            code,
+            reason: reason.as_ref().to_string(),
+            details: None,
+        }
+    }
+
+    pub fn conflict(code: u16, reason: impl AsRef<str>) -> Self {
+        Self {
+            status_code: StatusCode::CONFLICT,
+            code,
+            reason: reason.as_ref().to_string(),
+            details: None,
+        }
+    }
+
+    pub fn too_many_requests(code: u16, reason: impl AsRef<str>) -> Self {
+        Self {
+            status_code: StatusCode::TOO_MANY_REQUESTS,
+            code,
             reason: reason.as_ref().to_string(),
-            details: details,
+            details: None,
         }
     }
 
     pub fn internal_server_error(code: u16, reason: impl AsRef<str>) -> Self {
         Self {
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            code: code,
+            code,
             reason: reason.as_ref().to_string(),
             details: None,
         }
     }
+
+    pub fn service_unavailable(code: u16, reason: impl AsRef<str>) -> Self {
+        Self {
+            status_code: StatusCode::SERVICE_UNAVAILABLE,
+            code,
+            reason: reason.as_ref().to_string(),
+            details: None,
+        }
+    }
+
+    pub fn gateway_timeout(code: u16, reason: impl AsRef<str>) -> Self {
+        Self {
+            status_code: StatusCode::GATEWAY_TIMEOUT,
+            code,
+            reason: reason.as_ref().to_string(),
+            details: None,
+        }
+    }
+
+    /// Response to a failed read of balances from the node clients
+    pub fn upstream(e: &AppError) -> Self {
+        match e {
+            AppError::QuorumNotReached(answers) => {
+                let mut response = Self::conflict(20012, "Providers do not agree on the balances");
+                response.details = Some(
+                    answers
+                        .iter()
+                        .map(|answer| (answer.provider.clone(), answer.answer.clone()))
+                        .collect(),
+                );
+                response
+            }
+            AppError::CircuitOpen(reason) => Self::service_unavailable(20013, reason),
+            AppError::UpstreamTimeout(reason) => Self::gateway_timeout(20014, reason),
+            AppError::RateLimited(reason) => Self::too_many_requests(20015, reason),
+            AppError::Saturated(reason) => Self::service_unavailable(20016, reason),
+            e => Self::internal_server_error(20000, e.to_string()),
+        }
+    }
 }
 
 impl Display for ErrorResponse {
@@ -1,4 +1,5 @@
 pub mod config;
+mod envelope;
 mod error;
 mod routes;
 pub mod server;
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{api::error::ErrorResponse, service::BalancesService, subscriptions::Subscription};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Asset ids or CAIP-19 ids of the assets read in addition to the native token
+    #[serde(default, rename = "id")]
+    pub ids: Vec<String>,
+}
+
+/// Balances of a CAIP-10 account, e.g. `eip155:1:0xab16a96D359eC26a11e2C2b3d8f8B8942d5Bfcdb`
+#[tracing::instrument(skip(service))]
+#[get("/accounts/{account_id}/balances")]
+pub async fn handler(
+    path: web::Path<String>,
+    request: serde_qs::actix::QsQuery<Request>,
+    service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+) -> Result<impl Responder, impl ResponseError> {
+    let account_id = path.into_inner();
+
+    let (chain, address) = service
+        .caip()
+        .parse_account_id(&account_id)
+        .map_err(|e| ErrorResponse::bad_request(10010, e.to_string(), None))?;
+
+    let mut asset_ids = request
+        .ids
+        .iter()
+        .map(|id| service.caip().parse_asset(&chain, id))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ErrorResponse::bad_request(10010, e.to_string(), None))?;
+    asset_ids.sort();
+    asset_ids.dedup();
+
+    let not_supported_assets = service
+        .check_assets_support(chain.clone(), asset_ids.clone())
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?;
+
+    // the native token is always read
+    let native_token = service.caip().native_token(&chain);
+    let not_supported_assets = not_supported_assets
+        .into_iter()
+        .filter(|asset_id| Some(asset_id) != native_token.as_ref())
+        .collect::<Vec<_>>();
+
+    if !not_supported_assets.is_empty() {
+        let details = BTreeMap::from([(
+            "not_supported_assets".to_string(),
+            not_supported_assets.join(","),
+        )]);
+        return Err(ErrorResponse::bad_request(
+            20001,
+            "Requests contains not supported assets",
+            Some(details),
+        ));
+    }
+    asset_ids.retain(|asset_id| Some(asset_id) != native_token.as_ref());
+
+    let subscription = Subscription {
+        chain,
+        address,
+        asset_ids,
+    };
+
+    let balances = subscription
+        .fetch(service.as_ref().as_ref())
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::upstream(&e)
+        })?;
+
+    let response = HttpResponse::Ok().json(&balances);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+use tracing::error;
+
+use crate::{
+    alerts::{self, AlertStore, RuleSpec},
+    api::error::ErrorResponse,
+    service::BalancesService,
+};
+
+#[tracing::instrument(skip(service, store))]
+#[post("/alerts/rules")]
+pub async fn handler(
+    request: web::Json<RuleSpec>,
+    service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+    store: web::Data<dyn AlertStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let spec = request.into_inner();
+
+    if let Err(reason) = spec.validate() {
+        let details = BTreeMap::from([("limits".to_string(), reason.to_string())]);
+        return Err(ErrorResponse::bad_request(
+            10008,
+            "Invalid alert rule",
+            Some(details),
+        ));
+    }
+
+    // fails for chains without a node client
+    service
+        .check_assets_support(spec.chain.clone(), vec![])
+        .map_err(|e| ErrorResponse::bad_request(10002, e.to_string(), None))?;
+
+    let rule = alerts::Rule::new(spec);
+
+    store.insert_rule(rule.clone()).await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let response = HttpResponse::Created().json(&rule);
+
+    Ok(response)
+}
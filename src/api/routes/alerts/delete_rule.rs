@@ -0,0 +1,26 @@
+use actix_web::{delete, web, HttpResponse, Responder, ResponseError};
+use tracing::error;
+
+use crate::{alerts::AlertStore, api::error::ErrorResponse, clock};
+
+#[tracing::instrument(skip(store))]
+#[delete("/alerts/rules/{id}")]
+pub async fn handler(
+    path: web::Path<String>,
+    store: web::Data<dyn AlertStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let id = path.into_inner();
+
+    store
+        .remove_rule(&id, clock::now())
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?
+        .ok_or_else(|| ErrorResponse::not_found(20010, format!("Alert rule {} not found", id)))?;
+
+    let response = HttpResponse::NoContent().finish();
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
@@ -0,0 +1,33 @@
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    alerts::{AlertState, AlertStore},
+    api::error::ErrorResponse,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Both active and resolved alerts if omitted
+    pub state: Option<AlertState>,
+}
+
+#[tracing::instrument(skip(store))]
+#[get("/alerts")]
+pub async fn handler(
+    request: web::Query<Request>,
+    store: web::Data<dyn AlertStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let alerts = store
+        .alerts(request.into_inner().state)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?;
+
+    let response = HttpResponse::Ok().json(&alerts);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
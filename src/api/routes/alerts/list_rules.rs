@@ -0,0 +1,19 @@
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use tracing::error;
+
+use crate::{alerts::AlertStore, api::error::ErrorResponse};
+
+#[tracing::instrument(skip(store))]
+#[get("/alerts/rules")]
+pub async fn handler(
+    store: web::Data<dyn AlertStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let rules = store.rules().await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let response = HttpResponse::Ok().json(&rules);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
@@ -0,0 +1,4 @@
+pub mod create_rule;
+pub mod delete_rule;
+pub mod list;
+pub mod list_rules;
@@ -0,0 +1,19 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Serialize;
+
+use crate::attestation::{Attester, Domain, PublicKey};
+
+#[derive(Debug, Serialize)]
+pub struct Response<'a> {
+    pub domain: &'a Domain,
+    pub keys: Vec<PublicKey>,
+}
+
+#[tracing::instrument(skip(attester))]
+#[get("/attestation/keys")]
+pub async fn handler(attester: web::Data<Attester>) -> impl Responder {
+    HttpResponse::Ok().json(&Response {
+        domain: attester.domain(),
+        keys: attester.public_keys(),
+    })
+}
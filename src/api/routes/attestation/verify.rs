@@ -0,0 +1,38 @@
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::error::ErrorResponse,
+    attestation::{verifier, AttestationSignature, Attester, Statement},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub statement: Statement,
+    pub signature: AttestationSignature,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    /// Whether the signature of the statement is made by its signer
+    pub valid: bool,
+    /// Whether the signer is a key of this service
+    pub own: bool,
+}
+
+#[tracing::instrument(skip(attester))]
+#[post("/attestation/verify")]
+pub async fn handler(
+    request: web::Json<Request>,
+    attester: web::Data<Attester>,
+) -> Result<impl Responder, impl ResponseError> {
+    let valid = verifier::verify(attester.domain(), &request.statement, &request.signature)
+        .map_err(|e| ErrorResponse::bad_request(10015, e.to_string(), None))?;
+
+    let response = HttpResponse::Ok().json(&Response {
+        valid,
+        own: attester.is_own(&request.signature),
+    });
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
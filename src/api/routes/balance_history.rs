@@ -0,0 +1,102 @@
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    api::error::ErrorResponse,
+    clock::now,
+    history::{self, HistoryStore, Series},
+    node_clients::Chain,
+};
+
+const DEFAULT_RANGE: u64 = 24 * 60 * 60;
+
+const MAX_POINTS: u64 = 10_000;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Unix timestamp in seconds, `to` minus a day if omitted
+    pub from: Option<u64>,
+    /// Unix timestamp in seconds, now if omitted
+    pub to: Option<u64>,
+    /// Bucket size in seconds, every recorded change is returned if omitted
+    pub interval: Option<u64>,
+    #[serde(default, rename = "id")]
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub chain: Chain,
+    pub address: String,
+    pub from: u64,
+    pub to: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    pub series: Vec<Series>,
+}
+
+#[tracing::instrument(skip(store))]
+#[get("/balances/{chain}/{address}/history")]
+pub async fn handler(
+    path: web::Path<(String, String)>,
+    request: serde_qs::actix::QsQuery<Request>,
+    store: web::Data<dyn HistoryStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let (chain, address) = path.into_inner();
+
+    let chain = Chain::try_from(chain)
+        .map_err(|e| ErrorResponse::bad_request(10002, e.to_string(), None))?;
+
+    let to = request.to.unwrap_or_else(now);
+    let from = request
+        .from
+        .unwrap_or_else(|| to.saturating_sub(DEFAULT_RANGE));
+
+    if from > to {
+        return Err(ErrorResponse::bad_request(
+            10004,
+            "`from` must not be later than `to`",
+            None,
+        ));
+    }
+
+    match request.interval {
+        Some(0) => {
+            return Err(ErrorResponse::bad_request(
+                10004,
+                "`interval` must be positive",
+                None,
+            ))
+        }
+        Some(interval) if (to - from) / interval >= MAX_POINTS => {
+            return Err(ErrorResponse::bad_request(
+                10004,
+                format!(
+                    "`interval` is too small, at most {} points are allowed",
+                    MAX_POINTS
+                ),
+                None,
+            ))
+        }
+        _ => {}
+    }
+
+    let records = store.query(&chain, &address, from, to).await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let series = history::series(records, &request.ids, from, to, request.interval);
+
+    let response = HttpResponse::Ok().json(&Response {
+        chain,
+        address,
+        from,
+        to,
+        interval: request.interval,
+        series,
+    });
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::sync::watch;
+use tracing::error;
+
+use crate::{
+    api::error::ErrorResponse,
+    error::Error as AppError,
+    node_clients::Chain,
+    service::{Balance, BalancesService},
+    subscriptions::{self, ChangeTracker, Subscription},
+};
+
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    #[serde(rename = "id", default)]
+    pub ids: Vec<String>,
+}
+
+/// Streams native balance of the address followed by balances of the requested
+/// assets as Server-Sent Events whenever any of them changes
+///
+/// Event ids are block heights, so a client reconnecting with `Last-Event-ID`
+/// receives the current balances only if a newer block has been produced since
+#[tracing::instrument(skip(http_request, service))]
+#[get("/balances/{chain}/{address}/stream")]
+pub async fn handler(
+    http_request: HttpRequest,
+    path: web::Path<(String, String)>,
+    request: serde_qs::actix::QsQuery<Request>,
+    service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+) -> Result<impl Responder, impl ResponseError> {
+    let (chain, address) = path.into_inner();
+
+    let chain = Chain::try_from(chain)
+        .map_err(|e| ErrorResponse::bad_request(10002, e.to_string(), None))?;
+
+    let not_supported_assets = service
+        .check_assets_support(chain.clone(), request.ids.clone())
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?;
+
+    if !not_supported_assets.is_empty() {
+        let details = BTreeMap::from([(
+            "not_supported_assets".to_string(),
+            not_supported_assets.join(","),
+        )]);
+        return Err(ErrorResponse::bad_request(
+            20001,
+            "Requests contains not supported assets",
+            Some(details),
+        ));
+    }
+
+    let last_event_id = http_request
+        .headers()
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    let blocks = service.subscribe_blocks(chain.clone()).map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let subscription = Subscription {
+        chain,
+        address,
+        asset_ids: request.ids.clone(),
+    };
+
+    let (tx, rx) = mpsc::channel(1);
+    actix_web::rt::spawn(stream(
+        subscription,
+        last_event_id,
+        blocks,
+        service.into_inner(),
+        tx,
+    ));
+
+    let response = HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(rx.map(Ok::<_, actix_web::Error>));
+
+    Ok(response)
+}
+
+async fn stream(
+    subscription: Subscription,
+    last_event_id: Option<u64>,
+    blocks: watch::Receiver<u64>,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    mut tx: mpsc::Sender<web::Bytes>,
+) {
+    let service = service.as_ref().as_ref();
+    let mut tracker = ChangeTracker::default();
+
+    let current_height = Some(*blocks.borrow()).filter(|height| *height > 0);
+    let mut blocks = subscriptions::blocks_stream(blocks);
+
+    let up_to_date = matches!(
+        (last_event_id, current_height),
+        (Some(last_event_id), Some(current_height)) if current_height <= last_event_id
+    );
+    let initial = match subscription.fetch(service).await {
+        Ok(balances) if up_to_date => {
+            tracker.changed(balances);
+            None
+        }
+        Ok(balances) => Some(event(current_height, &tracker.changed(balances))),
+        Err(e) => Some(error_event(&e)),
+    };
+    if let Some(initial) = initial {
+        if tx.send(initial).await.is_err() {
+            return;
+        }
+    }
+
+    let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+    keep_alive.tick().await;
+
+    loop {
+        let message = tokio::select! {
+            height = blocks.next() => match height {
+                Some(height) => match subscription.fetch(service).await {
+                    Ok(balances) if tracker.changed(balances.clone()).is_empty() => continue,
+                    Ok(balances) => event(Some(height), &balances),
+                    Err(e) => error_event(&e),
+                },
+                None => return,
+            },
+            _ = keep_alive.tick() => web::Bytes::from_static(b": keep-alive\n\n"),
+        };
+
+        if tx.send(message).await.is_err() {
+            // client has gone
+            return;
+        }
+    }
+}
+
+fn event(height: Option<u64>, balances: &[Balance]) -> web::Bytes {
+    let data = serde_json::to_string(balances).expect("Failed to serialize balances");
+    match height {
+        Some(height) => format!("id: {}\ndata: {}\n\n", height, data).into(),
+        None => format!("data: {}\n\n", data).into(),
+    }
+}
+
+fn error_event(e: &AppError) -> web::Bytes {
+    error!("{}", e);
+    let response = ErrorResponse::internal_server_error(20000, e.to_string());
+    format!("event: error\ndata: {}\n\n", response).into()
+}
@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_ws::{Message, MessageStream, Session};
+use futures::stream::{BoxStream, SelectAll, StreamExt};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::{
+    api::error::ErrorResponse,
+    node_clients::Chain,
+    service::{Balance, BalancesService},
+    subscriptions::{self, ChangeTracker, Subscription},
+};
+
+const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 64;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe(Subscription),
+    Unsubscribe(Subscription),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Balances {
+        chain: Chain,
+        address: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        height: Option<u64>,
+        balances: Vec<Balance>,
+    },
+    Unsubscribed(&'a Subscription),
+    Error(ErrorResponse),
+}
+
+#[tracing::instrument(skip(body, service))]
+#[get("/balances/ws")]
+pub async fn handler(
+    request: HttpRequest,
+    body: web::Payload,
+    service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (response, session, messages) = actix_ws::handle(&request, body)?;
+
+    actix_web::rt::spawn(Connection::new(session, service.into_inner()).run(messages));
+
+    Ok(response)
+}
+
+/// State of a single WebSocket client
+struct Connection {
+    session: Session,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    subscriptions: HashMap<Subscription, ChangeTracker>,
+    watched_chains: HashSet<Chain>,
+    blocks: SelectAll<BoxStream<'static, (Chain, u64)>>,
+}
+
+impl Connection {
+    fn new(session: Session, service: Arc<Box<dyn BalancesService + Send + Sync>>) -> Self {
+        Self {
+            session,
+            service,
+            subscriptions: HashMap::new(),
+            watched_chains: HashSet::new(),
+            blocks: SelectAll::new(),
+        }
+    }
+
+    async fn run(mut self, mut messages: MessageStream) {
+        loop {
+            let result = tokio::select! {
+                message = messages.recv() => match message {
+                    Some(Ok(Message::Text(text))) => self.on_text(&text).await,
+                    Some(Ok(Message::Ping(bytes))) => self.session.pong(&bytes).await,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => Ok(()),
+                    Some(Err(e)) => {
+                        warn!("websocket protocol error: {}", e);
+                        break;
+                    }
+                },
+                Some((chain, height)) = self.blocks.next(), if !self.blocks.is_empty() => {
+                    self.on_block(chain, height).await
+                }
+            };
+
+            if result.is_err() {
+                // session is closed by the client
+                return;
+            }
+        }
+
+        let _ = self.session.close(None).await;
+    }
+
+    async fn on_text(&mut self, text: &str) -> Result<(), actix_ws::Closed> {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                return self
+                    .send(&ServerMessage::Error(ErrorResponse::bad_request(
+                        10000,
+                        e.to_string(),
+                        None,
+                    )))
+                    .await
+            }
+        };
+
+        match message {
+            ClientMessage::Subscribe(subscription) => self.subscribe(subscription).await,
+            ClientMessage::Unsubscribe(subscription) => {
+                self.subscriptions.remove(&subscription);
+                self.send(&ServerMessage::Unsubscribed(&subscription)).await
+            }
+        }
+    }
+
+    async fn subscribe(&mut self, subscription: Subscription) -> Result<(), actix_ws::Closed> {
+        if let Err(e) = self.validate(&subscription) {
+            return self.send(&ServerMessage::Error(e)).await;
+        }
+
+        if !self.watched_chains.contains(&subscription.chain) {
+            match self.service.subscribe_blocks(subscription.chain.clone()) {
+                Ok(blocks) => {
+                    let chain = subscription.chain.clone();
+                    self.blocks.push(
+                        subscriptions::blocks_stream(blocks)
+                            .map(move |height| (chain.clone(), height))
+                            .boxed(),
+                    );
+                    self.watched_chains.insert(subscription.chain.clone());
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    return self
+                        .send(&ServerMessage::Error(ErrorResponse::internal_server_error(
+                            20000,
+                            e.to_string(),
+                        )))
+                        .await;
+                }
+            }
+        }
+
+        self.subscriptions.entry(subscription.clone()).or_default();
+
+        let result = subscription.fetch(self.service.as_ref().as_ref()).await;
+        self.deliver(&subscription, None, result).await
+    }
+
+    fn validate(&self, subscription: &Subscription) -> Result<(), ErrorResponse> {
+        if !self.subscriptions.contains_key(subscription)
+            && self.subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_CONNECTION
+        {
+            return Err(ErrorResponse::bad_request(
+                20002,
+                "Too many subscriptions",
+                Some(BTreeMap::from([(
+                    "max_subscriptions".to_string(),
+                    MAX_SUBSCRIPTIONS_PER_CONNECTION.to_string(),
+                )])),
+            ));
+        }
+
+        let not_supported_assets = self
+            .service
+            .check_assets_support(subscription.chain.clone(), subscription.asset_ids.clone())
+            .map_err(|e| {
+                error!("{}", e);
+                ErrorResponse::internal_server_error(20000, e.to_string())
+            })?;
+
+        if !not_supported_assets.is_empty() {
+            let details = BTreeMap::from([(
+                "not_supported_assets".to_string(),
+                not_supported_assets.join(","),
+            )]);
+            return Err(ErrorResponse::bad_request(
+                20001,
+                "Requests contains not supported assets",
+                Some(details),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn on_block(&mut self, chain: Chain, height: u64) -> Result<(), actix_ws::Closed> {
+        let subscriptions = self
+            .subscriptions
+            .keys()
+            .filter(|subscription| subscription.chain == chain)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let service = self.service.clone();
+        let results = futures::future::join_all(
+            subscriptions
+                .iter()
+                .map(|subscription| subscription.fetch(service.as_ref().as_ref())),
+        )
+        .await;
+
+        for (subscription, result) in subscriptions.iter().zip(results) {
+            self.deliver(subscription, Some(height), result).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends balances of the `subscription` that changed since the last delivery
+    async fn deliver(
+        &mut self,
+        subscription: &Subscription,
+        height: Option<u64>,
+        result: Result<Vec<Balance>, crate::error::Error>,
+    ) -> Result<(), actix_ws::Closed> {
+        let balances = match result {
+            Ok(balances) => balances,
+            Err(e) => {
+                error!("{}", e);
+                let details = BTreeMap::from([
+                    ("chain".to_string(), String::from(&subscription.chain)),
+                    ("address".to_string(), subscription.address.clone()),
+                ]);
+                let mut response = ErrorResponse::internal_server_error(20000, e.to_string());
+                response.details = Some(details);
+                return self.send(&ServerMessage::Error(response)).await;
+            }
+        };
+
+        let changed = match self.subscriptions.get_mut(subscription) {
+            Some(tracker) => tracker.changed(balances),
+            // unsubscribed while balances were being fetched
+            None => return Ok(()),
+        };
+
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        self.send(&ServerMessage::Balances {
+            chain: subscription.chain.clone(),
+            address: &subscription.address,
+            height,
+            balances: changed,
+        })
+        .await
+    }
+
+    async fn send(&mut self, message: &ServerMessage<'_>) -> Result<(), actix_ws::Closed> {
+        let text = serde_json::to_string(message).expect("Failed to serialize ServerMessage");
+        self.session.text(text).await
+    }
+}
@@ -4,34 +4,78 @@ use actix_web::{get, web, HttpResponse, Responder, ResponseError};
 use serde::Deserialize;
 use tracing::error;
 
-use crate::{api::error::ErrorResponse, node_clients::Chain, service::BalancesService};
+use crate::{
+    api::envelope::{BalancesBody, BlockEnvelope},
+    api::error::ErrorResponse,
+    attestation::{AttestedBalance, Attester},
+    error::Error as AppError,
+    service::BalancesService,
+    valuation::{self, Currency},
+};
 
 #[derive(Debug, Deserialize)]
 pub struct Request {
-    #[serde(rename = "id")]
+    /// Asset ids or CAIP-19 ids of the assets
+    #[serde(default, rename = "id")]
     pub ids: Vec<String>,
+    /// Values the balances by the oracle prices, the response is a portfolio then
+    #[serde(default)]
+    pub valuation: Option<Currency>,
+    /// `native` prepends the native balance read at the same block as the assets
+    #[serde(default)]
+    pub include: Option<Include>,
+    /// Reads the balances at the latest block and returns the block along with them
+    #[serde(default)]
+    pub with_block: bool,
+    /// Proves the balances by the state root of the latest block, implies `with_block`
+    #[serde(default)]
+    pub verified: bool,
+    /// Signs the balances along with the block they were read at, implies `with_block`
+    #[serde(default)]
+    pub attest: bool,
 }
 
-#[tracing::instrument(skip(service))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Include {
+    Native,
+}
+
+#[tracing::instrument(skip(service, attester))]
 #[get("/balances/{chain}/{address}/assets")]
 pub async fn handler(
     path: web::Path<(String, String)>,
     request: serde_qs::actix::QsQuery<Request>,
     service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+    attester: Option<web::Data<Attester>>,
 ) -> Result<impl Responder, impl ResponseError> {
     let (chain, address) = path.into_inner();
 
-    let chain = Chain::try_from(chain)
+    let chain = service
+        .caip()
+        .parse_chain(&chain)
         .map_err(|e| ErrorResponse::bad_request(10002, e.to_string(), None))?;
 
+    let address = service
+        .caip()
+        .parse_account(&chain, &address)
+        .map_err(|e| ErrorResponse::bad_request(10010, e.to_string(), None))?;
+
+    let asset_ids = request
+        .ids
+        .iter()
+        .map(|id| service.caip().parse_asset(&chain, id))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ErrorResponse::bad_request(10010, e.to_string(), None))?;
+
     let not_supported_assets = service
-        .check_assets_support(chain.clone(), request.ids.clone())
+        .check_assets_support(chain.clone(), asset_ids.clone())
         .map_err(|e| {
             error!("{}", e);
             ErrorResponse::internal_server_error(20000, e.to_string())
         })?;
 
-    if not_supported_assets.len() > 0 {
+    if !not_supported_assets.is_empty() {
         let details = BTreeMap::from([(
             "not_supported_assets".to_string(),
             not_supported_assets.join(","),
@@ -43,15 +87,143 @@ pub async fn handler(
         ));
     }
 
-    let balance = service
-        .get_assets_balances(chain, address, request.ids.clone())
-        .await
-        .map_err(|e| {
-            error!("{}", e);
-            ErrorResponse::internal_server_error(20000, e.to_string())
-        })?;
+    let native = request.include == Some(Include::Native);
+
+    let attester = match (request.attest, attester) {
+        (false, _) => None,
+        (true, None) => {
+            return Err(ErrorResponse::bad_request(
+                10013,
+                "Attestation is not configured",
+                None,
+            ))
+        }
+        (true, Some(_)) if request.valuation.is_some() => {
+            return Err(ErrorResponse::bad_request(
+                10014,
+                "Attested balances can not be valued",
+                None,
+            ))
+        }
+        (true, Some(attester)) => Some(attester),
+    };
+
+    if request.verified {
+        if request.valuation.is_some() {
+            return Err(ErrorResponse::bad_request(
+                10011,
+                "Verified balances can not be valued",
+                None,
+            ));
+        }
+
+        let verified = service
+            .get_verified_balances(chain.clone(), address.clone(), asset_ids, native)
+            .await
+            .map_err(|e| match e {
+                AppError::VerificationNotSupported => ErrorResponse::bad_request(
+                    10012,
+                    format!("{} balances can not be verified", chain),
+                    None,
+                ),
+                AppError::InvalidProof(_) => {
+                    error!("{}", e);
+                    ErrorResponse::internal_server_error(20011, e.to_string())
+                }
+                e => {
+                    error!("{}", e);
+                    ErrorResponse::upstream(&e)
+                }
+            })?;
+
+        let response = match attester {
+            Some(attester) => {
+                let balances = verified
+                    .balances
+                    .into_iter()
+                    .map(|balance| AttestedBalance {
+                        attestation: attester.attest(
+                            &chain,
+                            &address,
+                            &verified.block,
+                            &balance.balance,
+                        ),
+                        balance,
+                    })
+                    .collect::<Vec<_>>();
+
+                HttpResponse::Ok().json(&BlockEnvelope {
+                    block: verified.block,
+                    body: BalancesBody { balances },
+                })
+            }
+            None => HttpResponse::Ok().json(&BlockEnvelope {
+                block: verified.block,
+                body: BalancesBody {
+                    balances: verified.balances,
+                },
+            }),
+        };
+
+        return Ok(response);
+    }
+
+    let (block, balance) = match (request.with_block || attester.is_some(), native) {
+        (true, native) => service
+            .get_pinned_balances(chain.clone(), address.clone(), asset_ids, native)
+            .await
+            .map(|pinned| (Some(pinned.block), pinned.balances)),
+        (false, true) => service
+            .get_all_balances(chain.clone(), address.clone(), asset_ids)
+            .await
+            .map(|balances| (None, balances)),
+        (false, false) => service
+            .get_assets_balances(chain.clone(), address.clone(), asset_ids)
+            .await
+            .map(|balances| (None, balances)),
+    }
+    .map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::upstream(&e)
+    })?;
+
+    if let (Some(attester), Some(block)) = (attester, &block) {
+        let balances = balance
+            .into_iter()
+            .map(|balance| AttestedBalance {
+                attestation: attester.attest(&chain, &address, block, &balance),
+                balance,
+            })
+            .collect::<Vec<_>>();
+
+        return Ok(HttpResponse::Ok().json(&BlockEnvelope {
+            block: block.clone(),
+            body: BalancesBody { balances },
+        }));
+    }
 
-    let response = HttpResponse::Ok().json(&balance);
+    let response = match (request.valuation, block) {
+        (Some(currency), block) => {
+            let portfolio = valuation::value(service.as_ref().as_ref(), chain, balance, currency)
+                .await
+                .map_err(|e| {
+                    error!("{}", e);
+                    ErrorResponse::internal_server_error(20000, e.to_string())
+                })?;
+            match block {
+                Some(block) => HttpResponse::Ok().json(&BlockEnvelope {
+                    block,
+                    body: portfolio,
+                }),
+                None => HttpResponse::Ok().json(&portfolio),
+            }
+        }
+        (None, Some(block)) => HttpResponse::Ok().json(&BlockEnvelope {
+            block,
+            body: BalancesBody { balances: balance },
+        }),
+        (None, None) => HttpResponse::Ok().json(&balance),
+    };
 
     Ok(response)
 }
@@ -1,2 +1,12 @@
+pub mod account_balances;
+pub mod alerts;
+pub mod attestation;
+pub mod balance_history;
+pub mod balances_stream;
+pub mod balances_subscriptions;
 pub mod custom_asset_balances;
 pub mod native_asset_balances;
+pub mod portfolio;
+pub mod reconciliations;
+pub mod snapshots;
+pub mod watches;
@@ -1,25 +1,124 @@
 use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
 use tracing::error;
 
-use crate::{api::error::ErrorResponse, node_clients::Chain, service::BalancesService};
+use crate::{
+    api::envelope::{BalanceBody, BlockEnvelope},
+    api::error::ErrorResponse,
+    attestation::{AttestedBalance, Attester},
+    error::Error as AppError,
+    service::BalancesService,
+    valuation::{self, Currency},
+};
 
-#[tracing::instrument(skip(service))]
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Values the balance by the oracle price, the response is a portfolio then
+    #[serde(default)]
+    pub valuation: Option<Currency>,
+    /// Reads the balance at the latest block and returns the block along with it
+    #[serde(default)]
+    pub with_block: bool,
+    /// Signs the balance along with the block it was read at, implies `with_block`
+    #[serde(default)]
+    pub attest: bool,
+}
+
+#[tracing::instrument(skip(service, attester))]
 #[get("/balances/{chain}/{address}")]
 pub async fn handler(
     path: web::Path<(String, String)>,
+    request: web::Query<Request>,
     service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+    attester: Option<web::Data<Attester>>,
 ) -> Result<impl Responder, impl ResponseError> {
     let (chain, address) = path.into_inner();
 
-    let chain = Chain::try_from(chain)
+    let chain = service
+        .caip()
+        .parse_chain(&chain)
         .map_err(|e| ErrorResponse::bad_request(10002, e.to_string(), None))?;
 
-    let balance = service.get_balance(chain, address).await.map_err(|e| {
+    let address = service
+        .caip()
+        .parse_account(&chain, &address)
+        .map_err(|e| ErrorResponse::bad_request(10010, e.to_string(), None))?;
+
+    let attester = match (request.attest, attester) {
+        (false, _) => None,
+        (true, None) => {
+            return Err(ErrorResponse::bad_request(
+                10013,
+                "Attestation is not configured",
+                None,
+            ))
+        }
+        (true, Some(_)) if request.valuation.is_some() => {
+            return Err(ErrorResponse::bad_request(
+                10014,
+                "Attested balances can not be valued",
+                None,
+            ))
+        }
+        (true, Some(attester)) => Some(attester),
+    };
+
+    let (block, balance) = if request.with_block || attester.is_some() {
+        service
+            .get_pinned_balances(chain.clone(), address.clone(), vec![], true)
+            .await
+            .and_then(|pinned| {
+                let balance = pinned.balances.into_iter().next().ok_or_else(|| {
+                    AppError::UpstreamResponse("native balance is missing".to_string())
+                })?;
+                Ok((Some(pinned.block), balance))
+            })
+    } else {
+        service
+            .get_balance(chain.clone(), address.clone())
+            .await
+            .map(|balance| (None, balance))
+    }
+    .map_err(|e| {
         error!("{}", e);
-        ErrorResponse::internal_server_error(20000, e.to_string())
+        ErrorResponse::upstream(&e)
     })?;
 
-    let response = HttpResponse::Ok().json(&balance);
+    if let (Some(attester), Some(block)) = (attester, &block) {
+        let balance = AttestedBalance {
+            attestation: attester.attest(&chain, &address, block, &balance),
+            balance,
+        };
+
+        return Ok(HttpResponse::Ok().json(&BlockEnvelope {
+            block: block.clone(),
+            body: BalanceBody { balance },
+        }));
+    }
+
+    let response = match (request.valuation, block) {
+        (Some(currency), block) => {
+            let portfolio =
+                valuation::value(service.as_ref().as_ref(), chain, vec![balance], currency)
+                    .await
+                    .map_err(|e| {
+                        error!("{}", e);
+                        ErrorResponse::internal_server_error(20000, e.to_string())
+                    })?;
+            match block {
+                Some(block) => HttpResponse::Ok().json(&BlockEnvelope {
+                    block,
+                    body: portfolio,
+                }),
+                None => HttpResponse::Ok().json(&portfolio),
+            }
+        }
+        (None, Some(block)) => HttpResponse::Ok().json(&BlockEnvelope {
+            block,
+            body: BalanceBody { balance },
+        }),
+        (None, None) => HttpResponse::Ok().json(&balance),
+    };
 
     Result::<HttpResponse, ErrorResponse>::Ok(response)
 }
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    api::error::ErrorResponse, portfolio, registry::Registry, service::BalancesService,
+    valuation::Currency,
+};
+
+const MAX_ADDRESSES: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Chain -> addresses
+    pub addresses: BTreeMap<String, Vec<String>>,
+    /// Chain -> asset ids read in addition to the native token
+    #[serde(default)]
+    pub asset_ids: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub valuation: Option<Currency>,
+}
+
+#[tracing::instrument(skip(request, service, registry))]
+#[post("/portfolio")]
+pub async fn handler(
+    request: web::Json<Request>,
+    service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+    registry: web::Data<Registry>,
+) -> Result<impl Responder, impl ResponseError> {
+    let request = request.into_inner();
+
+    if request.addresses.values().map(Vec::len).sum::<usize>() > MAX_ADDRESSES {
+        let details = BTreeMap::from([(
+            "addresses".to_string(),
+            format!("must contain at most {} items", MAX_ADDRESSES),
+        )]);
+        return Err(ErrorResponse::bad_request(
+            10009,
+            "Too many addresses",
+            Some(details),
+        ));
+    }
+
+    let portfolio = portfolio::portfolio(
+        service.as_ref().as_ref(),
+        &registry,
+        request.addresses,
+        &request.asset_ids,
+        request.valuation,
+    )
+    .await
+    .map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let response = HttpResponse::Ok().json(&portfolio);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
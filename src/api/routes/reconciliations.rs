@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+
+use crate::{
+    api::error::ErrorResponse,
+    reconciliation::{self, Expectation},
+    service::BalancesService,
+};
+
+const MAX_EXPECTATIONS: usize = 10_000;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub expectations: Vec<Expectation>,
+    /// Asset id -> maximum absolute difference still considered a match
+    #[serde(default)]
+    pub tolerances: BTreeMap<String, u64>,
+}
+
+#[tracing::instrument(skip(request, service))]
+#[post("/reconciliations")]
+pub async fn handler(
+    request: web::Json<Request>,
+    service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+) -> Result<impl Responder, impl ResponseError> {
+    let request = request.into_inner();
+
+    if request.expectations.len() > MAX_EXPECTATIONS {
+        let details = BTreeMap::from([(
+            "expectations".to_string(),
+            format!("must contain at most {} items", MAX_EXPECTATIONS),
+        )]);
+        return Err(ErrorResponse::bad_request(
+            10007,
+            "Too many expected balances",
+            Some(details),
+        ));
+    }
+
+    let report = reconciliation::reconcile(
+        service.as_ref().as_ref(),
+        request.expectations,
+        &request.tolerances,
+    )
+    .await;
+
+    let response = HttpResponse::Ok().json(&report);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
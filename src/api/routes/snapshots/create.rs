@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    api::error::ErrorResponse,
+    node_clients::Chain,
+    service::BalancesService,
+    snapshots::{Snapshots, Spec},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub chain: String,
+    #[serde(default)]
+    pub native: bool,
+    #[serde(default)]
+    pub asset_ids: Vec<String>,
+    pub addresses: Vec<String>,
+    #[serde(default, alias = "block")]
+    pub height: Option<u64>,
+}
+
+#[tracing::instrument(skip(request, service, snapshots))]
+#[post("/snapshots")]
+pub async fn handler(
+    request: web::Json<Request>,
+    service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<impl Responder, impl ResponseError> {
+    let request = request.into_inner();
+
+    let chain = Chain::try_from(request.chain)
+        .map_err(|e| ErrorResponse::bad_request(10002, e.to_string(), None))?;
+
+    let invalid_spec = |field: &str, description: String| {
+        let details = BTreeMap::from([(field.to_string(), description)]);
+        ErrorResponse::bad_request(10005, "Invalid snapshot spec", Some(details))
+    };
+
+    if request.addresses.is_empty() {
+        return Err(invalid_spec("addresses", "must not be empty".to_string()));
+    }
+    if request.addresses.len() > snapshots.config().max_addresses {
+        return Err(invalid_spec(
+            "addresses",
+            format!(
+                "must contain at most {} addresses",
+                snapshots.config().max_addresses
+            ),
+        ));
+    }
+    if !request.native && request.asset_ids.is_empty() {
+        return Err(invalid_spec(
+            "asset_ids",
+            "must not be empty unless native balances are requested".to_string(),
+        ));
+    }
+
+    let not_supported_assets = service
+        .check_assets_support(chain.clone(), request.asset_ids.clone())
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?;
+
+    if !not_supported_assets.is_empty() {
+        let details = BTreeMap::from([(
+            "not_supported_assets".to_string(),
+            not_supported_assets.join(","),
+        )]);
+        return Err(ErrorResponse::bad_request(
+            20001,
+            "Requests contains not supported assets",
+            Some(details),
+        ));
+    }
+
+    let supports_balances_at = service.supports_balances_at(chain.clone()).map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let height = match request.height {
+        Some(_) if !supports_balances_at => {
+            return Err(invalid_spec(
+                "height",
+                format!("balances at past blocks of {} are not available", chain),
+            ))
+        }
+        Some(height) => Some(height),
+        // pin the snapshot to the latest block when the chain allows
+        None if supports_balances_at => {
+            let block = service.get_block(chain.clone(), None).await.map_err(|e| {
+                error!("{}", e);
+                ErrorResponse::service_unavailable(
+                    20017,
+                    format!("Latest block of {} is not available: {}", chain, e),
+                )
+            })?;
+            Some(block.block_number)
+        }
+        None => None,
+    };
+
+    let pending = snapshots.pending().await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    if pending >= snapshots.config().max_pending_jobs {
+        return Err(ErrorResponse::too_many_requests(
+            20007,
+            "Too many pending snapshot jobs",
+        ));
+    }
+
+    let spec = Spec {
+        chain,
+        native: request.native,
+        asset_ids: request.asset_ids,
+        addresses: request.addresses,
+        height,
+    };
+
+    let job = snapshots.submit(spec).await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let response = HttpResponse::Accepted().json(&job);
+
+    Ok(response)
+}
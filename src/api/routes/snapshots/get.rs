@@ -0,0 +1,26 @@
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use tracing::error;
+
+use crate::{api::error::ErrorResponse, snapshots::Snapshots};
+
+#[tracing::instrument(skip(snapshots))]
+#[get("/snapshots/{id}")]
+pub async fn handler(
+    path: web::Path<String>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<impl Responder, impl ResponseError> {
+    let id = path.into_inner();
+
+    let job = snapshots
+        .get(&id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?
+        .ok_or_else(|| ErrorResponse::not_found(20005, format!("Snapshot {} not found", id)))?;
+
+    let response = HttpResponse::Ok().json(&job);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
@@ -0,0 +1,61 @@
+use std::str::FromStr;
+
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use ethers_core::types::Address;
+use serde::Serialize;
+
+use super::merkle_root::{load_tree, to_hex, Request};
+use crate::{api::error::ErrorResponse, snapshots::Snapshots};
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub address: String,
+    pub asset_id: String,
+    pub amount: u64,
+    pub leaf: String,
+    pub proof: Vec<String>,
+    pub root: String,
+}
+
+#[tracing::instrument(skip(snapshots))]
+#[get("/snapshots/{id}/merkle/{address}")]
+pub async fn handler(
+    path: web::Path<(String, String)>,
+    request: web::Query<Request>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<impl Responder, impl ResponseError> {
+    let (id, address) = path.into_inner();
+    let request = request.into_inner();
+
+    let parsed_address = Address::from_str(&address)
+        .map_err(|e| ErrorResponse::bad_request(10006, e.to_string(), None))?;
+
+    let tree = load_tree(&snapshots, &id, &request).await?;
+
+    let claim = tree
+        .claims
+        .iter()
+        .find(|claim| claim.address == parsed_address)
+        .ok_or_else(|| {
+            ErrorResponse::not_found(
+                20009,
+                format!("Address {} has no claim in snapshot {}", address, id),
+            )
+        })?;
+
+    let proof = tree
+        .tree
+        .proof(&claim.leaf)
+        .expect("leaf of the claim is in the tree");
+
+    let response = HttpResponse::Ok().json(&Response {
+        address,
+        asset_id: tree.asset_id.clone(),
+        amount: claim.amount,
+        leaf: to_hex(&claim.leaf),
+        proof: proof.iter().map(to_hex).collect(),
+        root: to_hex(&tree.tree.root()),
+    });
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
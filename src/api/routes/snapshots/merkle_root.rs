@@ -0,0 +1,165 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use rustc_hex::ToHex;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    api::error::ErrorResponse,
+    service::BalanceKind,
+    snapshots::{
+        merkle::{self, ClaimsTree, LeafEncoding, MerkleTree, TreeKey},
+        JobStatus, Snapshots,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// May be omitted if the snapshot holds balances of a single asset
+    pub asset_id: Option<String>,
+    /// The first reported balance kind of the asset if omitted
+    pub kind: Option<BalanceKind>,
+    #[serde(default)]
+    pub encoding: LeafEncoding,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub snapshot_id: String,
+    pub asset_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<BalanceKind>,
+    pub encoding: LeafEncoding,
+    pub root: String,
+    pub leaves: usize,
+}
+
+#[tracing::instrument(skip(snapshots))]
+#[get("/snapshots/{id}/merkle")]
+pub async fn handler(
+    path: web::Path<String>,
+    request: web::Query<Request>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<impl Responder, impl ResponseError> {
+    let id = path.into_inner();
+    let request = request.into_inner();
+
+    let tree = load_tree(&snapshots, &id, &request).await?;
+
+    let response = HttpResponse::Ok().json(&Response {
+        snapshot_id: id,
+        asset_id: tree.asset_id.clone(),
+        kind: request.kind,
+        encoding: request.encoding,
+        root: to_hex(&tree.tree.root()),
+        leaves: tree.claims.len(),
+    });
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
+
+/// Reads the (address, amount) pairs of a completed snapshot without failed entries
+/// and builds their tree, unless it is cached
+pub(super) async fn load_tree(
+    snapshots: &Snapshots,
+    id: &str,
+    request: &Request,
+) -> Result<Arc<ClaimsTree>, ErrorResponse> {
+    let key = TreeKey {
+        snapshot_id: id.to_string(),
+        asset_id: request.asset_id.clone(),
+        kind: request.kind.clone(),
+        encoding: request.encoding.clone(),
+    };
+    if let Some(tree) = snapshots.trees().get(&key) {
+        return Ok(tree);
+    }
+
+    let job = snapshots
+        .get(id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?
+        .ok_or_else(|| ErrorResponse::not_found(20005, format!("Snapshot {} not found", id)))?;
+
+    if job.status != JobStatus::Completed {
+        return Err(ErrorResponse::conflict(
+            20006,
+            format!("Snapshot {} is not completed", id),
+        ));
+    }
+
+    if job.failed > 0 {
+        return Err(ErrorResponse::conflict(
+            20008,
+            format!(
+                "Snapshot {} has {} addresses which balances were not read",
+                id, job.failed
+            ),
+        ));
+    }
+
+    let entries = snapshots.entries(id).await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let asset_ids = entries
+        .iter()
+        .flat_map(|entry| entry.balances.iter().flatten())
+        .map(|balance| balance.asset_id.as_str())
+        .collect::<BTreeSet<_>>();
+
+    let asset_id = match &request.asset_id {
+        Some(asset_id) if asset_ids.contains(asset_id.as_str()) => asset_id.clone(),
+        None if asset_ids.len() == 1 => asset_ids
+            .iter()
+            .next()
+            .expect("there is exactly one asset")
+            .to_string(),
+        _ => {
+            let details = BTreeMap::from([(
+                "asset_id".to_string(),
+                format!(
+                    "must be one of the snapshot assets: {}",
+                    asset_ids.into_iter().collect::<Vec<_>>().join(",")
+                ),
+            )]);
+            return Err(ErrorResponse::bad_request(
+                10006,
+                "Invalid Merkle tree parameters",
+                Some(details),
+            ));
+        }
+    };
+
+    let claims = merkle::claims(
+        &entries,
+        &asset_id,
+        request.kind.as_ref(),
+        &request.encoding,
+    )
+    .map_err(|e| {
+        let details = BTreeMap::from([("addresses".to_string(), e.to_string())]);
+        ErrorResponse::bad_request(10006, "Invalid Merkle tree parameters", Some(details))
+    })?;
+
+    let tree = MerkleTree::new(claims.iter().map(|claim| claim.leaf).collect());
+
+    Ok(snapshots.trees().insert(
+        key,
+        ClaimsTree {
+            asset_id,
+            claims,
+            tree,
+        },
+    ))
+}
+
+pub(super) fn to_hex(hash: &[u8; 32]) -> String {
+    format!("0x{}", hash.to_hex::<String>())
+}
@@ -0,0 +1,5 @@
+pub mod create;
+pub mod get;
+pub mod merkle_proof;
+pub mod merkle_root;
+pub mod results;
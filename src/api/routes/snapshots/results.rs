@@ -0,0 +1,137 @@
+use actix_web::{get, http::header, web, HttpResponse, Responder, ResponseError};
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    api::error::ErrorResponse,
+    snapshots::{Entry, JobStatus, Snapshots},
+};
+
+/// Entries written at once to the streamed body
+const CHUNK_ENTRIES: usize = 500;
+
+const CSV_HEADER: &str = "address,asset_id,kind,amount,error\n";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Csv,
+    #[default]
+    Jsonl,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub format: Format,
+}
+
+#[tracing::instrument(skip(snapshots))]
+#[get("/snapshots/{id}/results")]
+pub async fn handler(
+    path: web::Path<String>,
+    request: web::Query<Request>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<impl Responder, impl ResponseError> {
+    let id = path.into_inner();
+
+    let job = snapshots
+        .get(&id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?
+        .ok_or_else(|| ErrorResponse::not_found(20005, format!("Snapshot {} not found", id)))?;
+
+    if job.status != JobStatus::Completed {
+        return Err(ErrorResponse::conflict(
+            20006,
+            format!("Snapshot {} is not completed", id),
+        ));
+    }
+
+    let entries = snapshots.entries(&id).await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let format = request.format;
+    let (content_type, extension, header) = match format {
+        Format::Csv => ("text/csv", "csv", Some(CSV_HEADER)),
+        Format::Jsonl => ("application/x-ndjson", "jsonl", None),
+    };
+
+    let rows = stream::iter(entries)
+        .chunks(CHUNK_ENTRIES)
+        .map(move |entries| match format {
+            Format::Csv => Ok(to_csv(&entries)),
+            Format::Jsonl => to_jsonl(&entries).map_err(|e| {
+                error!("{}", e);
+                ErrorResponse::internal_server_error(20000, e.to_string())
+            }),
+        });
+    let body = stream::iter(header.map(|header| Ok(header.to_string())))
+        .chain(rows)
+        .map(|chunk| chunk.map(web::Bytes::from).map_err(actix_web::Error::from));
+
+    let response = HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"snapshot-{}.{}\"", id, extension),
+        ))
+        .streaming(body);
+
+    Ok(response)
+}
+
+/// One row per balance amount, failed addresses have only the error filled
+fn to_csv(entries: &[Entry]) -> String {
+    let mut csv = String::new();
+
+    for entry in entries {
+        match (&entry.balances, &entry.error) {
+            (Some(balances), _) => {
+                for balance in balances {
+                    for amount in &balance.balances {
+                        let kind = serde_json::to_value(&amount.kind)
+                            .ok()
+                            .and_then(|kind| kind.as_str().map(str::to_string))
+                            .unwrap_or_default();
+                        csv.push_str(&format!(
+                            "{},{},{},{},\n",
+                            csv_field(&entry.address),
+                            csv_field(&balance.asset_id),
+                            kind,
+                            amount.amount
+                        ));
+                    }
+                }
+            }
+            (None, error) => csv.push_str(&format!(
+                "{},,,,{}\n",
+                csv_field(&entry.address),
+                csv_field(error.as_deref().unwrap_or_default())
+            )),
+        }
+    }
+
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_jsonl(entries: &[Entry]) -> Result<String, serde_json::Error> {
+    entries
+        .iter()
+        .map(|entry| serde_json::to_string(entry).map(|line| line + "\n"))
+        .collect()
+}
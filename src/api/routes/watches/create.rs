@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    api::error::ErrorResponse,
+    clock,
+    error::Error as AppError,
+    node_clients::Chain,
+    service::BalancesService,
+    webhooks::{CallbackPolicy, Threshold, Watch, WatchlistStore},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub chain: String,
+    pub address: String,
+    #[serde(default)]
+    pub asset_ids: Vec<String>,
+    pub callback_url: String,
+    #[serde(default = "default_notify_on_change")]
+    pub notify_on_change: bool,
+    #[serde(default)]
+    pub thresholds: Vec<Threshold>,
+}
+
+fn default_notify_on_change() -> bool {
+    true
+}
+
+#[tracing::instrument(skip(service, store, callbacks))]
+#[post("/watches")]
+pub async fn handler(
+    request: web::Json<Request>,
+    service: web::Data<Box<dyn BalancesService + Send + Sync>>,
+    store: web::Data<dyn WatchlistStore + Send + Sync>,
+    callbacks: web::Data<CallbackPolicy>,
+) -> Result<impl Responder, impl ResponseError> {
+    let request = request.into_inner();
+
+    let chain = Chain::try_from(request.chain)
+        .map_err(|e| ErrorResponse::bad_request(10002, e.to_string(), None))?;
+
+    if let Err(e) = callbacks.check(&request.callback_url).await {
+        let reason = match e {
+            AppError::InvalidCallbackUrl(reason) => reason,
+            e => e.to_string(),
+        };
+        let details = BTreeMap::from([("callback_url".to_string(), reason)]);
+        return Err(ErrorResponse::bad_request(
+            10003,
+            "Invalid callback url",
+            Some(details),
+        ));
+    }
+
+    let not_supported_assets = service
+        .check_assets_support(chain.clone(), request.asset_ids.clone())
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?;
+
+    if !not_supported_assets.is_empty() {
+        let details = BTreeMap::from([(
+            "not_supported_assets".to_string(),
+            not_supported_assets.join(","),
+        )]);
+        return Err(ErrorResponse::bad_request(
+            20001,
+            "Requests contains not supported assets",
+            Some(details),
+        ));
+    }
+
+    let watch = Watch {
+        id: uuid::Uuid::new_v4().to_string(),
+        chain,
+        address: request.address,
+        asset_ids: request.asset_ids,
+        callback_url: request.callback_url,
+        notify_on_change: request.notify_on_change,
+        thresholds: request.thresholds,
+        created_at: clock::now(),
+    };
+
+    store.insert(watch.clone()).await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let response = HttpResponse::Created().json(&watch);
+
+    Ok(response)
+}
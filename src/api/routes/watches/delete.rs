@@ -0,0 +1,26 @@
+use actix_web::{delete, web, HttpResponse, Responder, ResponseError};
+use tracing::error;
+
+use crate::{api::error::ErrorResponse, webhooks::WatchlistStore};
+
+#[tracing::instrument(skip(store))]
+#[delete("/watches/{id}")]
+pub async fn handler(
+    path: web::Path<String>,
+    store: web::Data<dyn WatchlistStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let id = path.into_inner();
+
+    store
+        .remove(&id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?
+        .ok_or_else(|| ErrorResponse::not_found(20004, format!("Watch {} not found", id)))?;
+
+    let response = HttpResponse::NoContent().finish();
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
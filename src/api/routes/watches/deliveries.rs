@@ -0,0 +1,30 @@
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use tracing::error;
+
+use crate::{api::error::ErrorResponse, webhooks::WatchlistStore};
+
+#[tracing::instrument(skip(store))]
+#[get("/watches/{id}/deliveries")]
+pub async fn handler(
+    path: web::Path<String>,
+    store: web::Data<dyn WatchlistStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let id = path.into_inner();
+
+    let internal_error = |e: crate::error::Error| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    };
+
+    store
+        .get(&id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ErrorResponse::not_found(20004, format!("Watch {} not found", id)))?;
+
+    let deliveries = store.deliveries(&id).await.map_err(internal_error)?;
+
+    let response = HttpResponse::Ok().json(&deliveries);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
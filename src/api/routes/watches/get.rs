@@ -0,0 +1,26 @@
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use tracing::error;
+
+use crate::{api::error::ErrorResponse, webhooks::WatchlistStore};
+
+#[tracing::instrument(skip(store))]
+#[get("/watches/{id}")]
+pub async fn handler(
+    path: web::Path<String>,
+    store: web::Data<dyn WatchlistStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let id = path.into_inner();
+
+    let watch = store
+        .get(&id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            ErrorResponse::internal_server_error(20000, e.to_string())
+        })?
+        .ok_or_else(|| ErrorResponse::not_found(20004, format!("Watch {} not found", id)))?;
+
+    let response = HttpResponse::Ok().json(&watch);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
@@ -0,0 +1,19 @@
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use tracing::error;
+
+use crate::{api::error::ErrorResponse, webhooks::WatchlistStore};
+
+#[tracing::instrument(skip(store))]
+#[get("/watches")]
+pub async fn handler(
+    store: web::Data<dyn WatchlistStore + Send + Sync>,
+) -> Result<impl Responder, impl ResponseError> {
+    let watches = store.list().await.map_err(|e| {
+        error!("{}", e);
+        ErrorResponse::internal_server_error(20000, e.to_string())
+    })?;
+
+    let response = HttpResponse::Ok().json(&watches);
+
+    Result::<HttpResponse, ErrorResponse>::Ok(response)
+}
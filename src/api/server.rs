@@ -11,25 +11,88 @@ use tracing::info;
 use tracing_actix_web::TracingLogger;
 
 use super::{config::Config, error::ErrorResponse, routes};
-use crate::{error::Error as AppError, service::BalancesService};
+use crate::{
+    alerts::AlertStore,
+    attestation::Attester,
+    error::Error as AppError,
+    history::HistoryStore,
+    registry::Registry,
+    service::BalancesService,
+    snapshots::Snapshots,
+    webhooks::{CallbackPolicy, WatchlistStore},
+};
 
 pub struct Server {
     pub server: actix_server::Server,
 }
 
+/// Optional APIs, each one is served only if present
+pub struct Features {
+    pub watchlist: Option<(Arc<dyn WatchlistStore + Send + Sync>, CallbackPolicy)>,
+    pub history: Option<Arc<dyn HistoryStore + Send + Sync>>,
+    pub snapshots: Option<Arc<Snapshots>>,
+    pub alerts: Option<Arc<dyn AlertStore + Send + Sync>>,
+    pub attester: Option<Arc<Attester>>,
+}
+
 impl Server {
     pub fn try_new(
         cfg: &Config,
-        service: Box<dyn BalancesService + Send + Sync>,
+        service: Arc<Box<dyn BalancesService + Send + Sync>>,
+        registry: Arc<Registry>,
+        features: Features,
     ) -> Result<Server, AppError> {
-        let service = Data::new(service);
+        let service = Data::from(service);
+        let registry = Data::from(registry);
+        let watchlist = features
+            .watchlist
+            .map(|(store, callbacks)| (Data::from(store), Data::new(callbacks)));
+        let history = features.history.map(Data::from);
+        let snapshots = features.snapshots.map(Data::from);
+        let alerts = features.alerts.map(Data::from);
+        let attester = features.attester.map(Data::from);
 
         let srv = HttpServer::new(move || {
-            App::new()
+            let app = App::new()
                 .configure(server_config())
                 .app_data(service.clone())
-                .wrap(Logger::default())
-                .wrap(TracingLogger::default())
+                .app_data(registry.clone());
+
+            let app = match &watchlist {
+                Some((watchlist, callbacks)) => app
+                    .app_data(watchlist.clone())
+                    .app_data(callbacks.clone())
+                    .configure(watches_config()),
+                None => app,
+            };
+
+            let app = match &history {
+                Some(history) => app
+                    .app_data(history.clone())
+                    .service(routes::balance_history::handler),
+                None => app,
+            };
+
+            let app = match &snapshots {
+                Some(snapshots) => app
+                    .app_data(snapshots.clone())
+                    .configure(snapshots_config()),
+                None => app,
+            };
+
+            let app = match &alerts {
+                Some(alerts) => app.app_data(alerts.clone()).configure(alerts_config()),
+                None => app,
+            };
+
+            let app = match &attester {
+                Some(attester) => app
+                    .app_data(attester.clone())
+                    .configure(attestation_config()),
+                None => app,
+            };
+
+            app.wrap(Logger::default()).wrap(TracingLogger::default())
         });
 
         let server = srv
@@ -45,7 +108,7 @@ impl Server {
     pub async fn run(self) -> Result<(), AppError> {
         self.server
             .await
-            .map_err(|e| AppError::ApiServerRun(Arc::new(e).into()))
+            .map_err(|e| AppError::ApiServerRun(Arc::new(e)))
     }
 }
 
@@ -85,7 +148,48 @@ fn server_config() -> Box<dyn Fn(&mut web::ServiceConfig)> {
 
         cfg.app_data(json_cfg)
             .app_data(query_cfg)
+            .service(routes::account_balances::handler)
+            .service(routes::balances_stream::handler)
+            .service(routes::balances_subscriptions::handler)
             .service(routes::custom_asset_balances::handler)
-            .service(routes::native_asset_balances::handler);
+            .service(routes::native_asset_balances::handler)
+            .service(routes::portfolio::handler)
+            .service(routes::reconciliations::handler);
+    })
+}
+
+fn watches_config() -> Box<dyn Fn(&mut web::ServiceConfig)> {
+    Box::new(move |cfg| {
+        cfg.service(routes::watches::create::handler)
+            .service(routes::watches::list::handler)
+            .service(routes::watches::deliveries::handler)
+            .service(routes::watches::get::handler)
+            .service(routes::watches::delete::handler);
+    })
+}
+
+fn snapshots_config() -> Box<dyn Fn(&mut web::ServiceConfig)> {
+    Box::new(move |cfg| {
+        cfg.service(routes::snapshots::create::handler)
+            .service(routes::snapshots::get::handler)
+            .service(routes::snapshots::results::handler)
+            .service(routes::snapshots::merkle_root::handler)
+            .service(routes::snapshots::merkle_proof::handler);
+    })
+}
+
+fn alerts_config() -> Box<dyn Fn(&mut web::ServiceConfig)> {
+    Box::new(move |cfg| {
+        cfg.service(routes::alerts::list::handler)
+            .service(routes::alerts::list_rules::handler)
+            .service(routes::alerts::create_rule::handler)
+            .service(routes::alerts::delete_rule::handler);
+    })
+}
+
+fn attestation_config() -> Box<dyn Fn(&mut web::ServiceConfig)> {
+    Box::new(move |cfg| {
+        cfg.service(routes::attestation::keys::handler)
+            .service(routes::attestation::verify::handler);
     })
 }
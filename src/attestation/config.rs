@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// Name of the EIP-712 domain of the statements
+    #[serde(default = "default_domain_name")]
+    pub domain_name: String,
+    #[serde(default = "default_domain_version")]
+    pub domain_version: String,
+    /// Hex secret key of ed25519 signatures
+    #[serde(default)]
+    pub ed25519_key: Option<String>,
+    /// Hex private key of EIP-712 signatures
+    #[serde(default)]
+    pub secp256k1_key: Option<String>,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("domain_name", &self.domain_name)
+            .field("domain_version", &self.domain_version)
+            .field("ed25519_key", &self.ed25519_key.as_ref().map(|_| "***"))
+            .field("secp256k1_key", &self.secp256k1_key.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+fn default_domain_name() -> String {
+    "balances-service".to_string()
+}
+
+fn default_domain_version() -> String {
+    "1".to_string()
+}
@@ -0,0 +1,177 @@
+mod config;
+pub mod verifier;
+
+use ed25519_dalek::Signer;
+use ethers_core::k256::ecdsa::{recoverable, signature::DigestSigner, SigningKey};
+use ethers_core::types::Address;
+use ethers_core::utils::{keccak256, secret_key_to_address};
+use rustc_hex::{FromHex, ToHex};
+use serde::Serialize;
+use sha3::{Digest, Keccak256};
+
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+use crate::service::{Balance, BlockRef};
+
+pub use config::Config;
+pub use verifier::{AttestationSignature, Domain, Scheme, Statement};
+
+/// Statement about a balance together with its signatures by the keys of the service
+#[derive(Clone, Debug, Serialize)]
+pub struct Attestation {
+    pub statement: Statement,
+    pub signatures: Vec<AttestationSignature>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AttestedBalance<T> {
+    #[serde(flatten)]
+    pub balance: T,
+    /// Absent if the balance has no amounts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+}
+
+/// Key a consumer checks the signatures with
+#[derive(Clone, Debug, Serialize)]
+pub struct PublicKey {
+    pub scheme: Scheme,
+    /// Hex public key for ed25519, address for EIP-712
+    pub signer: String,
+}
+
+/// Signs statements about balances with the configured keys
+pub struct Attester {
+    domain: Domain,
+    ed25519: Option<ed25519_dalek::SigningKey>,
+    secp256k1: Option<(SigningKey, Address)>,
+}
+
+impl Attester {
+    pub fn try_new(config: &Config) -> Result<Self, AppError> {
+        let ed25519 = config
+            .ed25519_key
+            .as_deref()
+            .map(|key| {
+                let key = <[u8; 32]>::try_from(from_hex(key)?).map_err(|_| {
+                    AppError::InvalidAttestationKey("ed25519 key must be 32 bytes".to_string())
+                })?;
+                Ok::<_, AppError>(ed25519_dalek::SigningKey::from_bytes(&key))
+            })
+            .transpose()?;
+
+        let secp256k1 = config
+            .secp256k1_key
+            .as_deref()
+            .map(|key| {
+                let key = SigningKey::from_bytes(&from_hex(key)?)
+                    .map_err(|e| AppError::InvalidAttestationKey(e.to_string()))?;
+                let address = secret_key_to_address(&key);
+                Ok::<_, AppError>((key, address))
+            })
+            .transpose()?;
+
+        if ed25519.is_none() && secp256k1.is_none() {
+            return Err(AppError::InvalidAttestationKey(
+                "neither ed25519 nor secp256k1 key is configured".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            domain: Domain {
+                name: config.domain_name.clone(),
+                version: config.domain_version.clone(),
+            },
+            ed25519,
+            secp256k1,
+        })
+    }
+
+    pub fn domain(&self) -> &Domain {
+        &self.domain
+    }
+
+    pub fn public_keys(&self) -> Vec<PublicKey> {
+        let ed25519 = self.ed25519.as_ref().map(|key| PublicKey {
+            scheme: Scheme::Ed25519,
+            signer: to_hex(key.verifying_key().as_bytes()),
+        });
+        let eip712 = self.secp256k1.as_ref().map(|(_, address)| PublicKey {
+            scheme: Scheme::Eip712,
+            signer: format!("{:?}", address),
+        });
+
+        ed25519.into_iter().chain(eip712).collect()
+    }
+
+    /// Whether the `signature` is made by a key of the service, its validity is not checked
+    pub fn is_own(&self, signature: &AttestationSignature) -> bool {
+        self.public_keys().iter().any(|key| {
+            key.scheme == signature.scheme && key.signer.eq_ignore_ascii_case(&signature.signer)
+        })
+    }
+
+    pub fn sign(&self, statement: &Statement) -> Vec<AttestationSignature> {
+        let preimage = verifier::preimage(&self.domain, statement);
+        let digest = keccak256(&preimage);
+
+        let ed25519 = self.ed25519.as_ref().map(|key| AttestationSignature {
+            scheme: Scheme::Ed25519,
+            signer: to_hex(key.verifying_key().as_bytes()),
+            signature: to_hex(&key.sign(&digest).to_bytes()),
+        });
+
+        let eip712 = self.secp256k1.as_ref().map(|(key, address)| {
+            let signature: recoverable::Signature =
+                key.sign_digest(Keccak256::new().chain(&preimage));
+            let mut signature = signature.as_ref().to_vec();
+            // recovery id to `v` of Ethereum signatures
+            signature[64] += 27;
+
+            AttestationSignature {
+                scheme: Scheme::Eip712,
+                signer: format!("{:?}", address),
+                signature: to_hex(&signature),
+            }
+        });
+
+        ed25519.into_iter().chain(eip712).collect()
+    }
+
+    /// Attests the first reported kind of the `balance` of `address` read at the `block`
+    pub fn attest(
+        &self,
+        chain: &Chain,
+        address: &str,
+        block: &BlockRef,
+        balance: &Balance,
+    ) -> Option<Attestation> {
+        let amount = balance.balances.first()?;
+        let kind = serde_json::to_value(&amount.kind)
+            .ok()
+            .and_then(|kind| kind.as_str().map(ToString::to_string))?;
+
+        let statement = Statement {
+            chain: chain.to_string(),
+            address: address.to_string(),
+            asset_id: balance.asset_id.clone(),
+            kind,
+            amount: amount.amount,
+            block_number: block.block_number,
+        };
+        let signatures = self.sign(&statement);
+
+        Some(Attestation {
+            statement,
+            signatures,
+        })
+    }
+}
+
+fn from_hex(value: &str) -> Result<Vec<u8>, AppError> {
+    Ok(value.trim_start_matches("0x").from_hex()?)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", bytes.to_hex::<String>())
+}
@@ -0,0 +1,121 @@
+//! Verification of balance attestations, depends on no service state
+//! so it can be used by the consumers of the attestations as is
+
+use ed25519_dalek::Verifier;
+use ethabi::Token;
+use ethers_core::types::{Address, Signature, H256, U256};
+use ethers_core::utils::keccak256;
+use rustc_hex::FromHex;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::error::Error as AppError;
+
+const DOMAIN_TYPE: &str = "EIP712Domain(string name,string version)";
+const STATEMENT_TYPE: &str = "Balance(string chain,string address,string assetId,string kind,uint256 amount,uint256 blockNumber)";
+
+/// "The balances service said `amount` of `asset_id` at `block_number`"
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Statement {
+    pub chain: String,
+    pub address: String,
+    pub asset_id: String,
+    pub kind: String,
+    pub amount: u64,
+    pub block_number: u64,
+}
+
+/// EIP-712 domain of the statements
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Domain {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    /// Ed25519 signature of the EIP-712 digest, the signer is the hex public key
+    Ed25519,
+    /// secp256k1 EIP-712 signature, the signer is the address
+    Eip712,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AttestationSignature {
+    pub scheme: Scheme,
+    pub signer: String,
+    /// Hex signature, `r . s . v` for EIP-712
+    pub signature: String,
+}
+
+/// EIP-712 digest of the `statement`, which both schemes sign
+pub fn digest(domain: &Domain, statement: &Statement) -> [u8; 32] {
+    keccak256(preimage(domain, statement))
+}
+
+/// `0x1901 . domainSeparator . hashStruct(statement)` hashed into the digest
+pub fn preimage(domain: &Domain, statement: &Statement) -> Vec<u8> {
+    let domain_separator = keccak256(ethabi::encode(&[
+        Token::FixedBytes(keccak256(DOMAIN_TYPE).to_vec()),
+        Token::FixedBytes(keccak256(&domain.name).to_vec()),
+        Token::FixedBytes(keccak256(&domain.version).to_vec()),
+    ]));
+
+    let struct_hash = keccak256(ethabi::encode(&[
+        Token::FixedBytes(keccak256(STATEMENT_TYPE).to_vec()),
+        Token::FixedBytes(keccak256(&statement.chain).to_vec()),
+        Token::FixedBytes(keccak256(&statement.address).to_vec()),
+        Token::FixedBytes(keccak256(&statement.asset_id).to_vec()),
+        Token::FixedBytes(keccak256(&statement.kind).to_vec()),
+        Token::Uint(U256::from(statement.amount)),
+        Token::Uint(U256::from(statement.block_number)),
+    ]));
+
+    [&[0x19, 0x01][..], &domain_separator, &struct_hash].concat()
+}
+
+/// Whether the `signature` of the `statement` was made by its signer
+pub fn verify(
+    domain: &Domain,
+    statement: &Statement,
+    signature: &AttestationSignature,
+) -> Result<bool, AppError> {
+    let digest = digest(domain, statement);
+    let signature_bytes = from_hex(&signature.signature)?;
+
+    match signature.scheme {
+        Scheme::Ed25519 => {
+            let public_key = <[u8; 32]>::try_from(from_hex(&signature.signer)?)
+                .map_err(|_| malformed("ed25519 public key must be 32 bytes"))?;
+            let public_key = ed25519_dalek::VerifyingKey::from_bytes(&public_key)
+                .map_err(|e| malformed(e.to_string()))?;
+            let signature_bytes = <[u8; 64]>::try_from(signature_bytes)
+                .map_err(|_| malformed("ed25519 signature must be 64 bytes"))?;
+
+            Ok(public_key
+                .verify(
+                    &digest,
+                    &ed25519_dalek::Signature::from_bytes(&signature_bytes),
+                )
+                .is_ok())
+        }
+        Scheme::Eip712 => {
+            let signer = Address::from_str(&signature.signer)?;
+            let signature = Signature::try_from(signature_bytes.as_slice())
+                .map_err(|e| malformed(e.to_string()))?;
+
+            Ok(signature
+                .recover(H256(digest))
+                .is_ok_and(|recovered| recovered == signer))
+        }
+    }
+}
+
+fn from_hex(value: &str) -> Result<Vec<u8>, AppError> {
+    Ok(value.trim_start_matches("0x").from_hex()?)
+}
+
+fn malformed(reason: impl AsRef<str>) -> AppError {
+    AppError::MalformedAttestation(reason.as_ref().to_string())
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::config::redact_url;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// Cache shared by the replicas, the in-process one is used while it is unreachable
+    #[serde(default)]
+    pub redis_url: Option<String>,
+    /// Prefix of the keys, so several deployments can share the Redis
+    #[serde(default = "default_key_prefix")]
+    pub key_prefix: String,
+    /// TTL of the balances at the latest block in milliseconds
+    #[serde(default = "default_balances_ttl")]
+    pub balances_ttl: u64,
+    /// TTL of the balances at past blocks in milliseconds
+    #[serde(default = "default_historical_ttl")]
+    pub historical_ttl: u64,
+    /// TTL of the oracle prices in milliseconds
+    #[serde(default = "default_prices_ttl")]
+    pub prices_ttl: u64,
+    /// TTL of the asset metadata, e.g. decimals of the price feeds, in milliseconds
+    #[serde(default = "default_metadata_ttl")]
+    pub metadata_ttl: u64,
+    /// Timeout of a Redis command in milliseconds
+    #[serde(default = "default_redis_timeout")]
+    pub redis_timeout: u64,
+    /// Time Redis is not tried again after a failure in milliseconds
+    #[serde(default = "default_redis_retry_interval")]
+    pub redis_retry_interval: u64,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("redis_url", &self.redis_url.as_deref().map(redact_url))
+            .field("key_prefix", &self.key_prefix)
+            .field("balances_ttl", &self.balances_ttl)
+            .field("historical_ttl", &self.historical_ttl)
+            .field("prices_ttl", &self.prices_ttl)
+            .field("metadata_ttl", &self.metadata_ttl)
+            .field("redis_timeout", &self.redis_timeout)
+            .field("redis_retry_interval", &self.redis_retry_interval)
+            .finish()
+    }
+}
+
+fn default_key_prefix() -> String {
+    "balances-service".to_string()
+}
+
+fn default_balances_ttl() -> u64 {
+    15000
+}
+
+fn default_historical_ttl() -> u64 {
+    3600000
+}
+
+fn default_prices_ttl() -> u64 {
+    60000
+}
+
+fn default_metadata_ttl() -> u64 {
+    86400000
+}
+
+fn default_redis_timeout() -> u64 {
+    200
+}
+
+fn default_redis_retry_interval() -> u64 {
+    5000
+}
@@ -0,0 +1,314 @@
+mod config;
+mod store;
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+
+pub use config::Config;
+pub use store::{CacheStore, InMemoryCacheStore, RedisCacheStore};
+
+/// Cache of the balances and prices read from the nodes, in Redis if configured
+/// and reachable, in the process otherwise
+pub struct Cache {
+    key_prefix: String,
+    pub balances_ttl: Duration,
+    pub historical_ttl: Duration,
+    pub prices_ttl: Duration,
+    metadata_ttl: Duration,
+    redis: Option<RedisCacheStore>,
+    redis_timeout: Duration,
+    redis_retry_interval: Duration,
+    /// Redis is skipped until then after a failure
+    redis_down_until: Mutex<Option<Instant>>,
+    local: InMemoryCacheStore,
+}
+
+impl Cache {
+    /// Does not connect to Redis, so the service starts while it is unreachable
+    pub fn try_new(config: &Config) -> Result<Self, AppError> {
+        let redis = config
+            .redis_url
+            .as_deref()
+            .map(RedisCacheStore::try_new)
+            .transpose()?;
+
+        Ok(Self {
+            key_prefix: config.key_prefix.clone(),
+            balances_ttl: Duration::from_millis(config.balances_ttl),
+            historical_ttl: Duration::from_millis(config.historical_ttl),
+            prices_ttl: Duration::from_millis(config.prices_ttl),
+            metadata_ttl: Duration::from_millis(config.metadata_ttl),
+            redis,
+            redis_timeout: Duration::from_millis(config.redis_timeout),
+            redis_retry_interval: Duration::from_millis(config.redis_retry_interval),
+            redis_down_until: Mutex::new(None),
+            local: InMemoryCacheStore::default(),
+        })
+    }
+
+    /// Key of the balances of a `lookup` at the `block` of the `chain`
+    pub fn balances_key(&self, chain: &Chain, block: u64, lookup: &str) -> String {
+        format!(
+            "{}:balances:{}:{}:{}",
+            self.key_prefix, chain, block, lookup
+        )
+    }
+
+    pub fn prices_key(&self, chain: &Chain, asset_ids: &[String]) -> String {
+        format!(
+            "{}:prices:{}:{}",
+            self.key_prefix,
+            chain,
+            asset_ids.join(",")
+        )
+    }
+
+    /// Key of the `name` metadata of the asset or contract `id` of the `chain`
+    pub fn metadata_key(&self, chain: &Chain, id: &str, name: &str) -> String {
+        format!("{}:metadata:{}:{}:{}", self.key_prefix, chain, id, name)
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = match self.redis() {
+            Some(redis) => match self.with_timeout(redis.get(key)).await {
+                Ok(value) => value,
+                Err(e) => {
+                    self.redis_failed(e);
+                    self.local.get(key).await.ok().flatten()
+                }
+            },
+            None => self.local.get(key).await.ok().flatten(),
+        }?;
+
+        serde_json::from_str(&value)
+            .map_err(|e| warn!("cached value of {} is malformed: {}", key, e))
+            .ok()
+    }
+
+    pub async fn set<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) {
+        let value = match serde_json::to_string(value) {
+            Ok(value) => value,
+            Err(e) => return warn!("failed to serialize cached value of {}: {}", key, e),
+        };
+
+        if let Some(redis) = self.redis() {
+            match self.with_timeout(redis.set(key, value.clone(), ttl)).await {
+                Ok(()) => return,
+                Err(e) => self.redis_failed(e),
+            }
+        }
+        let _ = self.local.set(key, value, ttl).await;
+    }
+
+    /// Redis unless it failed within the retry interval
+    fn redis(&self) -> Option<&RedisCacheStore> {
+        let down_until = self
+            .redis_down_until
+            .lock()
+            .expect("cache lock is poisoned");
+        match *down_until {
+            Some(down_until) if down_until > Instant::now() => None,
+            _ => self.redis.as_ref(),
+        }
+    }
+
+    fn redis_failed(&self, e: AppError) {
+        warn!(
+            "redis is unavailable for {}ms, caching in the process: {}",
+            self.redis_retry_interval.as_millis(),
+            e
+        );
+        *self
+            .redis_down_until
+            .lock()
+            .expect("cache lock is poisoned") = Some(Instant::now() + self.redis_retry_interval);
+    }
+
+    async fn with_timeout<T>(
+        &self,
+        command: impl std::future::Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        tokio::time::timeout(self.redis_timeout, command)
+            .await
+            .map_err(|_| {
+                AppError::Cache(format!("no answer in {}ms", self.redis_timeout.as_millis()))
+            })?
+    }
+}
+
+/// Metadata of the assets of a chain, which does not change from block to block,
+/// so it is read from the node once per `metadata_ttl`
+#[derive(Clone)]
+pub struct MetadataCache {
+    chain: Chain,
+    cache: Arc<Cache>,
+}
+
+impl MetadataCache {
+    pub fn new(chain: &Chain, cache: Arc<Cache>) -> Self {
+        Self {
+            chain: chain.clone(),
+            cache,
+        }
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, id: &str, name: &str) -> Option<T> {
+        let key = self.cache.metadata_key(&self.chain, id, name);
+        self.cache.get(&key).await
+    }
+
+    pub async fn set<T: Serialize>(&self, id: &str, name: &str, value: &T) {
+        let key = self.cache.metadata_key(&self.chain, id, name);
+        self.cache.set(&key, value, self.cache.metadata_ttl).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    type Values = Arc<Mutex<HashMap<String, String>>>;
+
+    /// Redis stand-in answering `GET` and `SET` of the RESP protocol
+    async fn redis_stand_in() -> (String, Values) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        let values = Values::default();
+
+        let stored = values.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, stored.clone()));
+            }
+        });
+
+        (url, values)
+    }
+
+    async fn serve(socket: TcpStream, values: Values) {
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader);
+
+        while let Some(command) = read_command(&mut reader).await {
+            let reply = match command[0].to_uppercase().as_str() {
+                "GET" => match values.lock().unwrap().get(&command[1]) {
+                    Some(value) => format!("${}\r\n{}\r\n", value.len(), value),
+                    None => "$-1\r\n".to_string(),
+                },
+                "SET" => {
+                    let mut values = values.lock().unwrap();
+                    values.insert(command[1].clone(), command[2].clone());
+                    "+OK\r\n".to_string()
+                }
+                _ => "+OK\r\n".to_string(),
+            };
+            if writer.write_all(reply.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Array of bulk strings sent by the client
+    async fn read_command(
+        reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+    ) -> Option<Vec<String>> {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .await
+            .ok()
+            .filter(|read| *read > 0)?;
+        let count = line.trim_end().strip_prefix('*')?.parse::<usize>().ok()?;
+
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            reader.read_line(&mut line).await.ok()?;
+            let len = line.trim_end().strip_prefix('$')?.parse::<usize>().ok()?;
+
+            let mut arg = vec![0; len + 2];
+            reader.read_exact(&mut arg).await.ok()?;
+            arg.truncate(len);
+            args.push(String::from_utf8(arg).ok()?);
+        }
+        Some(args)
+    }
+
+    fn config(redis_url: Option<String>) -> Config {
+        Config {
+            redis_url,
+            key_prefix: "test".to_string(),
+            balances_ttl: 15000,
+            historical_ttl: 3600000,
+            prices_ttl: 60000,
+            metadata_ttl: 86400000,
+            redis_timeout: 200,
+            redis_retry_interval: 5000,
+        }
+    }
+
+    #[tokio::test]
+    async fn shares_values_between_replicas_through_redis() {
+        let (url, values) = redis_stand_in().await;
+        let replica = Cache::try_new(&config(Some(url.clone()))).unwrap();
+        let other_replica = Cache::try_new(&config(Some(url))).unwrap();
+
+        let key = replica.balances_key(&Chain::Ethereum, 7, "0xabc:native:");
+        replica
+            .set(&key, &vec![1u64, 2], replica.balances_ttl)
+            .await;
+
+        assert_eq!(key, "test:balances:ethereum:7:0xabc:native:");
+        assert_eq!(values.lock().unwrap().get(&key).unwrap(), "[1,2]");
+        assert_eq!(other_replica.get::<Vec<u64>>(&key).await, Some(vec![1, 2]));
+        assert_eq!(other_replica.get::<Vec<u64>>("test:missing").await, None);
+    }
+
+    #[tokio::test]
+    async fn caches_in_process_while_redis_is_unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let cache = Cache::try_new(&config(Some(url))).unwrap();
+        cache.set("key", &42u64, cache.balances_ttl).await;
+
+        assert!(cache.redis().is_none());
+        assert_eq!(cache.get::<u64>("key").await, Some(42));
+    }
+
+    #[tokio::test]
+    async fn expires_values_cached_in_process() {
+        let cache = Cache::try_new(&config(None)).unwrap();
+        cache.set("key", &42u64, Duration::from_millis(10)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(cache.get::<u64>("key").await, None);
+    }
+
+    #[tokio::test]
+    async fn namespaces_metadata_by_chain() {
+        let (url, values) = redis_stand_in().await;
+        let cache = Arc::new(Cache::try_new(&config(Some(url))).unwrap());
+        let ethereum = MetadataCache::new(&Chain::Ethereum, cache.clone());
+        let bsc = MetadataCache::new(&Chain::BSC, cache);
+
+        ethereum.set("0xfeed", "decimals", &8u32).await;
+
+        assert_eq!(ethereum.get::<u32>("0xfeed", "decimals").await, Some(8));
+        assert_eq!(bsc.get::<u32>("0xfeed", "decimals").await, None);
+        assert!(values
+            .lock()
+            .unwrap()
+            .contains_key("test:metadata:ethereum:0xfeed:decimals"));
+    }
+}
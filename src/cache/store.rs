@@ -0,0 +1,111 @@
+use redis::aio::MultiplexedConnection;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::Error as AppError;
+
+/// Entries stored before the first sweep of the expired ones
+const INITIAL_SWEEP_SIZE: usize = 1024;
+
+#[async_trait::async_trait]
+pub trait CacheStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, AppError>;
+
+    async fn set(&self, key: &str, value: String, ttl: Duration) -> Result<(), AppError>;
+}
+
+#[derive(Default)]
+struct Entries {
+    values: HashMap<String, (String, Instant)>,
+    next_sweep: usize,
+}
+
+/// Cache of a single replica
+#[derive(Default)]
+pub struct InMemoryCacheStore {
+    entries: Mutex<Entries>,
+}
+
+#[async_trait::async_trait]
+impl CacheStore for InMemoryCacheStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, AppError> {
+        let entries = self.entries.lock().expect("cache lock is poisoned");
+
+        Ok(entries
+            .values
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Duration) -> Result<(), AppError> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().expect("cache lock is poisoned");
+        entries.values.insert(key.to_string(), (value, now + ttl));
+
+        if entries.values.len() >= entries.next_sweep.max(INITIAL_SWEEP_SIZE) {
+            entries
+                .values
+                .retain(|_, (_, expires_at)| *expires_at > now);
+            entries.next_sweep = entries.values.len() * 2;
+        }
+        Ok(())
+    }
+}
+
+/// Cache shared by the replicas, connected on the first use and reconnected after failures
+pub struct RedisCacheStore {
+    client: redis::Client,
+    connection: tokio::sync::Mutex<Option<MultiplexedConnection>>,
+}
+
+impl RedisCacheStore {
+    pub fn try_new(url: &str) -> Result<Self, AppError> {
+        Ok(Self {
+            client: redis::Client::open(url).map_err(Arc::new)?,
+            connection: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    async fn query<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> Result<T, AppError> {
+        let mut connection = self.connection.lock().await;
+        let mut established = match connection.as_ref() {
+            Some(established) => established.clone(),
+            None => {
+                let established = self
+                    .client
+                    .get_multiplexed_tokio_connection()
+                    .await
+                    .map_err(Arc::new)?;
+                *connection = Some(established.clone());
+                established
+            }
+        };
+        drop(connection);
+
+        let result = cmd.query_async(&mut established).await;
+        if result.is_err() {
+            *self.connection.lock().await = None;
+        }
+        Ok(result.map_err(Arc::new)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl CacheStore for RedisCacheStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, AppError> {
+        self.query(redis::cmd("GET").arg(key)).await
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Duration) -> Result<(), AppError> {
+        self.query(
+            redis::cmd("SET")
+                .arg(key)
+                .arg(value)
+                .arg("PX")
+                .arg(ttl.as_millis() as u64),
+        )
+        .await
+    }
+}
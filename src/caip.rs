@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use crate::error::Error as AppError;
+use crate::node_clients::{Chain, Config as ChainConfig};
+
+/// CAIP-2 chain id, e.g. `eip155:1`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ChainId {
+    namespace: &'static str,
+    reference: String,
+    /// SLIP-44 coin type of the native token
+    slip44: u32,
+    /// CAIP-19 asset namespace of the tokens
+    token_namespace: &'static str,
+    native_token: String,
+}
+
+impl ChainId {
+    fn caip2(&self) -> String {
+        format!("{}:{}", self.namespace, self.reference)
+    }
+}
+
+/// Maps chains and assets to CAIP-2, CAIP-10 and CAIP-19 identifiers and back
+///
+/// EVM chains are `eip155:<chain id>`, Waves is `waves:<chain id>`,
+/// other chains have no CAIP identifiers
+#[derive(Clone, Debug, Default)]
+pub struct Caip {
+    chains: HashMap<Chain, ChainId>,
+}
+
+impl Caip {
+    pub fn new<'a>(configs: impl IntoIterator<Item = (&'a Chain, &'a ChainConfig)>) -> Self {
+        let chains = configs
+            .into_iter()
+            .filter_map(|(chain, config)| {
+                let native_token = config.native_token();
+                let chain_id = match config {
+                    ChainConfig::Ethereum(c) | ChainConfig::Bsc(c) => ChainId {
+                        namespace: "eip155",
+                        reference: c.chain_id.as_u64().to_string(),
+                        slip44: match chain {
+                            Chain::BSC => 714,
+                            _ => 60,
+                        },
+                        token_namespace: "erc20",
+                        native_token,
+                    },
+                    ChainConfig::Waves(c) => ChainId {
+                        namespace: "waves",
+                        reference: c.chain_id.to_string(),
+                        slip44: 5741564,
+                        token_namespace: "token",
+                        native_token,
+                    },
+                    ChainConfig::Polkadot(_) => return None,
+                };
+                Some((chain.clone(), chain_id))
+            })
+            .collect();
+
+        Self { chains }
+    }
+
+    /// CAIP-2 id of the `chain`
+    pub fn chain_id(&self, chain: &Chain) -> Option<String> {
+        self.chains.get(chain).map(ChainId::caip2)
+    }
+
+    /// Id of the native token of the `chain`, if the chain has CAIP identifiers
+    pub fn native_token(&self, chain: &Chain) -> Option<String> {
+        self.chains
+            .get(chain)
+            .map(|chain_id| chain_id.native_token.clone())
+    }
+
+    /// CAIP-19 id of `asset_id` on the `chain`, the native token is a SLIP-44 asset
+    pub fn asset_id(&self, chain: &Chain, asset_id: &str) -> Option<String> {
+        let chain_id = self.chains.get(chain)?;
+
+        Some(if asset_id == chain_id.native_token {
+            format!("{}/slip44:{}", chain_id.caip2(), chain_id.slip44)
+        } else {
+            format!(
+                "{}/{}:{}",
+                chain_id.caip2(),
+                chain_id.token_namespace,
+                asset_id
+            )
+        })
+    }
+
+    /// Parses either a chain name, e.g. `ethereum`, or a CAIP-2 chain id
+    pub fn parse_chain(&self, value: &str) -> Result<Chain, AppError> {
+        if !value.contains(':') {
+            return Chain::try_from(value.to_string());
+        }
+
+        self.chains
+            .iter()
+            .find(|(_, chain_id)| chain_id.caip2() == value)
+            .map(|(chain, _)| chain.clone())
+            .ok_or_else(|| AppError::UnexpectedChain(value.to_string()))
+    }
+
+    /// Parses either a plain address or a CAIP-10 account id of an address on the `chain`
+    pub fn parse_account(&self, chain: &Chain, value: &str) -> Result<String, AppError> {
+        match value.rsplit_once(':') {
+            Some((chain_id, address)) => {
+                if &self.parse_chain(chain_id)? != chain {
+                    return Err(AppError::InvalidCaipId(format!(
+                        "account {} is not on {}",
+                        value, chain
+                    )));
+                }
+                Ok(address.to_string())
+            }
+            None => Ok(value.to_string()),
+        }
+    }
+
+    /// Parses a CAIP-10 account id into the chain and the address
+    pub fn parse_account_id(&self, value: &str) -> Result<(Chain, String), AppError> {
+        let (chain_id, address) = value
+            .rsplit_once(':')
+            .ok_or_else(|| AppError::InvalidCaipId(format!("{} is not an account id", value)))?;
+
+        Ok((self.parse_chain(chain_id)?, address.to_string()))
+    }
+
+    /// Parses either a plain asset id or a CAIP-19 id of an asset on the `chain`
+    pub fn parse_asset(&self, chain: &Chain, value: &str) -> Result<String, AppError> {
+        let (chain_id, asset) = match value.split_once('/') {
+            Some(parts) => parts,
+            None => return Ok(value.to_string()),
+        };
+
+        let invalid = || AppError::InvalidCaipId(format!("{} is not an asset on {}", value, chain));
+
+        if &self.parse_chain(chain_id)? != chain {
+            return Err(invalid());
+        }
+        let caip = self.chains.get(chain).ok_or_else(invalid)?;
+
+        match asset.split_once(':') {
+            Some(("slip44", coin_type)) if coin_type == caip.slip44.to_string() => {
+                Ok(caip.native_token.clone())
+            }
+            Some((namespace, reference)) if namespace == caip.token_namespace => {
+                Ok(reference.to_string())
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
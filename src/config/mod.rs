@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::marker::PhantomData;
 use std::sync::Arc;
@@ -6,11 +6,19 @@ use std::sync::Arc;
 use serde::de::{self};
 use serde::{Deserialize, Serialize};
 
+use crate::alerts::Config as AlertsConfig;
 use crate::api::config::Config as ApiConfig;
+use crate::attestation::Config as AttestationConfig;
+use crate::cache::Config as CacheConfig;
 use crate::error::Error as AppError;
+use crate::history::Config as HistoryConfig;
 use crate::node_clients::{
-    evm::Config as EvmConfig, waves::Config as WavesConfig, Chain, Config as ChainConfig,
+    evm::Config as EvmConfig, substrate::Config as SubstrateConfig, waves::Config as WavesConfig,
+    Chain, Config as ChainConfig,
 };
+use crate::registry::CanonicalAsset;
+use crate::snapshots::Config as SnapshotsConfig;
+use crate::webhooks::Config as WebhooksConfig;
 
 const DEFAULT_CONFIG: &str = include_str!("../../config.yaml");
 
@@ -21,6 +29,38 @@ pub struct Config {
     pub api: ApiConfig,
     #[serde(deserialize_with = "deserialize_config_map")]
     pub chains: ConfigMap,
+    /// Watchlist API and webhook notifications are enabled only if configured
+    #[serde(default)]
+    pub webhooks: Option<WebhooksConfig>,
+    /// Balance history is recorded and served only if configured
+    #[serde(default)]
+    pub history: Option<HistoryConfig>,
+    /// Snapshot jobs API is enabled only if configured
+    #[serde(default)]
+    pub snapshots: Option<SnapshotsConfig>,
+    /// Balance alerting is enabled only if configured
+    #[serde(default)]
+    pub alerts: Option<AlertsConfig>,
+    /// Balances can be attested and attestations verified only if configured
+    #[serde(default)]
+    pub attestation: Option<AttestationConfig>,
+    /// Balances and prices are cached only if configured
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+    /// Canonical asset key -> ids of the asset on different chains
+    #[serde(default)]
+    pub assets: BTreeMap<String, CanonicalAsset>,
+}
+
+/// Hides the password of connection `url`, so it can be logged
+pub fn redact_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("***"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
 }
 
 pub fn load() -> Result<Config, AppError> {
@@ -78,6 +118,10 @@ where
                         let c = access.next_value::<WavesConfig>()?;
                         ChainConfig::Waves(c)
                     }
+                    Chain::Polkadot => {
+                        let c = access.next_value::<SubstrateConfig>()?;
+                        ChainConfig::Polkadot(c)
+                    }
                 };
                 map.insert(key, value);
             }
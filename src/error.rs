@@ -2,6 +2,8 @@ use std::sync::Arc;
 
 use tracing::{dispatcher::SetGlobalDefaultError, log::SetLoggerError};
 
+use crate::node_clients::quorum::ProviderAnswer;
+
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum Error {
     #[error("LogTracerInit: {0}")]
@@ -37,6 +39,9 @@ pub enum Error {
     #[error("UrlParse: {0}")]
     UrlParse(#[from] url::ParseError),
 
+    #[error("UnsupportedTransport: {0}")]
+    UnsupportedTransport(String),
+
     #[error("EthersProvider: {0}")]
     EthersProvider(#[from] Arc<ethers_providers::ProviderError>),
 
@@ -51,4 +56,88 @@ pub enum Error {
 
     #[error("UnexpectedOutputToken: {0}")]
     UnexpectedOutputToken(String),
+
+    #[error("InvalidAddress: {0}")]
+    InvalidAddress(String),
+
+    #[error("InvalidAssetId: {0}")]
+    InvalidAssetId(String),
+
+    #[error("ScaleDecode: {0}")]
+    ScaleDecode(String),
+
+    #[error("AmountOverflow: {0}")]
+    AmountOverflow(String),
+
+    #[error("InvalidCallbackUrl: {0}")]
+    InvalidCallbackUrl(String),
+
+    #[error("Database: {0}")]
+    Database(#[from] Arc<sqlx::Error>),
+
+    #[error("Redis: {0}")]
+    Redis(#[from] Arc<redis::RedisError>),
+
+    #[error("Cache: {0}")]
+    Cache(String),
+
+    #[error("UnexpectedBalanceKind: {0}")]
+    UnexpectedBalanceKind(String),
+
+    #[error("BalancesAtBlockNotSupported")]
+    BalancesAtBlockNotSupported,
+
+    #[error("BlockNotFound: {0}")]
+    BlockNotFound(u64),
+
+    #[error("Serialization: {0}")]
+    Serialization(String),
+
+    #[error("InvalidAssetRegistry: {0}")]
+    InvalidAssetRegistry(String),
+
+    #[error("InvalidCaipId: {0}")]
+    InvalidCaipId(String),
+
+    #[error("VerificationNotSupported")]
+    VerificationNotSupported,
+
+    #[error("InvalidProof: {0}")]
+    InvalidProof(String),
+
+    #[error("InvalidQuorum: {0}")]
+    InvalidQuorum(String),
+
+    #[error("InvalidAttestationKey: {0}")]
+    InvalidAttestationKey(String),
+
+    #[error("MalformedAttestation: {0}")]
+    MalformedAttestation(String),
+
+    #[error("UpstreamTimeout: {0}")]
+    UpstreamTimeout(String),
+
+    #[error("CircuitOpen: {0}")]
+    CircuitOpen(String),
+
+    #[error("RateLimited: {0}")]
+    RateLimited(String),
+
+    #[error("Saturated: {0}")]
+    Saturated(String),
+
+    #[error("QuorumNotReached: {}", crate::node_clients::quorum::format_answers(.0))]
+    QuorumNotReached(Vec<ProviderAnswer>),
+
+    /// Token contract address -> error of reading the balance of the token
+    #[error("TokenBalances: {}", format_failures(.0))]
+    TokenBalances(Vec<(String, Error)>),
+}
+
+fn format_failures(failures: &[(String, Error)]) -> String {
+    failures
+        .iter()
+        .map(|(token, e)| format!("{}: {}", token, e))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
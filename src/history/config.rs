@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::config::redact_url;
+use crate::subscriptions::Subscription;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// `sqlite://` or, with the `postgres` feature, `postgres://` connection url
+    pub database_url: String,
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+    /// Addresses recorded in addition to the ones on the watchlist
+    #[serde(default)]
+    pub addresses: Vec<Subscription>,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("database_url", &redact_url(&self.database_url))
+            .field("max_connections", &self.max_connections)
+            .field("addresses", &self.addresses)
+            .finish()
+    }
+}
+
+fn default_max_connections() -> u32 {
+    5
+}
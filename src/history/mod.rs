@@ -0,0 +1,108 @@
+mod config;
+mod recorder;
+mod store;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::service::BalanceKind;
+
+pub use config::Config;
+pub use recorder::spawn_recorders;
+pub use store::{HistoryStore, Record, SqlHistoryStore};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Point {
+    pub timestamp: u64,
+    pub height: u64,
+    pub amount: u64,
+}
+
+/// Time series of a single balance of an asset
+#[derive(Clone, Debug, Serialize)]
+pub struct Series {
+    pub asset_id: String,
+    pub kind: BalanceKind,
+    pub points: Vec<Point>,
+}
+
+/// Groups `records` ordered by observation time into series of `asset_ids`
+/// (of all recorded assets if empty)
+///
+/// Without `interval` every change within `from..=to` becomes a point, otherwise
+/// there is a point per interval starting at `from` holding the balance at the
+/// end of the interval
+pub fn series(
+    records: Vec<Record>,
+    asset_ids: &[String],
+    from: u64,
+    to: u64,
+    interval: Option<u64>,
+) -> Vec<Series> {
+    let mut grouped: BTreeMap<(String, String), (BalanceKind, Vec<Record>)> = BTreeMap::new();
+    for record in records {
+        if !asset_ids.is_empty() && !asset_ids.contains(&record.asset_id) {
+            continue;
+        }
+        let key = (record.asset_id.clone(), format!("{:?}", record.kind));
+        grouped
+            .entry(key)
+            .or_insert_with(|| (record.kind.clone(), vec![]))
+            .1
+            .push(record);
+    }
+
+    grouped
+        .into_iter()
+        .map(|((asset_id, _), (kind, records))| {
+            let points = match interval {
+                None => records
+                    .iter()
+                    .filter(|record| record.observed_at >= from)
+                    .map(|record| Point {
+                        timestamp: record.observed_at,
+                        height: record.height,
+                        amount: record.amount,
+                    })
+                    .collect(),
+                Some(interval) => resample(&records, from, to, interval),
+            };
+
+            Series {
+                asset_id,
+                kind,
+                points,
+            }
+        })
+        .collect()
+}
+
+fn resample(records: &[Record], from: u64, to: u64, interval: u64) -> Vec<Point> {
+    let mut points = vec![];
+    let mut records = records.iter().peekable();
+    let mut last: Option<&Record> = None;
+
+    let mut start = from;
+    while start <= to {
+        let end = start.saturating_add(interval - 1).min(to);
+
+        while let Some(record) = records.next_if(|record| record.observed_at <= end) {
+            last = Some(record);
+        }
+
+        if let Some(record) = last {
+            points.push(Point {
+                timestamp: start,
+                height: record.height,
+                amount: record.amount,
+            });
+        }
+
+        start = match start.checked_add(interval) {
+            Some(start) => start,
+            None => break,
+        };
+    }
+
+    points
+}
@@ -0,0 +1,105 @@
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{error, warn};
+
+use super::HistoryStore;
+use crate::clock::now;
+use crate::node_clients::Chain;
+use crate::service::BalancesService;
+use crate::subscriptions::{self, ChangeTracker, Subscription};
+use crate::webhooks::WatchlistStore;
+
+/// Starts a background recorder per chain, which stores balances of the
+/// `addresses` and of the watchlist addresses whenever they change
+pub fn spawn_recorders(
+    chains: impl IntoIterator<Item = Chain>,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    store: Arc<dyn HistoryStore + Send + Sync>,
+    addresses: Vec<Subscription>,
+    watchlist: Option<Arc<dyn WatchlistStore + Send + Sync>>,
+) {
+    for chain in chains {
+        let addresses = addresses
+            .iter()
+            .filter(|subscription| subscription.chain == chain)
+            .cloned()
+            .collect();
+
+        tokio::spawn(record(
+            chain,
+            service.clone(),
+            store.clone(),
+            addresses,
+            watchlist.clone(),
+        ));
+    }
+}
+
+async fn record(
+    chain: Chain,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    store: Arc<dyn HistoryStore + Send + Sync>,
+    addresses: Vec<Subscription>,
+    watchlist: Option<Arc<dyn WatchlistStore + Send + Sync>>,
+) {
+    let blocks = match service.subscribe_blocks(chain.clone()) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let mut blocks = subscriptions::blocks_stream(blocks);
+
+    let mut trackers: HashMap<Subscription, ChangeTracker> = HashMap::new();
+
+    while let Some(height) = blocks.next().await {
+        let mut recorded = addresses.iter().cloned().collect::<HashSet<_>>();
+        if let Some(watchlist) = &watchlist {
+            match watchlist.list_by_chain(&chain).await {
+                Ok(watches) => recorded.extend(watches.iter().map(|watch| watch.subscription())),
+                Err(e) => error!("{}", e),
+            }
+        }
+
+        trackers.retain(|subscription, _| recorded.contains(subscription));
+
+        let recorded = recorded.into_iter().collect::<Vec<_>>();
+        let results = futures::future::join_all(
+            recorded
+                .iter()
+                .map(|subscription| subscription.fetch(service.as_ref().as_ref())),
+        )
+        .await;
+
+        let observed_at = now();
+        for (subscription, result) in recorded.into_iter().zip(results) {
+            let balances = match result {
+                Ok(balances) => balances,
+                Err(e) => {
+                    warn!(
+                        "failed to read balances of {} on {}: {}",
+                        subscription.address, chain, e
+                    );
+                    continue;
+                }
+            };
+
+            let tracker = trackers.entry(subscription.clone()).or_default();
+            let changed = tracker.changed(balances);
+            if changed.is_empty() {
+                continue;
+            }
+
+            if let Err(e) = store
+                .record(&chain, &subscription.address, height, observed_at, &changed)
+                .await
+            {
+                error!("{}", e);
+                // forget the balances, so they are recorded again on the next block
+                trackers.remove(&subscription);
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use sqlx::any::{AnyPool, AnyPoolOptions};
+use sqlx::Row;
+use std::sync::Arc;
+
+use super::Config;
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+use crate::service::{Balance, BalanceKind};
+
+static MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS balance_history (
+        chain TEXT NOT NULL,
+        address TEXT NOT NULL,
+        asset_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        height BIGINT NOT NULL,
+        observed_at BIGINT NOT NULL,
+        amount TEXT NOT NULL,
+        PRIMARY KEY (chain, address, asset_id, kind, height)
+    )",
+    "CREATE INDEX IF NOT EXISTS balance_history_observed_at
+        ON balance_history (chain, address, observed_at)",
+];
+
+/// Balance amount observed at a block
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub asset_id: String,
+    pub kind: BalanceKind,
+    pub height: u64,
+    pub observed_at: u64,
+    pub amount: u64,
+}
+
+#[async_trait::async_trait]
+pub trait HistoryStore {
+    async fn record(
+        &self,
+        chain: &Chain,
+        address: &str,
+        height: u64,
+        observed_at: u64,
+        balances: &[Balance],
+    ) -> Result<(), AppError>;
+
+    /// Records observed within `from..=to` preceded by the last record of
+    /// every balance before `from`, ordered by observation time
+    async fn query(
+        &self,
+        chain: &Chain,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<Record>, AppError>;
+}
+
+/// History store on top of SQLite or Postgres
+pub struct SqlHistoryStore {
+    pool: AnyPool,
+}
+
+impl SqlHistoryStore {
+    /// Connects to the database and creates missing tables
+    pub async fn connect(config: &Config) -> Result<Self, AppError> {
+        let pool = AnyPoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect(&config.database_url)
+            .await
+            .map_err(Arc::new)?;
+
+        for migration in MIGRATIONS {
+            sqlx::query(migration)
+                .execute(&pool)
+                .await
+                .map_err(Arc::new)?;
+        }
+
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl HistoryStore for SqlHistoryStore {
+    async fn record(
+        &self,
+        chain: &Chain,
+        address: &str,
+        height: u64,
+        observed_at: u64,
+        balances: &[Balance],
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await.map_err(Arc::new)?;
+
+        for balance in balances {
+            for amount in &balance.balances {
+                sqlx::query(
+                    "INSERT INTO balance_history
+                        (chain, address, asset_id, kind, height, observed_at, amount)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    ON CONFLICT DO NOTHING",
+                )
+                .bind(chain.to_string())
+                .bind(address)
+                .bind(&balance.asset_id)
+                .bind(kind_to_string(&amount.kind))
+                .bind(to_i64(height)?)
+                .bind(to_i64(observed_at)?)
+                .bind(amount.amount.to_string())
+                .execute(&mut tx)
+                .await
+                .map_err(Arc::new)?;
+            }
+        }
+
+        tx.commit().await.map_err(Arc::new)?;
+
+        Ok(())
+    }
+
+    async fn query(
+        &self,
+        chain: &Chain,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<Record>, AppError> {
+        let rows = sqlx::query(
+            "SELECT asset_id, kind, height, observed_at, amount
+            FROM balance_history h
+            WHERE chain = $1 AND address = $2 AND observed_at <= $3
+                AND (observed_at >= $4 OR observed_at = (
+                    SELECT MAX(p.observed_at) FROM balance_history p
+                    WHERE p.chain = h.chain AND p.address = h.address
+                        AND p.asset_id = h.asset_id AND p.kind = h.kind
+                        AND p.observed_at < $5
+                ))
+            ORDER BY observed_at, height",
+        )
+        .bind(chain.to_string())
+        .bind(address)
+        .bind(to_i64(to)?)
+        .bind(to_i64(from)?)
+        .bind(to_i64(from)?)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Arc::new)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let amount = row.try_get::<String, _>("amount").map_err(Arc::new)?;
+                Ok(Record {
+                    asset_id: row.try_get("asset_id").map_err(Arc::new)?,
+                    kind: kind_from_string(row.try_get("kind").map_err(Arc::new)?)?,
+                    height: row.try_get::<i64, _>("height").map_err(Arc::new)? as u64,
+                    observed_at: row.try_get::<i64, _>("observed_at").map_err(Arc::new)? as u64,
+                    amount: amount
+                        .parse()
+                        .map_err(|_| AppError::AmountOverflow(amount.clone()))?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn kind_to_string(kind: &BalanceKind) -> String {
+    match serde_json::to_value(kind) {
+        Ok(serde_json::Value::String(kind)) => kind,
+        _ => unreachable!("BalanceKind is serialized as a string"),
+    }
+}
+
+fn kind_from_string(kind: String) -> Result<BalanceKind, AppError> {
+    serde_json::from_value(serde_json::Value::String(kind))
+        .map_err(|e| AppError::UnexpectedBalanceKind(e.to_string()))
+}
+
+fn to_i64(value: u64) -> Result<i64, AppError> {
+    i64::try_from(value).map_err(|_| AppError::AmountOverflow(value.to_string()))
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::node_clients::Chain;
+use crate::service::{Balance, Staleness};
+
+/// Chain, address and asset id of a balance, `None` for the native one
+type Key = (Chain, String, Option<String>);
+
+/// Entries recorded before the first sweep of the expired ones
+const INITIAL_SWEEP_SIZE: usize = 1024;
+
+/// Last successfully read balances, served in place of the fresh ones while the node fails
+pub struct LastKnown {
+    max_staleness: HashMap<Chain, Duration>,
+    balances: RwLock<Balances>,
+}
+
+struct Balances {
+    entries: HashMap<Key, (Balance, Instant)>,
+    next_sweep: usize,
+}
+
+impl LastKnown {
+    /// Keeps the balances of the chains with a `max_staleness` only
+    pub fn new(max_staleness: HashMap<Chain, Duration>) -> Self {
+        Self {
+            max_staleness,
+            balances: RwLock::new(Balances {
+                entries: HashMap::new(),
+                next_sweep: INITIAL_SWEEP_SIZE,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self, chain: &Chain) -> bool {
+        self.max_staleness.contains_key(chain)
+    }
+
+    /// Records the `balances` read from the node, the first one is the native balance if `native`
+    pub fn record(&self, chain: &Chain, address: &str, native: bool, balances: &[Balance]) {
+        let max_staleness = match self.max_staleness.get(chain) {
+            Some(max_staleness) => *max_staleness,
+            None => return,
+        };
+
+        let now = Instant::now();
+        let mut state = self.balances.write().expect("last known lock is poisoned");
+
+        for (i, balance) in balances.iter().enumerate() {
+            let asset_id = (!native || i > 0).then(|| balance.asset_id.clone());
+            state.entries.insert(
+                (chain.clone(), address.to_string(), asset_id),
+                (balance.clone(), now),
+            );
+        }
+
+        // the entries are looked up by the requested keys only, so the expired ones
+        // of the addresses not requested anymore are swept from time to time
+        if state.entries.len() >= state.next_sweep {
+            state
+                .entries
+                .retain(|_, (_, read_at)| now.duration_since(*read_at) <= max_staleness);
+            state.next_sweep = (state.entries.len() * 2).max(INITIAL_SWEEP_SIZE);
+        }
+    }
+
+    /// Last known native balance, if `native`, followed by the balances of `asset_ids`
+    /// marked as stale, `None` if any of them is unknown or older than the max staleness
+    pub fn get(
+        &self,
+        chain: &Chain,
+        address: &str,
+        native: bool,
+        asset_ids: &[String],
+    ) -> Option<Vec<Balance>> {
+        let max_staleness = self.max_staleness.get(chain)?;
+        let state = self.balances.read().expect("last known lock is poisoned");
+
+        native
+            .then_some(None)
+            .into_iter()
+            .chain(asset_ids.iter().cloned().map(Some))
+            .map(|asset_id| {
+                let (balance, read_at) =
+                    state
+                        .entries
+                        .get(&(chain.clone(), address.to_string(), asset_id))?;
+                let age = read_at.elapsed();
+
+                (age <= *max_staleness).then(|| Balance {
+                    staleness: Some(Staleness {
+                        stale: true,
+                        age: age.as_secs(),
+                    }),
+                    ..balance.clone()
+                })
+            })
+            .collect()
+    }
+}
@@ -1,13 +1,35 @@
+mod alerts;
 mod api;
+mod attestation;
+mod cache;
+mod caip;
+mod clock;
 mod config;
 pub mod error;
+mod history;
+mod last_known;
 pub mod node_clients;
+mod portfolio;
+mod reconciliation;
+mod registry;
 mod service;
+mod single_flight;
+mod snapshots;
+mod subscriptions;
 mod tracing;
+mod valuation;
+mod webhooks;
 
 use ::tracing::info;
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::alerts::AlertStore;
 use crate::error::Error as AppError;
+use crate::history::HistoryStore;
+use crate::service::BalancesService;
+use crate::snapshots::SnapshotStore;
+use crate::webhooks::WatchlistStore;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
@@ -16,15 +38,114 @@ async fn main() -> Result<(), anyhow::Error> {
     let config = config::load()?;
     info!("config loaded: {:?}", &config);
 
+    let chains = config.chains.keys().cloned().collect::<Vec<_>>();
+    let caip = caip::Caip::new(&config.chains);
+    let max_staleness = config
+        .chains
+        .iter()
+        .filter_map(|(chain, config)| {
+            config
+                .max_staleness()
+                .map(|max_staleness| (chain.clone(), Duration::from_millis(max_staleness)))
+        })
+        .collect();
+
+    let registry = Arc::new(registry::Registry::try_new(&config.assets)?);
+
+    let cache = config
+        .cache
+        .as_ref()
+        .map(cache::Cache::try_new)
+        .transpose()?
+        .map(Arc::new);
+
     let node_clients = config
         .chains
         .into_iter()
-        .map(|(chain, config)| node_clients::new(&config).map(|node_client| (chain, node_client)))
+        .map(|(chain, config)| {
+            node_clients::new(&chain, &config, cache.as_ref())
+                .map(|node_client| (chain, node_client))
+        })
         .collect::<Result<_, AppError>>()?;
 
-    let service = service::Service::new(node_clients);
+    let service: Arc<Box<dyn BalancesService + Send + Sync>> = Arc::new(Box::new(
+        service::Service::new(node_clients, caip, max_staleness, cache),
+    ));
+
+    let watchlist = match &config.webhooks {
+        Some(webhooks_config) => {
+            let store: Arc<dyn WatchlistStore + Send + Sync> =
+                Arc::new(webhooks::InMemoryWatchlistStore::default());
+            let dispatcher = Arc::new(webhooks::Dispatcher::try_new(
+                webhooks_config,
+                store.clone(),
+            )?);
+            webhooks::spawn_pollers(chains.clone(), service.clone(), store.clone(), dispatcher);
+            Some((store, webhooks::CallbackPolicy::new(webhooks_config)))
+        }
+        None => None,
+    };
+
+    let history = match &config.history {
+        Some(history_config) => {
+            let store: Arc<dyn HistoryStore + Send + Sync> =
+                Arc::new(history::SqlHistoryStore::connect(history_config).await?);
+            history::spawn_recorders(
+                chains.clone(),
+                service.clone(),
+                store.clone(),
+                history_config.addresses.clone(),
+                watchlist.as_ref().map(|(store, _)| store.clone()),
+            );
+            Some(store)
+        }
+        None => None,
+    };
+
+    let snapshots = match &config.snapshots {
+        Some(snapshots_config) => {
+            let store: Arc<dyn SnapshotStore + Send + Sync> =
+                Arc::new(snapshots::SqlSnapshotStore::connect(snapshots_config).await?);
+            Some(Arc::new(
+                snapshots::Snapshots::start(snapshots_config, service.clone(), store).await?,
+            ))
+        }
+        None => None,
+    };
+
+    let alerts = match &config.alerts {
+        Some(alerts_config) => {
+            let store: Arc<dyn AlertStore + Send + Sync> =
+                Arc::new(alerts::InMemoryAlertStore::default());
+            for spec in &alerts_config.rules {
+                store.insert_rule(alerts::Rule::new(spec.clone())).await?;
+            }
+            let notifier = Arc::new(alerts::Notifier::try_new(alerts_config)?);
+            alerts::spawn_monitors(chains, service.clone(), store.clone(), notifier);
+            Some(store)
+        }
+        None => None,
+    };
+
+    let attester = config
+        .attestation
+        .as_ref()
+        .map(attestation::Attester::try_new)
+        .transpose()?
+        .map(Arc::new);
 
-    let api = api::server::Server::try_new(&config.api, Box::new(service))?;
+    let api = api::server::Server::try_new(
+        &config.api,
+        service,
+        registry,
+        api::server::Features {
+            watchlist,
+            history,
+            snapshots,
+            alerts,
+            attester,
+        },
+    )?;
 
     api.run().await?;
 
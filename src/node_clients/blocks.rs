@@ -0,0 +1,37 @@
+use futures::stream::{self, BoxStream, StreamExt};
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::error::Error as AppError;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Stream of new block heights produced by polling `fetch_height`
+///
+/// Heights are emitted only when they grow, polling failures are logged and skipped
+pub fn poll_heights<F, Fut>(fetch_height: F) -> BoxStream<'static, u64>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<u64, AppError>> + Send,
+{
+    let interval = tokio::time::interval(POLL_INTERVAL);
+
+    stream::unfold(
+        (fetch_height, interval, 0),
+        |(fetch_height, mut interval, last_height)| async move {
+            loop {
+                interval.tick().await;
+
+                match fetch_height().await {
+                    Ok(height) if height > last_height => {
+                        return Some((height, (fetch_height, interval, height)));
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("failed to poll block height: {}", e),
+                }
+            }
+        },
+    )
+    .boxed()
+}
@@ -0,0 +1,314 @@
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::{
+    error::Error as AppError,
+    service::{AddressBalancesService, Balance, BlockRef, PinnedBalances, VerifiedBalances},
+    valuation::Price,
+};
+
+/// Concurrency limit of the reads of a chain
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+    /// Reads running at once
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    /// Reads waiting for a running one to finish, the next ones are rejected
+    #[serde(default = "default_max_queued")]
+    pub max_queued: usize,
+    /// Time a read may wait in the queue in milliseconds
+    #[serde(default = "default_queue_timeout")]
+    pub queue_timeout: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_concurrent: default_max_concurrent(),
+            max_queued: default_max_queued(),
+            queue_timeout: default_queue_timeout(),
+        }
+    }
+}
+
+type Client = Box<dyn AddressBalancesService + Send + Sync>;
+
+/// Bounds the reads of a chain, so a slow node holds at most `max_concurrent`
+/// requests of the service instead of all of them
+pub struct Bulkhead {
+    name: String,
+    client: Client,
+    permits: Semaphore,
+    queued: AtomicUsize,
+    max_queued: usize,
+    queue_timeout: Duration,
+}
+
+/// Leaves the queue when dropped, including when the waiting read is cancelled
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Bulkhead {
+    pub fn new(name: impl AsRef<str>, client: Client, config: &Config) -> Self {
+        Self {
+            name: name.as_ref().to_string(),
+            client,
+            permits: Semaphore::new(config.max_concurrent),
+            queued: AtomicUsize::new(0),
+            max_queued: config.max_queued,
+            queue_timeout: Duration::from_millis(config.queue_timeout),
+        }
+    }
+
+    async fn call<T>(
+        &self,
+        read: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        let _permit = match self.permits.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queued {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
+                    return Err(AppError::Saturated(format!(
+                        "{} has {} reads queued",
+                        self.name, self.max_queued
+                    )));
+                }
+                let _slot = QueueSlot(&self.queued);
+
+                tokio::time::timeout(self.queue_timeout, self.permits.acquire())
+                    .await
+                    .map_err(|_| {
+                        AppError::Saturated(format!(
+                            "{} has not started the read in {}ms",
+                            self.name,
+                            self.queue_timeout.as_millis()
+                        ))
+                    })?
+                    .expect("bulkhead semaphore is never closed")
+            }
+        };
+
+        read.await
+    }
+}
+
+#[async_trait::async_trait]
+impl AddressBalancesService for Bulkhead {
+    fn is_asset_supported(&self, asset_id: String) -> bool {
+        self.client.is_asset_supported(asset_id)
+    }
+
+    fn new_blocks(&self) -> BoxStream<'static, u64> {
+        self.client.new_blocks()
+    }
+
+    async fn get_balance(&self, address: String) -> Result<Balance, AppError> {
+        self.call(self.client.get_balance(address)).await
+    }
+
+    async fn get_assets_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.call(self.client.get_assets_balances(address, asset_ids))
+            .await
+    }
+
+    async fn get_all_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.call(self.client.get_all_balances(address, asset_ids))
+            .await
+    }
+
+    async fn get_pinned_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        self.call(self.client.get_pinned_balances(address, asset_ids, native))
+            .await
+    }
+
+    async fn get_verified_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<VerifiedBalances, AppError> {
+        self.call(
+            self.client
+                .get_verified_balances(address, asset_ids, native),
+        )
+        .await
+    }
+
+    async fn get_block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+        self.call(self.client.get_block(height)).await
+    }
+
+    fn supports_balances_at(&self) -> bool {
+        self.client.supports_balances_at()
+    }
+
+    async fn get_balance_at(&self, address: String, block: BlockRef) -> Result<Balance, AppError> {
+        self.call(self.client.get_balance_at(address, block)).await
+    }
+
+    async fn get_assets_balances_at(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        block: BlockRef,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.call(
+            self.client
+                .get_assets_balances_at(address, asset_ids, block),
+        )
+        .await
+    }
+
+    async fn get_prices(&self, asset_ids: Vec<String>) -> Result<HashMap<String, Price>, AppError> {
+        self.call(self.client.get_prices(asset_ids)).await
+    }
+}
+
+fn default_max_concurrent() -> usize {
+    32
+}
+
+fn default_max_queued() -> usize {
+    64
+}
+
+fn default_queue_timeout() -> u64 {
+    5000
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream::{self, StreamExt};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::service::BalanceKind;
+
+    /// Node answering after the `delay`, never if `None`
+    struct Node {
+        delay: Option<Duration>,
+    }
+
+    #[async_trait::async_trait]
+    impl AddressBalancesService for Node {
+        fn is_asset_supported(&self, _asset_id: String) -> bool {
+            true
+        }
+
+        fn new_blocks(&self) -> BoxStream<'static, u64> {
+            stream::empty().boxed()
+        }
+
+        async fn get_balance(&self, _address: String) -> Result<Balance, AppError> {
+            match self.delay {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => std::future::pending().await,
+            }
+            Ok(Balance::single("ETH", &BalanceKind::Wallet, 5))
+        }
+
+        async fn get_assets_balances(
+            &self,
+            _address: String,
+            _asset_ids: Vec<String>,
+        ) -> Result<Vec<Balance>, AppError> {
+            Ok(vec![])
+        }
+
+        async fn get_pinned_balances(
+            &self,
+            _address: String,
+            _asset_ids: Vec<String>,
+            _native: bool,
+        ) -> Result<PinnedBalances, AppError> {
+            Err(AppError::BalancesAtBlockNotSupported)
+        }
+    }
+
+    fn bulkhead(delay: Option<Duration>, queue_timeout: u64) -> Arc<Bulkhead> {
+        let config = Config {
+            max_concurrent: 1,
+            max_queued: 1,
+            queue_timeout,
+        };
+        Arc::new(Bulkhead::new("ethereum", Box::new(Node { delay }), &config))
+    }
+
+    fn read(bulkhead: &Arc<Bulkhead>) -> tokio::task::JoinHandle<Result<Balance, AppError>> {
+        let bulkhead = bulkhead.clone();
+        tokio::spawn(async move { bulkhead.get_balance("address".to_string()).await })
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    #[tokio::test]
+    async fn rejects_reads_beyond_the_queue() {
+        let bulkhead = bulkhead(None, 60_000);
+        let running = read(&bulkhead);
+        settle().await;
+        let queued = read(&bulkhead);
+        settle().await;
+
+        match bulkhead.get_balance("address".to_string()).await {
+            Err(AppError::Saturated(message)) => assert!(message.contains("1 reads queued")),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        running.abort();
+        queued.abort();
+    }
+
+    #[tokio::test]
+    async fn rejects_reads_queued_longer_than_the_timeout() {
+        let bulkhead = bulkhead(None, 50);
+        let running = read(&bulkhead);
+        settle().await;
+
+        match bulkhead.get_balance("address".to_string()).await {
+            Err(AppError::Saturated(message)) => assert!(message.contains("in 50ms")),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        // the timed out read has left the queue
+        assert_eq!(bulkhead.queued.load(Ordering::SeqCst), 0);
+        running.abort();
+    }
+
+    #[tokio::test]
+    async fn starts_queued_reads_once_running_ones_finish() {
+        let bulkhead = bulkhead(Some(Duration::from_millis(20)), 60_000);
+        let running = read(&bulkhead);
+        settle().await;
+        let queued = read(&bulkhead);
+
+        assert!(running.await.unwrap().is_ok());
+        assert!(queued.await.unwrap().is_ok());
+        assert_eq!(bulkhead.queued.load(Ordering::SeqCst), 0);
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::{evm, waves, Chain};
+use super::{bulkhead, evm, substrate, waves, Chain};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase", untagged)]
@@ -8,6 +8,7 @@ pub enum Config {
     Ethereum(evm::Config),
     Bsc(evm::Config),
     Waves(waves::Config),
+    Polkadot(substrate::Config),
 }
 
 impl Config {
@@ -16,6 +17,7 @@ impl Config {
             Self::Ethereum(_) => Chain::Ethereum,
             Self::Bsc(_) => Chain::BSC,
             Self::Waves(_) => Chain::Waves,
+            Self::Polkadot(_) => Chain::Polkadot,
         }
     }
 
@@ -24,6 +26,23 @@ impl Config {
             Self::Ethereum(_) => "ETHEREUM".to_string(),
             Self::Bsc(_) => "BNB".to_string(),
             Self::Waves(_) => "WAVES".to_string(),
+            Self::Polkadot(_) => "DOT".to_string(),
+        }
+    }
+
+    pub fn max_staleness(&self) -> Option<u64> {
+        match self {
+            Self::Ethereum(config) | Self::Bsc(config) => config.max_staleness,
+            Self::Waves(config) => config.max_staleness,
+            Self::Polkadot(config) => config.max_staleness,
+        }
+    }
+
+    pub fn bulkhead(&self) -> &bulkhead::Config {
+        match self {
+            Self::Ethereum(config) | Self::Bsc(config) => &config.bulkhead,
+            Self::Waves(config) => &config.bulkhead,
+            Self::Polkadot(config) => &config.bulkhead,
         }
     }
 }
@@ -1,5 +1,12 @@
 use ethabi::ethereum_types::U64;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::proof::BalanceSlot;
+use crate::node_clients::{
+    bulkhead::Config as BulkheadConfig, quorum::Config as QuorumConfig,
+    upstream::Config as UpstreamConfig,
+};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
@@ -7,4 +14,40 @@ pub struct Config {
     pub base_url: String,
     pub supported_asset_ids: Option<Vec<String>>,
     pub multicall_contract_address: Option<String>,
+    /// Balances of the tokens read at once if there is no multicall contract
+    #[serde(default = "default_token_read_concurrency")]
+    pub token_read_concurrency: usize,
+    /// Asset id (or the native token) -> Chainlink USD price feed
+    #[serde(default)]
+    pub price_feeds: HashMap<String, PriceFeed>,
+    /// Token address -> storage slot of the balances mapping for verified reads,
+    /// discovered for the other tokens
+    #[serde(default)]
+    pub balance_slots: HashMap<String, BalanceSlot>,
+    /// Balances are returned only if enough providers agree on them,
+    /// verified reads need it to check the block of the proofs
+    #[serde(default)]
+    pub quorum: Option<QuorumConfig>,
+    /// Timeouts, retries and circuit breaker of the node calls
+    #[serde(default)]
+    pub upstream: UpstreamConfig,
+    /// Concurrency limit of the reads of the chain
+    #[serde(default)]
+    pub bulkhead: BulkheadConfig,
+    /// Age in milliseconds up to which the last known balances are served
+    /// while the node fails, they are not kept if absent
+    #[serde(default)]
+    pub max_staleness: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PriceFeed {
+    /// Address of the aggregator, e.g. of the `ETH / USD` feed
+    pub address: String,
+    /// Decimals of the asset amounts
+    pub decimals: u8,
+}
+
+fn default_token_read_concurrency() -> usize {
+    8
 }
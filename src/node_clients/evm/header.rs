@@ -0,0 +1,158 @@
+use ethers_core::abi::ethereum_types::H64;
+use ethers_core::types::{Address, Bloom, Bytes, H256, U256, U64};
+use ethers_core::utils::keccak256;
+use ethers_core::utils::rlp::RlpStream;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error as AppError;
+
+/// Block header as returned by `eth_getBlockByNumber`
+///
+/// The fields added by the forks are absent in the blocks before them
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Header {
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub sha3_uncles: H256,
+    pub miner: Address,
+    pub state_root: H256,
+    pub transactions_root: H256,
+    pub receipts_root: H256,
+    pub logs_bloom: Bloom,
+    pub difficulty: U256,
+    pub number: U64,
+    pub gas_limit: U256,
+    pub gas_used: U256,
+    pub timestamp: U64,
+    pub extra_data: Bytes,
+    pub mix_hash: H256,
+    pub nonce: H64,
+    /// London
+    #[serde(default)]
+    pub base_fee_per_gas: Option<U256>,
+    /// Shanghai
+    #[serde(default)]
+    pub withdrawals_root: Option<H256>,
+    /// Cancun
+    #[serde(default)]
+    pub blob_gas_used: Option<U64>,
+    #[serde(default)]
+    pub excess_blob_gas: Option<U64>,
+    #[serde(default)]
+    pub parent_beacon_block_root: Option<H256>,
+    /// Prague
+    #[serde(default)]
+    pub requests_hash: Option<H256>,
+}
+
+impl Header {
+    /// Fails unless `hash` is the hash of the other fields, so the state root
+    /// is the one of the block of `hash`
+    pub fn verify(&self) -> Result<(), AppError> {
+        let computed = self.compute_hash();
+        if computed != self.hash {
+            return Err(AppError::InvalidProof(format!(
+                "header of block {} hashes to {:?} instead of {:?}",
+                self.number, computed, self.hash
+            )));
+        }
+        Ok(())
+    }
+
+    fn compute_hash(&self) -> H256 {
+        let optional = [
+            self.base_fee_per_gas.map(|fee| rlp(&fee)),
+            self.withdrawals_root.map(|root| rlp(&root)),
+            self.blob_gas_used.map(|gas| rlp(&gas)),
+            self.excess_blob_gas.map(|gas| rlp(&gas)),
+            self.parent_beacon_block_root.map(|root| rlp(&root)),
+            self.requests_hash.map(|hash| rlp(&hash)),
+        ];
+        // the fields of a fork are present in the headers of the later forks
+        let optional = optional
+            .into_iter()
+            .take_while(Option::is_some)
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let mut stream = RlpStream::new_list(15 + optional.len());
+        stream
+            .append(&self.parent_hash)
+            .append(&self.sha3_uncles)
+            .append(&self.miner)
+            .append(&self.state_root)
+            .append(&self.transactions_root)
+            .append(&self.receipts_root)
+            .append(&self.logs_bloom)
+            .append(&self.difficulty)
+            .append(&self.number)
+            .append(&self.gas_limit)
+            .append(&self.gas_used)
+            .append(&self.timestamp)
+            .append(&self.extra_data.as_ref())
+            .append(&self.mix_hash)
+            .append(&self.nonce);
+        for field in optional {
+            stream.append_raw(&field, 1);
+        }
+
+        H256(keccak256(stream.out()))
+    }
+}
+
+fn rlp<T: ethers_core::utils::rlp::Encodable>(value: &T) -> Vec<u8> {
+    ethers_core::utils::rlp::encode(value).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `eth_getBlockByNumber("0x0", false)` of Ethereum mainnet
+    const MAINNET_GENESIS: &str = r#"{
+        "hash": "0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3",
+        "parentHash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+        "sha3Uncles": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+        "miner": "0x0000000000000000000000000000000000000000",
+        "stateRoot": "0xd7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544",
+        "transactionsRoot": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+        "receiptsRoot": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+        "logsBloom": "0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "difficulty": "0x400000000",
+        "number": "0x0",
+        "gasLimit": "0x1388",
+        "gasUsed": "0x0",
+        "timestamp": "0x0",
+        "extraData": "0x11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa",
+        "mixHash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+        "nonce": "0x0000000000000042",
+        "totalDifficulty": "0x400000000",
+        "size": "0x21c",
+        "transactions": [],
+        "uncles": []
+    }"#;
+
+    /// `eth_getBlockByNumber("0x3", false)` of a Ganache dev chain
+    const GANACHE_BLOCK: &str = r#"{"number":"0x3","hash":"0xda53da08ef6a3cbde84c33e51c04f68c3853b6a3731f10baa2324968eee63972","parentHash":"0x689c70c080ca22bc0e681694fa803c1aba16a69c8b6368fed5311d279eb9de90","mixHash":"0x0000000000000000000000000000000000000000000000000000000000000000","nonce":"0x0000000000000000","sha3Uncles":"0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347","logsBloom":"0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","transactionsRoot":"0x7270c1c4440180f2bd5215809ee3d545df042b67329499e1ab97eb759d31610d","stateRoot":"0x29f32984517a7d25607da485b23cefabfd443751422ca7e603395e1de9bc8a4b","receiptsRoot":"0x056b23fbba480696b65fe5a59b8f2148a1299103c4f57df839233af2cf4ca2d2","miner":"0x0000000000000000000000000000000000000000","difficulty":"0x0","totalDifficulty":"0x0","extraData":"0x","size":"0x3e8","gasLimit":"0x6691b7","gasUsed":"0x5208","timestamp":"0x5ecedbb9","transactions":["0xc3c5f700243de37ae986082fd2af88d2a7c2752a0c0f7b9d6ac47c729d45e067"],"uncles":[]}"#;
+
+    #[test]
+    fn computes_hash_of_mainnet_genesis() {
+        let header: Header = serde_json::from_str(MAINNET_GENESIS).unwrap();
+        assert_eq!(header.compute_hash(), header.hash);
+        assert!(header.verify().is_ok());
+    }
+
+    #[test]
+    fn computes_hash_of_dev_chain_block() {
+        let header: Header = serde_json::from_str(GANACHE_BLOCK).unwrap();
+        assert_eq!(header.compute_hash(), header.hash);
+    }
+
+    #[test]
+    fn rejects_header_with_other_state_root() {
+        let mut header: Header = serde_json::from_str(MAINNET_GENESIS).unwrap();
+        header.state_root = H256::repeat_byte(1);
+        assert!(matches!(header.verify(), Err(AppError::InvalidProof(_))));
+    }
+}
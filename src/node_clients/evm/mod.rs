@@ -1,90 +1,135 @@
 mod config;
+mod header;
+mod proof;
+mod transport;
 
 use ethabi::{Function, Param, ParamType, StateMutability, Token};
 use ethers_core::types::transaction::eip2718::TypedTransaction;
 use ethers_core::types::transaction::eip2930::AccessList;
-use ethers_core::types::{Address, Eip1559TransactionRequest, NameOrAddress, U256, U64};
-use ethers_providers::{Http, Middleware, Provider};
-use futures::stream::TryStreamExt;
-use std::sync::Arc;
+use ethers_core::types::{
+    Address, BlockId, BlockNumber, Eip1559TransactionRequest, NameOrAddress, H256, U256, U64,
+};
+use ethers_providers::{Middleware, Provider};
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use std::{collections::HashSet, str::FromStr};
 
 use crate::{
+    cache::MetadataCache,
     error::Error as AppError,
-    service::{AddressBalancesService, Balance, BalanceAmount, BalanceKind},
+    node_clients::rate_limit::RateLimiter,
+    service::{
+        AddressBalancesService, Balance, BalanceAmount, BalanceKind, BlockRef, PinnedBalances,
+        VerifiedBalance, VerifiedBalances,
+    },
+    valuation::{Currency, Price},
 };
 
-pub use config::Config;
+pub use config::{Config, PriceFeed};
+use header::Header;
+pub use proof::{BalanceSlot, SlotLayout};
+pub use transport::{Metered, Transport};
 
 static BALANCE_OF_FUNCTION_NAME: &str = "balanceOf";
 static AGGREGATE_FUNCTION_NAME: &str = "aggregate";
+static LATEST_ROUND_DATA_FUNCTION_NAME: &str = "latestRoundData";
+static DECIMALS_FUNCTION_NAME: &str = "decimals";
+static GET_ETH_BALANCE_FUNCTION_NAME: &str = "getEthBalance";
+static GET_PROOF_METHOD: &str = "eth_getProof";
+static GET_BLOCK_BY_NUMBER_METHOD: &str = "eth_getBlockByNumber";
 
-mod dtos {
-    use serde::Deserialize;
-
-    #[derive(Clone, Debug, Deserialize)]
-    pub struct AddressAssetBalance {
-        #[serde(rename = "assetId")]
-        pub asset_id: String,
-        pub balance: u64,
-    }
+/// Slots probed by the discovery of the balances mapping of a token
+const DISCOVERY_SLOTS: u64 = 20;
 
-    #[derive(Clone, Debug, Deserialize)]
-    pub struct AddressAssetsBalancesResponse {
-        pub address: String,
-        pub balances: Vec<AddressAssetBalance>,
-    }
-}
+/// Metadata name of the discovered balances mapping of a token, `null` if none was found
+static BALANCE_SLOT_METADATA_NAME: &str = "balance_slot";
 
 pub struct NodeClient {
-    provider: ethers_providers::Provider<Http>,
+    provider: ethers_providers::Provider<Metered>,
     native_token: String,
     chain_id: U64,
     supported_asset_ids: Option<HashSet<String>>,
     multicall_contract_address: Option<Address>,
+    /// Balances of the tokens read at once without the multicall contract
+    token_read_concurrency: usize,
+    /// Asset id -> (price feed address, asset decimals)
+    price_feeds: HashMap<String, (Address, u8)>,
+    /// Token address -> balances mapping slot, configured or discovered
+    balance_slots: RwLock<HashMap<Address, BalanceSlot>>,
+    /// Whether the block of the proofs is checked by a quorum of providers,
+    /// the reads are not verified by a single node otherwise
+    verifiable: bool,
+    /// Decimals of the price feeds
+    metadata: Option<MetadataCache>,
 }
 
 impl NodeClient {
+    /// Client of the node at `base_url`, which is the one of the `config` or of a quorum provider
     pub fn try_new(
         base_url: impl AsRef<str>,
         native_token: impl AsRef<str>,
-        chain_id: &U64,
-        supported_asset_ids: &Option<&[impl AsRef<str>]>,
-        multicall_contract_address: &Option<impl AsRef<str>>,
+        config: &Config,
+        rate_limiter: Arc<RateLimiter>,
+        metadata: Option<MetadataCache>,
     ) -> Result<Self, AppError> {
-        let provider = Provider::<Http>::try_from(base_url.as_ref())?;
+        let transport = Transport::try_new(base_url, &config.upstream)?;
+        let provider = Provider::new(Metered::new(transport, rate_limiter));
 
-        let multicall_contract_address = match multicall_contract_address {
+        let multicall_contract_address = match &config.multicall_contract_address {
             Some(multicall_contract_address) => {
-                Address::from_str(multicall_contract_address.as_ref()).map(|s| Some(s))
+                Address::from_str(multicall_contract_address.as_ref()).map(Some)
             }
             _ => Ok(None),
         }?;
 
+        let price_feeds = config
+            .price_feeds
+            .iter()
+            .map(|(asset_id, feed)| {
+                Address::from_str(&feed.address)
+                    .map(|address| (asset_id.clone(), (address, feed.decimals)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let balance_slots = config
+            .balance_slots
+            .iter()
+            .map(|(token, slot)| Address::from_str(token).map(|token| (token, *slot)))
+            .collect::<Result<_, _>>()?;
+
         Ok(Self {
             provider,
             native_token: native_token.as_ref().to_string(),
-            chain_id: chain_id.clone(),
-            supported_asset_ids: supported_asset_ids.map(|supported_asset_ids| {
-                supported_asset_ids
-                    .to_owned()
-                    .iter()
-                    .map(|a| a.as_ref().to_string())
-                    .collect()
-            }),
+            chain_id: config.chain_id,
+            supported_asset_ids: config
+                .supported_asset_ids
+                .as_ref()
+                .map(|supported_asset_ids| supported_asset_ids.iter().cloned().collect()),
             multicall_contract_address,
+            token_read_concurrency: config.token_read_concurrency.max(1),
+            price_feeds,
+            balance_slots: RwLock::new(balance_slots),
+            verifiable: config.quorum.is_some(),
+            metadata,
         })
     }
 
+    /// Stream of new block numbers observed by the node
+    pub fn new_heads(&self) -> BoxStream<'static, u64> {
+        self.provider.as_ref().new_heads()
+    }
+
     pub async fn address_native_balance(
         &self,
         address: impl Into<NameOrAddress> + Send + Sync,
+        block: Option<BlockId>,
     ) -> Result<U256, AppError> {
         let balance = self
             .provider
-            .get_balance(address, None)
+            .get_balance(address, block)
             .await
-            .map_err(|e| Arc::new(e))?;
+            .map_err(Arc::new)?;
 
         Ok(balance)
     }
@@ -94,48 +139,11 @@ impl NodeClient {
         address: Address,
         asset_contract_addresses: &[impl AsRef<str>],
         multicall_contract_address: Option<impl Into<Address>>,
+        block: Option<BlockId>,
     ) -> Result<Vec<U256>, AppError> {
-        let balance_of = Function {
-            name: BALANCE_OF_FUNCTION_NAME.to_owned(),
-            inputs: vec![Param {
-                name: "address".to_owned(),
-                kind: ParamType::Address,
-                internal_type: None,
-            }],
-            outputs: vec![Param {
-                name: BALANCE_OF_FUNCTION_NAME.to_owned(),
-                kind: ParamType::Uint(256),
-                internal_type: None,
-            }],
-            constant: None,
-            state_mutability: StateMutability::View,
-        };
+        let balance_of = balance_of_function();
 
-        let aggregate = Function {
-            name: AGGREGATE_FUNCTION_NAME.to_owned(),
-            inputs: vec![Param {
-                name: "calls".to_owned(),
-                kind: ParamType::Array(Box::new(ParamType::Tuple(vec![
-                    ParamType::Address,
-                    ParamType::Bytes,
-                ]))),
-                internal_type: None,
-            }],
-            outputs: vec![
-                Param {
-                    name: "blockNumber".to_owned(),
-                    kind: ParamType::Uint(256),
-                    internal_type: None,
-                },
-                Param {
-                    name: "returnData".to_owned(),
-                    kind: ParamType::Array(Box::new(ParamType::Bytes)),
-                    internal_type: None,
-                },
-            ],
-            constant: None,
-            state_mutability: StateMutability::View,
-        };
+        let aggregate = aggregate_function();
 
         match multicall_contract_address {
             Some(multicall_contract_address) => {
@@ -144,7 +152,7 @@ impl NodeClient {
                     |mut acc, asset_contract_address| {
                         let call_data = balance_of
                             .encode_input(&[Token::Address(address)])
-                            .map_err(|e| Arc::new(e))?;
+                            .map_err(Arc::new)?;
 
                         let asset_contract_address =
                             Address::from_str(asset_contract_address.as_ref())?;
@@ -162,7 +170,7 @@ impl NodeClient {
 
                 let call_data = aggregate
                     .encode_input(&[Token::Array(input_tokens)])
-                    .map_err(|e| Arc::new(e))?
+                    .map_err(Arc::new)?
                     .into();
 
                 let req = Eip1559TransactionRequest {
@@ -181,13 +189,11 @@ impl NodeClient {
 
                 let response = self
                     .provider
-                    .call(&tx, None)
+                    .call(&tx, block)
                     .await
                     .map_err(|e| AppError::EthersProvider(Arc::new(e)))?;
 
-                let output_tokens = aggregate
-                    .decode_output(&response.0)
-                    .map_err(|e| Arc::new(e))?;
+                let output_tokens = aggregate.decode_output(&response.0).map_err(Arc::new)?;
 
                 let balances_token =
                     output_tokens
@@ -203,10 +209,10 @@ impl NodeClient {
                             .iter()
                             .map(|result_token| match result_token {
                                 Token::Bytes(bytes) => balance_of
-                                    .decode_output(&bytes)
+                                    .decode_output(bytes)
                                     .map_err(|e| AppError::Ethabi(Arc::new(e)))
                                     .and_then(|result| {
-                                        result.get(0).and_then(|t| t.clone().into_uint()).ok_or(
+                                        result.first().and_then(|t| t.clone().into_uint()).ok_or(
                                             AppError::MissingAbiOutputToken(
                                                 BALANCE_OF_FUNCTION_NAME.to_owned(),
                                             ),
@@ -223,112 +229,1127 @@ impl NodeClient {
                 Ok(balances)
             }
             None => {
-                let stream = futures::stream::iter(
-                    asset_contract_addresses
-                        .iter()
-                        .map(|c| Result::<String, AppError>::Ok(c.as_ref().to_owned())),
-                );
-                let balances = stream
-                    .try_fold(vec![], |mut acc, asset_contract_address| {
-                        let address = address.clone();
-                        let balance_of = balance_of.clone();
+                let reads = asset_contract_addresses
+                    .iter()
+                    .map(|asset_contract_address| {
+                        let asset_contract_address = asset_contract_address.as_ref().to_owned();
+                        let balance_of = &balance_of;
                         async move {
-                            let asset_contract_address =
-                                Address::from_str(asset_contract_address.as_ref())?;
-
-                            let call_data = balance_of
-                                .encode_input(&[Token::Address(address)])
-                                .map_err(|e| Arc::new(e))?
-                                .into();
-
-                            let req = Eip1559TransactionRequest {
-                                from: None,
-                                to: Some(NameOrAddress::Address(asset_contract_address)),
-                                gas: None,
-                                value: None,
-                                data: Some(call_data),
-                                nonce: None,
-                                access_list: AccessList(vec![]),
-                                max_priority_fee_per_gas: None,
-                                max_fee_per_gas: None,
-                                chain_id: Some(self.chain_id),
-                            };
-                            let tx = TypedTransaction::Eip1559(req);
-
-                            let response = self
-                                .provider
-                                .call(&tx, None)
-                                .await
-                                .map_err(|e| AppError::EthersProvider(Arc::new(e)))?;
-
-                            let result = balance_of
-                                .decode_output(&response.0)
-                                .map_err(|e| Arc::new(e))?;
-
-                            let balance = result.get(0).and_then(|t| t.clone().into_uint()).ok_or(
-                                AppError::MissingAbiOutputToken(
-                                    BALANCE_OF_FUNCTION_NAME.to_owned(),
-                                ),
-                            )?;
-
-                            acc.push(balance);
-
-                            Ok(acc)
+                            let balance = self
+                                .token_balance(balance_of, address, &asset_contract_address, block)
+                                .await;
+                            (asset_contract_address, balance)
                         }
                     })
-                    .await?;
+                    .collect::<Vec<_>>();
 
+                // every token is read, so all of the failed ones are reported at once
+                let results = futures::stream::iter(reads)
+                    .buffered(self.token_read_concurrency)
+                    .collect::<Vec<_>>()
+                    .await;
+
+                let mut balances = Vec::with_capacity(results.len());
+                let mut failures = vec![];
+                for (asset_contract_address, result) in results {
+                    match result {
+                        Ok(balance) => balances.push(balance),
+                        Err(e) => failures.push((asset_contract_address, e)),
+                    }
+                }
+
+                if !failures.is_empty() {
+                    return Err(AppError::TokenBalances(failures));
+                }
                 Ok(balances)
             }
         }
     }
-}
 
-#[async_trait::async_trait]
-impl AddressBalancesService for NodeClient {
-    fn is_asset_supported(&self, asset_id: String) -> bool {
-        self.supported_asset_ids
-            .as_ref()
-            .map_or(false, |supported_asset_ids| {
-                supported_asset_ids.contains(&asset_id)
+    /// Reads the balance of `address` by calling `balanceOf` of the token contract
+    async fn token_balance(
+        &self,
+        balance_of: &Function,
+        address: Address,
+        asset_contract_address: &str,
+        block: Option<BlockId>,
+    ) -> Result<U256, AppError> {
+        let asset_contract_address = Address::from_str(asset_contract_address)?;
+
+        let call_data = balance_of
+            .encode_input(&[Token::Address(address)])
+            .map_err(Arc::new)?;
+
+        let response = self.call(asset_contract_address, call_data, block).await?;
+
+        let result = balance_of.decode_output(&response).map_err(Arc::new)?;
+
+        result
+            .first()
+            .and_then(|t| t.clone().into_uint())
+            .ok_or(AppError::MissingAbiOutputToken(
+                BALANCE_OF_FUNCTION_NAME.to_owned(),
+            ))
+    }
+
+    /// Performs the `calls` (contract address, call data) in a single multicall
+    /// if the contract is configured, otherwise one by one concurrently
+    async fn call_all(
+        &self,
+        calls: Vec<(Address, Vec<u8>)>,
+        block: Option<BlockId>,
+    ) -> Result<Vec<Vec<u8>>, AppError> {
+        match self.multicall_contract_address {
+            Some(multicall_contract_address) => {
+                let aggregate = aggregate_function();
+
+                let input_tokens = calls
+                    .into_iter()
+                    .map(|(to, data)| Token::Tuple(vec![Token::Address(to), Token::Bytes(data)]))
+                    .collect();
+
+                let call_data = aggregate
+                    .encode_input(&[Token::Array(input_tokens)])
+                    .map_err(Arc::new)?;
+
+                let response = self
+                    .call(multicall_contract_address, call_data, block)
+                    .await?;
+
+                let output_tokens = aggregate.decode_output(&response).map_err(Arc::new)?;
+
+                match output_tokens.get(1) {
+                    Some(Token::Array(results)) => results
+                        .iter()
+                        .map(|result_token| match result_token {
+                            Token::Bytes(bytes) => Ok(bytes.clone()),
+                            _ => Err(AppError::UnexpectedOutputToken(result_token.to_string())),
+                        })
+                        .collect(),
+                    Some(token) => Err(AppError::UnexpectedOutputToken(token.to_string())),
+                    None => Err(AppError::MissingAbiOutputToken(
+                        AGGREGATE_FUNCTION_NAME.to_owned(),
+                    )),
+                }
+            }
+            None => {
+                futures::future::try_join_all(
+                    calls
+                        .into_iter()
+                        .map(|(to, data)| self.call(to, data, block)),
+                )
+                .await
+            }
+        }
+    }
+
+    async fn call(
+        &self,
+        to: Address,
+        data: Vec<u8>,
+        block: Option<BlockId>,
+    ) -> Result<Vec<u8>, AppError> {
+        let req = Eip1559TransactionRequest {
+            from: None,
+            to: Some(NameOrAddress::Address(to)),
+            gas: None,
+            value: None,
+            data: Some(data.into()),
+            nonce: None,
+            access_list: AccessList(vec![]),
+            max_priority_fee_per_gas: None,
+            max_fee_per_gas: None,
+            chain_id: Some(self.chain_id),
+        };
+        let tx = TypedTransaction::Eip1559(req);
+
+        let response = self
+            .provider
+            .call(&tx, block)
+            .await
+            .map_err(|e| AppError::EthersProvider(Arc::new(e)))?;
+
+        Ok(response.to_vec())
+    }
+
+    /// Reads `latestRoundData` of the price feeds of `asset_ids`, and their `decimals`
+    /// unless they are cached
+    pub async fn prices(&self, asset_ids: &[String]) -> Result<HashMap<String, Price>, AppError> {
+        let feeds = asset_ids
+            .iter()
+            .filter_map(|asset_id| {
+                self.price_feeds
+                    .get(asset_id)
+                    .map(|feed| (asset_id.clone(), *feed))
+            })
+            .collect::<HashMap<_, _>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        if feeds.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let latest_round_data = latest_round_data_function();
+        let decimals = decimals_function();
+
+        let mut cached_decimals = Vec::with_capacity(feeds.len());
+        for (_, (feed_address, _)) in &feeds {
+            cached_decimals.push(match &self.metadata {
+                Some(metadata) => {
+                    metadata
+                        .get::<u32>(&format!("{:?}", feed_address), DECIMALS_FUNCTION_NAME)
+                        .await
+                }
+                None => None,
+            });
+        }
+
+        let calls = feeds
+            .iter()
+            .zip(&cached_decimals)
+            .map(|((_, (feed_address, _)), cached)| {
+                let mut calls = vec![(*feed_address, latest_round_data.encode_input(&[])?)];
+                if cached.is_none() {
+                    calls.push((*feed_address, decimals.encode_input(&[])?));
+                }
+                Ok(calls)
+            })
+            .collect::<Result<Vec<_>, ethabi::Error>>()
+            .map_err(Arc::new)?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let mut results = self.call_all(calls, None).await?.into_iter();
+        let mut prices = HashMap::new();
+
+        for ((asset_id, (feed_address, asset_decimals)), cached) in
+            feeds.into_iter().zip(cached_decimals)
+        {
+            let round = latest_round_data
+                .decode_output(&results.next().unwrap_or_default())
+                .map_err(Arc::new)?;
+            let missing =
+                || AppError::MissingAbiOutputToken(LATEST_ROUND_DATA_FUNCTION_NAME.to_owned());
+            let answer = round
+                .get(1)
+                .cloned()
+                .and_then(Token::into_int)
+                .ok_or_else(missing)?;
+            let updated_at = round
+                .get(3)
+                .cloned()
+                .and_then(Token::into_uint)
+                .ok_or_else(missing)?;
+
+            let feed_decimals = match cached {
+                Some(feed_decimals) => feed_decimals,
+                None => {
+                    let feed_decimals = decimals
+                        .decode_output(&results.next().unwrap_or_default())
+                        .map_err(Arc::new)?
+                        .first()
+                        .cloned()
+                        .and_then(Token::into_uint)
+                        .ok_or_else(|| {
+                            AppError::MissingAbiOutputToken(DECIMALS_FUNCTION_NAME.to_owned())
+                        })?
+                        .low_u32();
+                    if let Some(metadata) = &self.metadata {
+                        metadata
+                            .set(
+                                &format!("{:?}", feed_address),
+                                DECIMALS_FUNCTION_NAME,
+                                &feed_decimals,
+                            )
+                            .await;
+                    }
+                    feed_decimals
+                }
+            };
+
+            // int256 answer, a negative or zero price is not a price
+            if answer.is_zero() || answer.bit(255) || answer.bits() > 128 {
+                continue;
+            }
+
+            prices.insert(
+                asset_id,
+                Price {
+                    currency: Currency::Usd,
+                    answer: answer.as_u128(),
+                    decimals: feed_decimals,
+                    asset_decimals: asset_decimals as u32,
+                    updated_at: Some(updated_at.low_u64()),
+                },
+            );
+        }
+
+        Ok(prices)
+    }
+
+    /// Reads the native balance and balances of `asset_ids` in a single multicall,
+    /// or by separate calls pinned to the latest block if there is no multicall contract
+    async fn all_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        block: Option<BlockId>,
+    ) -> Result<Vec<Balance>, AppError> {
+        let multicall_contract_address = match self.multicall_contract_address {
+            Some(multicall_contract_address) => multicall_contract_address,
+            None => {
+                let block = match block {
+                    Some(block) => block,
+                    None => {
+                        let number = self.provider.get_block_number().await.map_err(Arc::new)?;
+                        block_id(number.as_u64())
+                    }
+                };
+
+                let native = self.native_balance(address.clone(), Some(block));
+                if asset_ids.is_empty() {
+                    return native.await.map(|balance| vec![balance]);
+                }
+                let assets = self.assets_balances(address, asset_ids, Some(block));
+                let (native, assets) = futures::try_join!(native, assets)?;
+                return Ok(std::iter::once(native).chain(assets).collect());
+            }
+        };
+
+        let account = Address::from_str(&address)?;
+        let get_eth_balance = get_eth_balance_function();
+        let balance_of = balance_of_function();
+
+        let mut calls = vec![(
+            multicall_contract_address,
+            get_eth_balance
+                .encode_input(&[Token::Address(account)])
+                .map_err(Arc::new)?,
+        )];
+        for asset_id in &asset_ids {
+            calls.push((
+                Address::from_str(asset_id)?,
+                balance_of
+                    .encode_input(&[Token::Address(account)])
+                    .map_err(Arc::new)?,
+            ));
+        }
+
+        let results = self.call_all(calls, block).await?;
+
+        let amounts = results
+            .iter()
+            .zip(std::iter::once(&get_eth_balance).chain(std::iter::repeat(&balance_of)))
+            .map(|(result, function)| {
+                function
+                    .decode_output(result)
+                    .map_err(|e| AppError::Ethabi(Arc::new(e)))?
+                    .first()
+                    .cloned()
+                    .and_then(Token::into_uint)
+                    .ok_or_else(|| AppError::MissingAbiOutputToken(function.name.clone()))
             })
+            .collect::<Result<Vec<U256>, AppError>>()?;
+
+        let native = Balance {
+            asset_id: self.native_token.clone(),
+            caip_asset_id: None,
+            balances: vec![BalanceAmount::new(
+                &BalanceKind::Wallet,
+                to_amount(amounts[0])?,
+            )],
+            staleness: None,
+        };
+
+        std::iter::once(Ok(native))
+            .chain(
+                asset_ids
+                    .iter()
+                    .zip(&amounts[1..])
+                    .map(|(asset_id, amount)| {
+                        Ok(Balance::single(
+                            asset_id,
+                            &BalanceKind::Wallet,
+                            to_amount(*amount)?,
+                        ))
+                    }),
+            )
+            .collect()
     }
 
-    async fn get_balance(&self, address: String) -> Result<crate::service::Balance, AppError> {
+    /// Block at `height`, the latest one if `None`, the reads of a request are pinned to
+    async fn block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+        self.header(height).await.map(|header| block_ref(&header))
+    }
+
+    /// Header of the block at `height`, the latest one if `None`
+    async fn header(&self, height: Option<u64>) -> Result<Header, AppError> {
+        let number = match height {
+            Some(height) => BlockNumber::Number(height.into()),
+            None => BlockNumber::Latest,
+        };
+
+        let header: Option<Header> = self
+            .provider
+            .request(GET_BLOCK_BY_NUMBER_METHOD, (number, false))
+            .await
+            .map_err(Arc::new)?;
+
+        header.ok_or_else(|| AppError::UpstreamResponse(format!("block {} is missing", number)))
+    }
+
+    async fn proof(
+        &self,
+        address: Address,
+        keys: Vec<H256>,
+        block: BlockId,
+    ) -> Result<proof::ProofResponse, AppError> {
+        let proof = self
+            .provider
+            .request(GET_PROOF_METHOD, (address, keys, block))
+            .await
+            .map_err(Arc::new)?;
+
+        Ok(proof)
+    }
+
+    /// Reads balances at the latest block together with `eth_getProof` proofs
+    /// and verifies them against the state root of the block, which is checked
+    /// against the block hash, the hash itself is checked by the quorum
+    ///
+    /// Token balances are verified if the slot of the balances mapping is configured
+    /// or can be discovered, which needs a non-zero balance
+    async fn verified_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<VerifiedBalances, AppError> {
+        if !self.verifiable {
+            return Err(AppError::VerificationNotSupported);
+        }
+
+        let header = self.header(None).await?;
+        header.verify()?;
+
+        let block = block_ref(&header);
+        let state_root = header.state_root;
+        let at = block_id(block.block_number);
+        let holder = Address::from_str(&address)?;
+
+        let native_balance = async {
+            if !native {
+                return Ok(None);
+            }
+
+            let proof = self.proof(holder, vec![], at).await?;
+            let account = proof::verify_account(state_root, holder, &proof.account_proof)?;
+
+            Ok(Some(VerifiedBalance {
+                balance: Balance::single(
+                    &self.native_token,
+                    &BalanceKind::Wallet,
+                    to_amount(account.balance)?,
+                ),
+                verified: true,
+            }))
+        };
+
+        let assets_balances = futures::future::try_join_all(
+            asset_ids
+                .iter()
+                .map(|asset_id| self.verified_asset_balance(holder, asset_id, at, state_root)),
+        );
+
+        let (native_balance, assets_balances) =
+            futures::try_join!(native_balance, assets_balances)?;
+
+        Ok(VerifiedBalances {
+            block,
+            balances: native_balance.into_iter().chain(assets_balances).collect(),
+        })
+    }
+
+    async fn verified_asset_balance(
+        &self,
+        holder: Address,
+        asset_id: &str,
+        block: BlockId,
+        state_root: H256,
+    ) -> Result<VerifiedBalance, AppError> {
+        let token = Address::from_str(asset_id)?;
+
+        let configured = self
+            .balance_slots
+            .read()
+            .expect("balance slots lock is poisoned")
+            .get(&token)
+            .copied();
+        let slot = match configured {
+            Some(slot) => Some(slot),
+            None => self.discover_balance_slot(holder, token, block).await?,
+        };
+
+        let slot = match slot {
+            Some(slot) => slot,
+            None => {
+                let amount = self
+                    .address_assets_balances(
+                        holder,
+                        &[asset_id],
+                        self.multicall_contract_address,
+                        Some(block),
+                    )
+                    .await?
+                    .pop()
+                    .unwrap_or_default();
+
+                return Ok(VerifiedBalance {
+                    balance: Balance::single(asset_id, &BalanceKind::Wallet, to_amount(amount)?),
+                    verified: false,
+                });
+            }
+        };
+
+        let key = slot.key(holder);
+        let proof = self.proof(token, vec![key], block).await?;
+        let account = proof::verify_account(state_root, token, &proof.account_proof)?;
+
+        let storage_proof = proof
+            .storage_proof
+            .iter()
+            .find(|storage_proof| storage_proof.key == U256::from_big_endian(key.as_bytes()))
+            .ok_or_else(|| {
+                AppError::InvalidProof(format!("storage proof of {:?} is missing", key))
+            })?;
+        let amount = proof::verify_storage(account.storage_root, key, &storage_proof.proof)?;
+
+        if amount != storage_proof.value {
+            return Err(AppError::InvalidProof(format!(
+                "storage value of {:?} differs from the proven one",
+                key
+            )));
+        }
+
+        Ok(VerifiedBalance {
+            balance: Balance::single(asset_id, &BalanceKind::Wallet, to_amount(amount)?),
+            verified: true,
+        })
+    }
+
+    /// Finds the slot of the balances mapping of the `token` by comparing
+    /// storage values with the `balanceOf` of the `holder`
+    ///
+    /// The outcome, including a slot not being found, is kept in the metadata cache
+    /// for `metadata_ttl`, so the probes are not repeated by every proof of the token
+    async fn discover_balance_slot(
+        &self,
+        holder: Address,
+        token: Address,
+        block: BlockId,
+    ) -> Result<Option<BalanceSlot>, AppError> {
+        let id = format!("{:?}", token);
+        if let Some(metadata) = &self.metadata {
+            if let Some(cached) = metadata
+                .get::<Option<BalanceSlot>>(&id, BALANCE_SLOT_METADATA_NAME)
+                .await
+            {
+                if let Some(slot) = cached {
+                    self.remember_balance_slot(token, slot);
+                }
+                return Ok(cached);
+            }
+        }
+
+        let expected = self
+            .address_assets_balances(
+                holder,
+                &[id.as_str()],
+                self.multicall_contract_address,
+                Some(block),
+            )
+            .await?
+            .pop()
+            .unwrap_or_default();
+
+        // a zero balance matches any empty slot, which tells nothing about the token
+        if expected.is_zero() {
+            return Ok(None);
+        }
+
+        let candidates = (0..DISCOVERY_SLOTS).flat_map(|slot| {
+            [SlotLayout::Solidity, SlotLayout::Vyper].map(|layout| BalanceSlot { slot, layout })
+        });
+        // probed in the order of the candidates, the ones in flight are dropped once found
+        let mut values = futures::stream::iter(candidates)
+            .map(|candidate| async move {
+                self.provider
+                    .get_storage_at(token, candidate.key(holder), Some(block))
+                    .await
+                    .map(|value| (candidate, value))
+                    .map_err(|e| AppError::EthersProvider(Arc::new(e)))
+            })
+            .buffered(self.token_read_concurrency);
+
+        let mut found = None;
+        while let Some((candidate, value)) = values.try_next().await? {
+            if U256::from_big_endian(value.as_bytes()) == expected {
+                found = Some(candidate);
+                break;
+            }
+        }
+
+        if let Some(slot) = found {
+            self.remember_balance_slot(token, slot);
+        }
+        if let Some(metadata) = &self.metadata {
+            metadata.set(&id, BALANCE_SLOT_METADATA_NAME, &found).await;
+        }
+        Ok(found)
+    }
+
+    fn remember_balance_slot(&self, token: Address, slot: BalanceSlot) {
+        self.balance_slots
+            .write()
+            .expect("balance slots lock is poisoned")
+            .insert(token, slot);
+    }
+
+    async fn native_balance(
+        &self,
+        address: String,
+        block: Option<BlockId>,
+    ) -> Result<Balance, AppError> {
         let address = Address::from_str(&address)?;
         let balance = self
-            .address_native_balance(NameOrAddress::Address(address))
+            .address_native_balance(NameOrAddress::Address(address), block)
             .await?;
 
         let balance = Balance {
             asset_id: self.native_token.clone(),
+            caip_asset_id: None,
             balances: vec![BalanceAmount {
                 kind: BalanceKind::Wallet,
-                amount: balance.as_u64(),
+                amount: to_amount(balance)?,
             }],
+            staleness: None,
         };
 
         Ok(balance)
     }
 
-    async fn get_assets_balances(
+    async fn assets_balances(
         &self,
         address: String,
         asset_ids: Vec<String>,
-    ) -> Result<Vec<crate::service::Balance>, AppError> {
+        block: Option<BlockId>,
+    ) -> Result<Vec<Balance>, AppError> {
+        if asset_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // separate calls must not hit different blocks
+        let block = match (block, self.multicall_contract_address) {
+            (None, None) => Some(block_id(
+                self.provider
+                    .get_block_number()
+                    .await
+                    .map_err(Arc::new)?
+                    .as_u64(),
+            )),
+            (block, _) => block,
+        };
+
         let address = Address::from_str(&address)?;
         let balances = self
-            .address_assets_balances(address, &asset_ids, self.multicall_contract_address)
+            .address_assets_balances(address, &asset_ids, self.multicall_contract_address, block)
             .await?;
 
-        let balances = asset_ids
+        asset_ids
             .iter()
-            .enumerate()
-            .map(|(idx, asset_id)| {
-                Balance::single(asset_id, &BalanceKind::Wallet, balances[idx].as_u64())
+            .zip(balances)
+            .map(|(asset_id, balance)| {
+                Ok(Balance::single(
+                    asset_id,
+                    &BalanceKind::Wallet,
+                    to_amount(balance)?,
+                ))
             })
-            .collect();
+            .collect::<Result<_, _>>()
+    }
+}
+
+#[async_trait::async_trait]
+impl AddressBalancesService for NodeClient {
+    fn is_asset_supported(&self, asset_id: String) -> bool {
+        self.supported_asset_ids
+            .as_ref()
+            .is_some_and(|supported_asset_ids| supported_asset_ids.contains(&asset_id))
+    }
+
+    fn new_blocks(&self) -> BoxStream<'static, u64> {
+        self.new_heads()
+    }
+
+    async fn get_balance(&self, address: String) -> Result<crate::service::Balance, AppError> {
+        self.native_balance(address, None).await
+    }
+
+    async fn get_assets_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<crate::service::Balance>, AppError> {
+        self.assets_balances(address, asset_ids, None).await
+    }
+
+    async fn get_all_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.all_balances(address, asset_ids, None).await
+    }
+
+    async fn get_pinned_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        let block = self.block(None).await?;
+        let at = Some(block_id(block.block_number));
+
+        let balances = if native {
+            self.all_balances(address, asset_ids, at).await?
+        } else {
+            self.assets_balances(address, asset_ids, at).await?
+        };
+
+        Ok(PinnedBalances { block, balances })
+    }
+
+    async fn get_verified_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<VerifiedBalances, AppError> {
+        self.verified_balances(address, asset_ids, native).await
+    }
+
+    async fn get_block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+        self.block(height).await
+    }
+
+    fn supports_balances_at(&self) -> bool {
+        true
+    }
+
+    async fn get_balance_at(&self, address: String, block: BlockRef) -> Result<Balance, AppError> {
+        self.native_balance(address, Some(block_id(block.block_number)))
+            .await
+    }
+
+    async fn get_assets_balances_at(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        block: BlockRef,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.assets_balances(address, asset_ids, Some(block_id(block.block_number)))
+            .await
+    }
+
+    async fn get_prices(&self, asset_ids: Vec<String>) -> Result<HashMap<String, Price>, AppError> {
+        self.prices(&asset_ids).await
+    }
+}
+
+fn block_ref(header: &Header) -> BlockRef {
+    BlockRef {
+        block_number: header.number.as_u64(),
+        block_hash: Some(format!("{:?}", header.hash)),
+        block_timestamp: Some(header.timestamp.as_u64()),
+    }
+}
 
-        Ok(balances)
+fn block_id(height: u64) -> BlockId {
+    BlockId::Number(BlockNumber::Number(height.into()))
+}
+
+fn to_amount(value: U256) -> Result<u64, AppError> {
+    if value > U256::from(u64::MAX) {
+        return Err(AppError::AmountOverflow(value.to_string()));
+    }
+    Ok(value.as_u64())
+}
+
+fn aggregate_function() -> Function {
+    #[allow(deprecated)]
+    Function {
+        name: AGGREGATE_FUNCTION_NAME.to_owned(),
+        inputs: vec![Param {
+            name: "calls".to_owned(),
+            kind: ParamType::Array(Box::new(ParamType::Tuple(vec![
+                ParamType::Address,
+                ParamType::Bytes,
+            ]))),
+            internal_type: None,
+        }],
+        outputs: vec![
+            Param {
+                name: "blockNumber".to_owned(),
+                kind: ParamType::Uint(256),
+                internal_type: None,
+            },
+            Param {
+                name: "returnData".to_owned(),
+                kind: ParamType::Array(Box::new(ParamType::Bytes)),
+                internal_type: None,
+            },
+        ],
+        constant: None,
+        state_mutability: StateMutability::View,
+    }
+}
+
+/// `latestRoundData()` of Chainlink aggregators
+fn latest_round_data_function() -> Function {
+    let output = |name: &str, kind: ParamType| Param {
+        name: name.to_owned(),
+        kind,
+        internal_type: None,
+    };
+
+    #[allow(deprecated)]
+    Function {
+        name: LATEST_ROUND_DATA_FUNCTION_NAME.to_owned(),
+        inputs: vec![],
+        outputs: vec![
+            output("roundId", ParamType::Uint(80)),
+            output("answer", ParamType::Int(256)),
+            output("startedAt", ParamType::Uint(256)),
+            output("updatedAt", ParamType::Uint(256)),
+            output("answeredInRound", ParamType::Uint(80)),
+        ],
+        constant: None,
+        state_mutability: StateMutability::View,
+    }
+}
+
+fn decimals_function() -> Function {
+    #[allow(deprecated)]
+    Function {
+        name: DECIMALS_FUNCTION_NAME.to_owned(),
+        inputs: vec![],
+        outputs: vec![Param {
+            name: DECIMALS_FUNCTION_NAME.to_owned(),
+            kind: ParamType::Uint(8),
+            internal_type: None,
+        }],
+        constant: None,
+        state_mutability: StateMutability::View,
+    }
+}
+
+fn balance_of_function() -> Function {
+    #[allow(deprecated)]
+    Function {
+        name: BALANCE_OF_FUNCTION_NAME.to_owned(),
+        inputs: vec![Param {
+            name: "address".to_owned(),
+            kind: ParamType::Address,
+            internal_type: None,
+        }],
+        outputs: vec![Param {
+            name: BALANCE_OF_FUNCTION_NAME.to_owned(),
+            kind: ParamType::Uint(256),
+            internal_type: None,
+        }],
+        constant: None,
+        state_mutability: StateMutability::View,
+    }
+}
+
+/// `getEthBalance(address)` of the multicall contract
+fn get_eth_balance_function() -> Function {
+    #[allow(deprecated)]
+    Function {
+        name: GET_ETH_BALANCE_FUNCTION_NAME.to_owned(),
+        inputs: vec![Param {
+            name: "addr".to_owned(),
+            kind: ParamType::Address,
+            internal_type: None,
+        }],
+        outputs: vec![Param {
+            name: "balance".to_owned(),
+            kind: ParamType::Uint(256),
+            internal_type: None,
+        }],
+        constant: None,
+        state_mutability: StateMutability::View,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::cache::Cache;
+    use crate::node_clients::Chain;
+
+    const ADDRESS: &str = "0x00000000000000000000000000000000000000aa";
+    const TOKEN: &str = "0x00000000000000000000000000000000000000bb";
+
+    type Answer = fn(&str, &Value) -> Value;
+
+    /// JSON-RPC node stand-in answering the requests with the results of `answer`
+    async fn node_stand_in(answer: Answer) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, answer));
+            }
+        });
+
+        url
+    }
+
+    async fn serve(socket: TcpStream, answer: Answer) {
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader);
+
+        loop {
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).await.unwrap();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+
+            let result = answer(request["method"].as_str().unwrap(), &request["params"]);
+            let body = json!({"jsonrpc": "2.0", "id": request["id"], "result": result}).to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if writer.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn client(answer: Answer) -> NodeClient {
+        metered_client(answer, Arc::new(RateLimiter::new(&None)), None).await
+    }
+
+    async fn metered_client(
+        answer: Answer,
+        rate_limiter: Arc<RateLimiter>,
+        metadata: Option<MetadataCache>,
+    ) -> NodeClient {
+        let url = node_stand_in(answer).await;
+        let config: Config = serde_json::from_value(json!({
+            "chain_id": "0x1",
+            "base_url": url,
+            "supported_asset_ids": [TOKEN],
+            "multicall_contract_address": null,
+        }))
+        .unwrap();
+
+        NodeClient::try_new(&url, "ETH", &config, rate_limiter, metadata).unwrap()
+    }
+
+    /// 2^64, one above `u64::MAX`
+    fn above_u64(method: &str, _params: &Value) -> Value {
+        match method {
+            "eth_getBalance" => json!("0x10000000000000000"),
+            "eth_blockNumber" => json!("0x10"),
+            "eth_call" => json!(format!("0x{:064x}", 1u128 << 64)),
+            _ => Value::Null,
+        }
+    }
+
+    fn within_u64(method: &str, _params: &Value) -> Value {
+        match method {
+            "eth_getBalance" => json!("0xffffffffffffffff"),
+            "eth_blockNumber" => json!("0x10"),
+            "eth_call" => json!(format!("0x{:064x}", 42)),
+            _ => Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_native_balance_above_u64() {
+        let client = client(above_u64).await;
+
+        let result = client.get_balance(ADDRESS.to_string()).await;
+        assert!(matches!(result, Err(AppError::AmountOverflow(_))));
+    }
+
+    #[tokio::test]
+    async fn fails_token_balance_above_u64() {
+        let client = client(above_u64).await;
+
+        let result = client
+            .get_assets_balances(ADDRESS.to_string(), vec![TOKEN.to_string()])
+            .await;
+        assert!(matches!(result, Err(AppError::AmountOverflow(_))));
+
+        let block = BlockRef {
+            block_number: 16,
+            block_hash: None,
+            block_timestamp: None,
+        };
+        let result = client
+            .get_assets_balances_at(ADDRESS.to_string(), vec![TOKEN.to_string()], block)
+            .await;
+        assert!(matches!(result, Err(AppError::AmountOverflow(_))));
+    }
+
+    #[tokio::test]
+    async fn reads_balances_up_to_u64() {
+        let client = client(within_u64).await;
+
+        let native = client.get_balance(ADDRESS.to_string()).await.unwrap();
+        assert_eq!(native.balances[0].amount, u64::MAX);
+
+        let assets = client
+            .get_assets_balances(ADDRESS.to_string(), vec![TOKEN.to_string()])
+            .await
+            .unwrap();
+        assert_eq!(assets[0].balances[0].amount, 42);
+    }
+
+    #[tokio::test]
+    async fn charges_polls_of_new_heads() {
+        let rate_limiter = Arc::new(RateLimiter::new(&Some(
+            serde_json::from_value(json!({
+                "compute_units_per_second": 10,
+                "compute_units": {"eth_blockNumber": 20},
+            }))
+            .unwrap(),
+        )));
+        let client = metered_client(within_u64, rate_limiter.clone(), None).await;
+
+        assert_eq!(client.new_blocks().next().await, Some(16));
+        assert!(matches!(
+            rate_limiter.check("read"),
+            Err(AppError::RateLimited(_))
+        ));
+    }
+
+    static STORAGE_READS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Token without a balances mapping in the probed slots
+    fn without_balance_slot(method: &str, _params: &Value) -> Value {
+        match method {
+            "eth_call" => json!(format!("0x{:064x}", 5)),
+            "eth_getStorageAt" => {
+                STORAGE_READS.fetch_add(1, Ordering::SeqCst);
+                json!(format!("0x{:064x}", 0))
+            }
+            _ => Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn caches_balance_slot_not_found() {
+        let cache = Cache::try_new(&serde_json::from_value(json!({})).unwrap()).unwrap();
+        let metadata = MetadataCache::new(&Chain::Ethereum, Arc::new(cache));
+        let client = metered_client(
+            without_balance_slot,
+            Arc::new(RateLimiter::new(&None)),
+            Some(metadata.clone()),
+        )
+        .await;
+        let holder = Address::from_str(ADDRESS).unwrap();
+        let token = Address::from_str(TOKEN).unwrap();
+        let block = BlockId::Number(BlockNumber::Number(16.into()));
+
+        assert_eq!(
+            client
+                .discover_balance_slot(holder, token, block)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            STORAGE_READS.load(Ordering::SeqCst),
+            2 * DISCOVERY_SLOTS as usize
+        );
+
+        assert_eq!(
+            client
+                .discover_balance_slot(holder, token, block)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            STORAGE_READS.load(Ordering::SeqCst),
+            2 * DISCOVERY_SLOTS as usize
+        );
+        assert_eq!(
+            metadata
+                .get::<Option<BalanceSlot>>(&format!("{:?}", token), BALANCE_SLOT_METADATA_NAME)
+                .await,
+            Some(None)
+        );
+    }
+
+    /// Token whose every probed slot holds the balance
+    fn with_balance_slot(method: &str, _params: &Value) -> Value {
+        match method {
+            "eth_call" | "eth_getStorageAt" => json!(format!("0x{:064x}", 5)),
+            _ => Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn discovers_first_matching_balance_slot() {
+        let client = client(with_balance_slot).await;
+        let holder = Address::from_str(ADDRESS).unwrap();
+        let token = Address::from_str(TOKEN).unwrap();
+        let block = BlockId::Number(BlockNumber::Number(16.into()));
+
+        let slot = BalanceSlot {
+            slot: 0,
+            layout: SlotLayout::Solidity,
+        };
+        assert_eq!(
+            client
+                .discover_balance_slot(holder, token, block)
+                .await
+                .unwrap(),
+            Some(slot)
+        );
+        assert_eq!(
+            client.balance_slots.read().unwrap().get(&token),
+            Some(&slot)
+        );
     }
 }
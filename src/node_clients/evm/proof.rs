@@ -0,0 +1,420 @@
+use ethers_core::types::{Address, Bytes, H256, U256};
+use ethers_core::utils::keccak256;
+use ethers_core::utils::rlp::Rlp;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error as AppError;
+
+/// Response of `eth_getProof`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofResponse {
+    pub balance: U256,
+    pub account_proof: Vec<Bytes>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StorageProof {
+    pub key: U256,
+    pub value: U256,
+    pub proof: Vec<Bytes>,
+}
+
+/// Fields of an account stored in the state trie
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Account {
+    pub balance: U256,
+    pub storage_root: H256,
+}
+
+/// Layout of the `mapping(address => uint256)` of token balances
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlotLayout {
+    /// `keccak256(holder . slot)`
+    #[default]
+    Solidity,
+    /// `keccak256(slot . holder)`
+    Vyper,
+}
+
+/// Position of the balances mapping in the storage of a token contract
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BalanceSlot {
+    pub slot: u64,
+    #[serde(default)]
+    pub layout: SlotLayout,
+}
+
+impl BalanceSlot {
+    /// Storage key of the balance of `holder`
+    pub fn key(&self, holder: Address) -> H256 {
+        let mut padded_holder = [0u8; 32];
+        padded_holder[12..].copy_from_slice(holder.as_bytes());
+
+        let mut padded_slot = [0u8; 32];
+        U256::from(self.slot).to_big_endian(&mut padded_slot);
+
+        let preimage = match self.layout {
+            SlotLayout::Solidity => [padded_holder, padded_slot].concat(),
+            SlotLayout::Vyper => [padded_slot, padded_holder].concat(),
+        };
+
+        H256(keccak256(preimage))
+    }
+}
+
+/// Verifies the proof of `address` against the `state_root`,
+/// a missing account is an empty one
+pub fn verify_account(
+    state_root: H256,
+    address: Address,
+    proof: &[Bytes],
+) -> Result<Account, AppError> {
+    let value = match verify(state_root, &keccak256(address.as_bytes()), proof)? {
+        Some(value) => value,
+        None => {
+            return Ok(Account {
+                balance: U256::zero(),
+                storage_root: H256::zero(),
+            })
+        }
+    };
+
+    // [nonce, balance, storage root, code hash]
+    let account = Rlp::new(&value);
+    let balance = account
+        .at(1)
+        .and_then(|item| item.data())
+        .map_err(invalid)?;
+    let storage_root = account
+        .at(2)
+        .and_then(|item| item.data())
+        .map_err(invalid)?;
+
+    if storage_root.len() != 32 {
+        return Err(AppError::InvalidProof(format!(
+            "storage root of {:?} is malformed",
+            address
+        )));
+    }
+
+    Ok(Account {
+        balance: U256::from_big_endian(balance),
+        storage_root: H256::from_slice(storage_root),
+    })
+}
+
+/// Verifies the proof of the storage `key` against the `storage_root`,
+/// an empty slot is zero
+pub fn verify_storage(storage_root: H256, key: H256, proof: &[Bytes]) -> Result<U256, AppError> {
+    match verify(storage_root, &keccak256(key.as_bytes()), proof)? {
+        Some(value) => {
+            let value = Rlp::new(&value).data().map_err(invalid)?;
+            Ok(U256::from_big_endian(value))
+        }
+        None => Ok(U256::zero()),
+    }
+}
+
+enum NodeRef {
+    Hash(H256),
+    Inline(Vec<u8>),
+}
+
+/// Walks the Merkle-Patricia trie `proof` from the `root` along `path`
+///
+/// Returns the value stored at the path or `None` if the proof shows it is absent
+fn verify(root: H256, path: &[u8; 32], proof: &[Bytes]) -> Result<Option<Vec<u8>>, AppError> {
+    let nibbles = path
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect::<Vec<_>>();
+
+    let mut proof = proof.iter();
+    let mut next = NodeRef::Hash(root);
+    let mut position = 0;
+
+    loop {
+        let node = match next {
+            NodeRef::Hash(hash) => {
+                let node = proof
+                    .next()
+                    .ok_or_else(|| AppError::InvalidProof("proof is incomplete".to_string()))?;
+                if keccak256(node.as_ref()) != hash.0 {
+                    return Err(AppError::InvalidProof(format!(
+                        "node hash mismatch, expected {:?}",
+                        hash
+                    )));
+                }
+                node.to_vec()
+            }
+            NodeRef::Inline(node) => node,
+        };
+
+        let rlp = Rlp::new(&node);
+        match rlp.item_count().map_err(invalid)? {
+            // branch
+            17 => {
+                if position == nibbles.len() {
+                    let value = rlp.at(16).and_then(|item| item.data()).map_err(invalid)?;
+                    return Ok((!value.is_empty()).then(|| value.to_vec()));
+                }
+
+                let child = rlp.at(nibbles[position] as usize).map_err(invalid)?;
+                position += 1;
+
+                next = match node_ref(&child)? {
+                    Some(child) => child,
+                    None => return Ok(None),
+                };
+            }
+            // leaf or extension
+            2 => {
+                let encoded_path = rlp.at(0).and_then(|item| item.data()).map_err(invalid)?;
+                let (is_leaf, path) = decode_path(encoded_path)?;
+                let rest = &nibbles[position..];
+
+                if is_leaf {
+                    if rest != path.as_slice() {
+                        return Ok(None);
+                    }
+                    let value = rlp.at(1).and_then(|item| item.data()).map_err(invalid)?;
+                    return Ok(Some(value.to_vec()));
+                }
+
+                if !rest.starts_with(&path) {
+                    return Ok(None);
+                }
+                position += path.len();
+
+                let child = rlp.at(1).map_err(invalid)?;
+                next = node_ref(&child)?.ok_or_else(|| {
+                    AppError::InvalidProof("extension node without a child".to_string())
+                })?;
+            }
+            count => {
+                return Err(AppError::InvalidProof(format!(
+                    "node of {} items is neither a branch nor a leaf",
+                    count
+                )))
+            }
+        }
+    }
+}
+
+/// Reference to a child node, either its hash or the node itself if shorter than 32 bytes
+fn node_ref(item: &Rlp) -> Result<Option<NodeRef>, AppError> {
+    if item.is_list() {
+        return Ok(Some(NodeRef::Inline(item.as_raw().to_vec())));
+    }
+
+    let data = item.data().map_err(invalid)?;
+    match data.len() {
+        0 => Ok(None),
+        32 => Ok(Some(NodeRef::Hash(H256::from_slice(data)))),
+        len => Err(AppError::InvalidProof(format!(
+            "child reference of {} bytes",
+            len
+        ))),
+    }
+}
+
+/// Decodes the hex-prefix encoded path of a leaf or an extension node
+fn decode_path(encoded: &[u8]) -> Result<(bool, Vec<u8>), AppError> {
+    let first = *encoded
+        .first()
+        .ok_or_else(|| AppError::InvalidProof("empty node path".to_string()))?;
+
+    let flag = first >> 4;
+    let is_leaf = flag & 0b10 != 0;
+    let is_odd = flag & 0b01 != 0;
+
+    let mut nibbles = vec![];
+    if is_odd {
+        nibbles.push(first & 0x0f);
+    }
+    for byte in &encoded[1..] {
+        nibbles.push(byte >> 4);
+        nibbles.push(byte & 0x0f);
+    }
+
+    Ok((is_leaf, nibbles))
+}
+
+fn invalid(e: ethers_core::utils::rlp::DecoderError) -> AppError {
+    AppError::InvalidProof(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::utils::rlp::RlpStream;
+
+    use super::*;
+
+    /// `eth_getProof` response of a contract with the storage proof of its empty slot 0
+    const GET_PROOF: &str = include_str!("testdata/get_proof.json");
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Fixture {
+        address: Address,
+        storage_hash: H256,
+        #[serde(flatten)]
+        proof: ProofResponse,
+    }
+
+    fn fixture() -> Fixture {
+        serde_json::from_str(GET_PROOF).unwrap()
+    }
+
+    /// The state root is the hash of the first node of the account proof
+    fn state_root(fixture: &Fixture) -> H256 {
+        H256(keccak256(fixture.proof.account_proof[0].as_ref()))
+    }
+
+    /// Hex-prefix encoding of the `nibbles` of a leaf or an extension node path
+    fn encode_path(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+        let flag = if is_leaf { 0x20 } else { 0x00 };
+        let (first, rest) = if nibbles.len() % 2 == 1 {
+            (flag | 0x10 | nibbles[0], &nibbles[1..])
+        } else {
+            (flag, nibbles)
+        };
+
+        std::iter::once(first)
+            .chain(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]))
+            .collect()
+    }
+
+    #[test]
+    fn verifies_account_proof() {
+        let fixture = fixture();
+
+        let account = verify_account(
+            state_root(&fixture),
+            fixture.address,
+            &fixture.proof.account_proof,
+        )
+        .unwrap();
+
+        assert_eq!(account.balance, fixture.proof.balance);
+        assert_eq!(account.storage_root, fixture.storage_hash);
+    }
+
+    #[test]
+    fn verifies_absence_of_storage_value() {
+        let fixture = fixture();
+        let storage_proof = &fixture.proof.storage_proof[0];
+
+        let mut key = [0u8; 32];
+        storage_proof.key.to_big_endian(&mut key);
+
+        let value = verify_storage(fixture.storage_hash, H256(key), &storage_proof.proof).unwrap();
+
+        assert_eq!(value, storage_proof.value);
+        assert!(value.is_zero());
+    }
+
+    #[test]
+    fn rejects_tampered_node() {
+        let fixture = fixture();
+        let mut proof = fixture.proof.account_proof.clone();
+        let mut node = proof[1].to_vec();
+        node[40] ^= 1;
+        proof[1] = node.into();
+
+        let result = verify_account(state_root(&fixture), fixture.address, &proof);
+
+        assert!(matches!(result, Err(AppError::InvalidProof(_))));
+    }
+
+    #[test]
+    fn rejects_proof_of_other_root() {
+        let fixture = fixture();
+
+        let result = verify_account(
+            fixture.storage_hash,
+            fixture.address,
+            &fixture.proof.account_proof,
+        );
+
+        assert!(matches!(result, Err(AppError::InvalidProof(_))));
+    }
+
+    #[test]
+    fn rejects_incomplete_proof() {
+        let fixture = fixture();
+        let proof = &fixture.proof.account_proof[..3];
+
+        let result = verify_account(state_root(&fixture), fixture.address, proof);
+
+        assert!(matches!(result, Err(AppError::InvalidProof(_))));
+    }
+
+    #[test]
+    fn decodes_hex_prefix_paths() {
+        assert_eq!(
+            decode_path(&[0x00, 0x01, 0x23]).unwrap(),
+            (false, vec![0, 1, 2, 3])
+        );
+        assert_eq!(decode_path(&[0x11, 0x23]).unwrap(), (false, vec![1, 2, 3]));
+        assert_eq!(
+            decode_path(&[0x20, 0x0f, 0x1c]).unwrap(),
+            (true, vec![0, 0xf, 1, 0xc])
+        );
+        assert_eq!(
+            decode_path(&[0x3f, 0x1c]).unwrap(),
+            (true, vec![0xf, 1, 0xc])
+        );
+        assert_eq!(decode_path(&[0x20]).unwrap(), (true, vec![]));
+        assert!(decode_path(&[]).is_err());
+    }
+
+    #[test]
+    fn walks_inline_nodes() {
+        let key = H256::from_low_u64_be(7);
+        let path = keccak256(key.as_bytes());
+        let nibbles = path
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0x0f])
+            .collect::<Vec<_>>();
+
+        // leaf and branch shorter than 32 bytes are embedded in their parents,
+        // so the proof is the extension node alone
+        let mut leaf = RlpStream::new_list(2);
+        leaf.append(&encode_path(&nibbles[63..], true))
+            .append(&rlp_value(42));
+        let leaf = leaf.out();
+
+        let mut branch = RlpStream::new_list(17);
+        for nibble in 0..16 {
+            if nibble == nibbles[62] {
+                branch.append_raw(&leaf, 1);
+            } else {
+                branch.append_empty_data();
+            }
+        }
+        branch.append_empty_data();
+        let branch = branch.out();
+        assert!(branch.len() < 32);
+
+        let mut extension = RlpStream::new_list(2);
+        extension
+            .append(&encode_path(&nibbles[..62], false))
+            .append_raw(&branch, 1);
+        let proof = [Bytes::from(extension.out().to_vec())];
+        let root = H256(keccak256(proof[0].as_ref()));
+
+        assert_eq!(verify_storage(root, key, &proof).unwrap(), U256::from(42));
+        assert!(verify_storage(root, H256::from_low_u64_be(8), &proof)
+            .unwrap()
+            .is_zero());
+    }
+
+    /// Storage value as stored in a leaf, the RLP of the trimmed big-endian bytes
+    fn rlp_value(value: u64) -> Vec<u8> {
+        ethers_core::utils::rlp::encode(&U256::from(value)).to_vec()
+    }
+}
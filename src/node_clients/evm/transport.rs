@@ -0,0 +1,269 @@
+use ethers_core::types::U64;
+use ethers_providers::{
+    Http, Ipc, IpcError, JsonRpcClient, Middleware, Provider, ProviderError, PubsubClient, Ws,
+    WsClientError,
+};
+use futures::channel::mpsc;
+use futures::stream::BoxStream;
+use futures::{SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{
+    error::Error as AppError,
+    node_clients::{blocks, rate_limit::RateLimiter, upstream::Config as UpstreamConfig},
+};
+
+const NEW_HEADS_BUFFER_SIZE: usize = 16;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// JSON-RPC transport of an EVM node, selected by the scheme of the node url:
+/// `http(s)://`, `ws(s)://` or `ipc://<socket path>`
+///
+/// WebSocket and IPC connections are established lazily and re-established
+/// whenever the node drops them
+#[derive(Clone, Debug)]
+pub enum Transport {
+    Http(Arc<Http>),
+    Ws(Arc<Reconnecting<Ws>>),
+    Ipc(Arc<Reconnecting<Ipc>>),
+}
+
+impl Transport {
+    pub fn try_new(url: impl AsRef<str>, upstream: &UpstreamConfig) -> Result<Self, AppError> {
+        let parsed = Url::parse(url.as_ref())?;
+        let connect_timeout = Duration::from_millis(upstream.connect_timeout);
+
+        match parsed.scheme() {
+            "http" | "https" => Ok(Self::Http(Arc::new(Http::new_with_client(
+                parsed,
+                upstream.http_client()?,
+            )))),
+            "ws" | "wss" => Ok(Self::Ws(Arc::new(Reconnecting::new(
+                url.as_ref(),
+                connect_timeout,
+            )))),
+            "ipc" => Ok(Self::Ipc(Arc::new(Reconnecting::new(
+                parsed.path(),
+                connect_timeout,
+            )))),
+            scheme => Err(AppError::UnsupportedTransport(scheme.to_owned())),
+        }
+    }
+}
+
+/// Transport charging every request to the budgets of the provider
+#[derive(Clone, Debug)]
+pub struct Metered {
+    transport: Transport,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl Metered {
+    pub fn new(transport: Transport, rate_limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            transport,
+            rate_limiter,
+        }
+    }
+
+    /// Stream of new block numbers
+    ///
+    /// Pub-sub transports subscribe to `newHeads` and resubscribe after
+    /// reconnects, HTTP transport polls `eth_blockNumber`, charged as any other request
+    pub fn new_heads(&self) -> BoxStream<'static, u64> {
+        match &self.transport {
+            Transport::Http(_) => {
+                let metered = self.clone();
+                blocks::poll_heights(move || {
+                    let metered = metered.clone();
+                    async move {
+                        metered
+                            .request::<_, U64>("eth_blockNumber", ())
+                            .await
+                            .map(|block_number| block_number.as_u64())
+                            .map_err(|e| AppError::EthersProvider(Arc::new(e)))
+                    }
+                })
+            }
+            Transport::Ws(ws) => ws.clone().new_heads(),
+            Transport::Ipc(ipc) => ipc.clone().new_heads(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl JsonRpcClient for Metered {
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        self.rate_limiter.charge(method);
+        self.transport.request(method, params).await
+    }
+}
+
+#[async_trait::async_trait]
+impl JsonRpcClient for Transport {
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        match self {
+            Self::Http(http) => http.request(method, params).await.map_err(Into::into),
+            Self::Ws(ws) => ws.request(method, params).await,
+            Self::Ipc(ipc) => ipc.request(method, params).await,
+        }
+    }
+}
+
+/// Pub-sub transport which can be (re)connected to a node by its address
+#[async_trait::async_trait]
+pub trait Connect: PubsubClient + Clone + Sized + 'static {
+    async fn connect(target: &str) -> Result<Self, Self::Error>;
+
+    /// Whether `error` means that the connection is gone and has to be re-established
+    fn is_disconnected(error: &Self::Error) -> bool;
+}
+
+#[async_trait::async_trait]
+impl Connect for Ws {
+    async fn connect(target: &str) -> Result<Self, Self::Error> {
+        Ws::connect(target).await
+    }
+
+    fn is_disconnected(error: &Self::Error) -> bool {
+        !matches!(
+            error,
+            WsClientError::JsonError(_) | WsClientError::JsonRpcError(_)
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl Connect for Ipc {
+    async fn connect(target: &str) -> Result<Self, Self::Error> {
+        Ipc::connect(target).await
+    }
+
+    fn is_disconnected(error: &Self::Error) -> bool {
+        !matches!(error, IpcError::JsonError(_) | IpcError::JsonRpcError(_))
+    }
+}
+
+#[derive(Debug)]
+pub struct Reconnecting<T> {
+    target: String,
+    connect_timeout: Duration,
+    /// Current connection and its generation, bumped on every reconnect
+    client: RwLock<Option<(u64, T)>>,
+}
+
+impl<T: Connect> Reconnecting<T> {
+    pub fn new(target: impl AsRef<str>, connect_timeout: Duration) -> Self {
+        Self {
+            target: target.as_ref().to_owned(),
+            connect_timeout,
+            client: RwLock::new(None),
+        }
+    }
+
+    async fn client(&self) -> Result<(u64, T), ProviderError> {
+        if let Some(client) = self.client.read().await.clone() {
+            return Ok(client);
+        }
+
+        self.reconnect(0).await
+    }
+
+    /// Replaces connection of `generation` with a new one, unless it has
+    /// already been replaced by a concurrent caller
+    async fn reconnect(&self, generation: u64) -> Result<(u64, T), ProviderError> {
+        let mut client = self.client.write().await;
+
+        if let Some((current, c)) = client.as_ref() {
+            if *current > generation {
+                return Ok((*current, c.clone()));
+            }
+        }
+
+        debug!("connecting to {}", self.target);
+        let connection = tokio::time::timeout(self.connect_timeout, T::connect(&self.target))
+            .await
+            .map_err(|_| {
+                ProviderError::CustomError(format!("connecting to {} timed out", self.target))
+            })?
+            .map_err(Into::into)?;
+        let next = (generation + 1, connection);
+        *client = Some(next.clone());
+
+        Ok(next)
+    }
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, ProviderError>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let (generation, client) = self.client().await?;
+
+        let error = match client.request(method, &params).await {
+            Err(e) if T::is_disconnected(&e) => e,
+            result => return result.map_err(Into::into),
+        };
+
+        warn!(
+            "connection to {} lost: {}, reconnecting",
+            self.target, error
+        );
+        let (_, client) = self.reconnect(generation).await?;
+        client.request(method, &params).await.map_err(Into::into)
+    }
+
+    fn new_heads(self: Arc<Self>) -> BoxStream<'static, u64> {
+        let (tx, rx) = mpsc::channel(NEW_HEADS_BUFFER_SIZE);
+        tokio::spawn(self.subscribe_new_heads(tx));
+        rx.boxed()
+    }
+
+    async fn subscribe_new_heads(self: Arc<Self>, mut tx: mpsc::Sender<u64>) {
+        while !tx.is_closed() {
+            match self.client().await {
+                Ok((generation, client)) => {
+                    let provider = Provider::new(client);
+                    match provider.subscribe_blocks().await {
+                        Ok(mut blocks) => {
+                            while let Some(block) = blocks.next().await {
+                                if let Some(number) = block.number {
+                                    if tx.send(number.as_u64()).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => warn!("failed to subscribe to new heads: {}", e),
+                    }
+
+                    // subscription ends only when the connection is gone
+                    if let Err(e) = self.reconnect(generation).await {
+                        warn!("failed to reconnect to {}: {}", self.target, e);
+                    }
+                }
+                Err(e) => warn!("failed to connect to {}: {}", self.target, e),
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
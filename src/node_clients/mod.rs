@@ -1,10 +1,22 @@
+pub mod blocks;
+pub mod bulkhead;
 mod config;
 pub mod evm;
+pub mod quorum;
+pub mod rate_limit;
+pub mod substrate;
+pub mod upstream;
 pub mod waves;
 
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
 
-use crate::{error::Error as AppError, service::AddressBalancesService};
+use crate::{
+    cache::{Cache, MetadataCache},
+    error::Error as AppError,
+    service::AddressBalancesService,
+};
 
 pub use config::Config;
 
@@ -13,6 +25,7 @@ pub use config::Config;
 pub enum NodeType {
     Waves,
     Evm,
+    Substrate,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
@@ -21,6 +34,18 @@ pub enum Chain {
     Waves,
     Ethereum,
     BSC,
+    Polkadot,
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Waves => "waves",
+            Self::Ethereum => "ethereum",
+            Self::BSC => "bsc",
+            Self::Polkadot => "polkadot",
+        })
+    }
 }
 
 impl From<Chain> for String {
@@ -43,35 +68,124 @@ impl TryFrom<String> for Chain {
             "waves" => Ok(Self::Waves),
             "ethereum" => Ok(Self::Ethereum),
             "bsc" => Ok(Self::BSC),
+            "polkadot" => Ok(Self::Polkadot),
             _ => Err(AppError::UnexpectedChain(value)),
         }
     }
 }
 
-pub fn new(config: &Config) -> Result<Box<dyn AddressBalancesService + Send + Sync>, AppError> {
-    match config {
-        Config::Waves(chain_config) => {
-            let client = waves::NodeClient::try_new(
-                &chain_config.base_url,
-                &chain_config
-                    .supported_asset_ids
-                    .as_ref()
-                    .map(|v| v.as_slice()),
-            )?;
-            Ok(Box::new(client))
-        }
-        Config::Ethereum(chain_config) | Config::Bsc(chain_config) => {
-            let client = evm::NodeClient::try_new(
+type NodeClient = Box<dyn AddressBalancesService + Send + Sync>;
+
+/// Metadata of the assets is cached in the `cache` if given
+pub fn new(
+    chain: &Chain,
+    config: &Config,
+    cache: Option<&Arc<Cache>>,
+) -> Result<NodeClient, AppError> {
+    let client = match config {
+        Config::Waves(chain_config) => with_quorum(
+            chain,
+            &chain_config.base_url,
+            &chain_config.quorum,
+            &chain_config.upstream,
+            true,
+            |base_url, rate_limiter| {
+                let client = waves::NodeClient::try_new(
+                    base_url,
+                    &chain_config.supported_asset_ids.as_deref(),
+                    &chain_config.price_oracle,
+                    &chain_config.upstream,
+                    rate_limiter,
+                )?;
+                Ok(Box::new(client))
+            },
+        ),
+        Config::Ethereum(chain_config) | Config::Bsc(chain_config) => with_quorum(
+            chain,
+            &chain_config.base_url,
+            &chain_config.quorum,
+            &chain_config.upstream,
+            false,
+            |base_url, rate_limiter| {
+                let client = evm::NodeClient::try_new(
+                    base_url,
+                    config.native_token(),
+                    chain_config,
+                    rate_limiter,
+                    cache.map(|cache| MetadataCache::new(chain, cache.clone())),
+                )?;
+                Ok(Box::new(client))
+            },
+        ),
+        Config::Polkadot(chain_config) => {
+            let rate_limiter = Arc::new(rate_limit::RateLimiter::new(
+                &chain_config.upstream.rate_limit,
+            ));
+            let client = substrate::NodeClient::try_new(
                 &chain_config.base_url,
-                &config.native_token(),
-                &chain_config.chain_id,
-                &chain_config
-                    .supported_asset_ids
-                    .as_ref()
-                    .map(|v| v.as_slice()),
-                &chain_config.multicall_contract_address,
+                config.native_token(),
+                chain_config.ss58_prefix,
+                &chain_config.supported_asset_ids.as_deref(),
+                &chain_config.upstream,
+                rate_limiter.clone(),
             )?;
-            Ok(Box::new(client))
+            Ok(Box::new(upstream::ResilientClient::new(
+                chain.to_string(),
+                Box::new(client),
+                &chain_config.upstream,
+                rate_limiter,
+            )) as NodeClient)
         }
-    }
+    }?;
+
+    Ok(Box::new(bulkhead::Bulkhead::new(
+        chain.to_string(),
+        client,
+        config.bulkhead(),
+    )))
+}
+
+/// Creates the client of `base_url`, or a quorum of the clients of all the providers,
+/// each of them with its own circuit breaker and budgets, the quorum agrees on
+/// the heights of the latest blocks only if their hashes are `liquid_blocks`
+fn with_quorum(
+    chain: &Chain,
+    base_url: &str,
+    quorum: &Option<quorum::Config>,
+    upstream: &upstream::Config,
+    liquid_blocks: bool,
+    client: impl Fn(&str, Arc<rate_limit::RateLimiter>) -> Result<NodeClient, AppError>,
+) -> Result<NodeClient, AppError> {
+    let client = |name: String, base_url: &str| -> Result<NodeClient, AppError> {
+        let rate_limiter = Arc::new(rate_limit::RateLimiter::new(&upstream.rate_limit));
+        Ok(Box::new(upstream::ResilientClient::new(
+            name,
+            client(base_url, rate_limiter.clone())?,
+            upstream,
+            rate_limiter,
+        )))
+    };
+
+    let quorum = match quorum {
+        Some(quorum) => quorum,
+        None => return client(chain.to_string(), base_url),
+    };
+
+    let providers = std::iter::once((quorum::PRIMARY_PROVIDER.to_string(), base_url))
+        .chain(
+            quorum
+                .providers
+                .iter()
+                .map(|provider| (provider.name.clone(), provider.base_url.as_str())),
+        )
+        .map(|(name, base_url)| {
+            client(format!("{} {}", chain, name), base_url).map(|client| (name, client))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Box::new(quorum::QuorumClient::try_new(
+        providers,
+        quorum.threshold,
+        liquid_blocks,
+    )?))
 }
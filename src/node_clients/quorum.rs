@@ -0,0 +1,590 @@
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use tracing::warn;
+
+use crate::{
+    error::Error as AppError,
+    service::{AddressBalancesService, Balance, BlockRef, PinnedBalances, VerifiedBalances},
+    valuation::Price,
+};
+
+/// Rounds of reads repeated while the providers answer at different blocks
+const QUORUM_ATTEMPTS: usize = 3;
+
+/// Name of the provider of the `base_url` of the chain
+pub const PRIMARY_PROVIDER: &str = "primary";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+    /// Providers read in addition to the `base_url` of the chain
+    pub providers: Vec<Provider>,
+    /// Providers which have to return the same balances at the same block
+    pub threshold: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Provider {
+    /// Name listed in the conflicts, so the url with credentials is not exposed
+    pub name: String,
+    pub base_url: String,
+}
+
+/// Answer of a provider which did not reach the quorum
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProviderAnswer {
+    pub provider: String,
+    pub answer: String,
+}
+
+impl fmt::Display for ProviderAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.provider, self.answer)
+    }
+}
+
+type Client = Box<dyn AddressBalancesService + Send + Sync>;
+
+/// Reads balances from all the providers of a chain and returns them
+/// only if at least `threshold` providers agree on them at the same block
+pub struct QuorumClient {
+    providers: Vec<(String, Client)>,
+    threshold: usize,
+    /// Whether the hash of the latest block changes until the next block, as the one
+    /// of Waves does with every microblock, so the providers agree on its height only
+    liquid_blocks: bool,
+}
+
+impl QuorumClient {
+    pub fn try_new(
+        providers: Vec<(String, Client)>,
+        threshold: usize,
+        liquid_blocks: bool,
+    ) -> Result<Self, AppError> {
+        if threshold == 0 || threshold > providers.len() {
+            return Err(AppError::InvalidQuorum(format!(
+                "threshold {} of {} providers",
+                threshold,
+                providers.len()
+            )));
+        }
+
+        Ok(Self {
+            providers,
+            threshold,
+            liquid_blocks,
+        })
+    }
+
+    fn primary(&self) -> &Client {
+        &self.providers[0].1
+    }
+
+    /// Reads pinned balances from all the providers at the block they agree on if they
+    /// read balances at past blocks, or at their latest blocks otherwise, in which case
+    /// another round is read if no answer reached the quorum while the providers were
+    /// at different blocks, and the block of the first provider of the quorum is returned
+    async fn pinned_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        if self.supports_balances_at() {
+            let block = self.get_block(None).await?;
+            let assets = async {
+                if asset_ids.is_empty() {
+                    Ok(vec![])
+                } else {
+                    self.get_assets_balances_at(address.clone(), asset_ids, block.clone())
+                        .await
+                }
+            };
+            let balances = if native {
+                let native = self.get_balance_at(address.clone(), block.clone());
+                let (native, assets) = futures::try_join!(native, assets)?;
+                std::iter::once(native).chain(assets).collect()
+            } else {
+                assets.await?
+            };
+            return Ok(PinnedBalances { block, balances });
+        }
+
+        let mut attempt = 1;
+        loop {
+            let answers = futures::future::join_all(self.providers.iter().map(|(_, client)| {
+                client.get_pinned_balances(address.clone(), asset_ids.clone(), native)
+            }))
+            .await;
+
+            let blocks = answers
+                .iter()
+                .filter_map(|answer| answer.as_ref().ok())
+                .map(|pinned| self.block_key(&pinned.block))
+                .collect::<HashSet<_>>();
+
+            match self.agree(answers, |pinned| {
+                (self.block_key(&pinned.block), pinned.balances.clone())
+            }) {
+                Err(AppError::QuorumNotReached(answers))
+                    if blocks.len() > 1 && attempt < QUORUM_ATTEMPTS =>
+                {
+                    warn!(
+                        "providers answered at different blocks, attempt {}: {}",
+                        attempt,
+                        format_answers(&answers)
+                    );
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Height and hash of the `block` which the providers have to agree on
+    fn block_key(&self, block: &BlockRef) -> (u64, Option<String>) {
+        let hash = if self.liquid_blocks {
+            None
+        } else {
+            block.block_hash.clone()
+        };
+        (block.block_number, hash)
+    }
+
+    /// Returns the answer given by at least `threshold` providers,
+    /// equal answers have the same `key`
+    fn agree<T, K>(
+        &self,
+        answers: Vec<Result<T, AppError>>,
+        key: impl Fn(&T) -> K,
+    ) -> Result<T, AppError>
+    where
+        T: Serialize,
+        K: PartialEq,
+    {
+        let keys = answers
+            .iter()
+            .map(|answer| answer.as_ref().ok().map(&key))
+            .collect::<Vec<_>>();
+
+        let agreed = keys.iter().enumerate().find_map(|(i, k)| {
+            let k = k.as_ref()?;
+            let votes = keys
+                .iter()
+                .filter(|other| other.as_ref() == Some(k))
+                .count();
+            (votes >= self.threshold).then_some(i)
+        });
+
+        match agreed {
+            Some(i) => answers
+                .into_iter()
+                .nth(i)
+                .expect("agreed answer is present"),
+            None => Err(self.not_reached(answers)),
+        }
+    }
+
+    fn not_reached<T: Serialize>(&self, answers: Vec<Result<T, AppError>>) -> AppError {
+        AppError::QuorumNotReached(
+            self.providers
+                .iter()
+                .zip(answers)
+                .map(|((provider, _), answer)| ProviderAnswer {
+                    provider: provider.clone(),
+                    answer: match answer {
+                        Ok(answer) => serde_json::to_string(&answer)
+                            .unwrap_or_else(|e| format!("unserializable answer, {}", e)),
+                        Err(e) => format!("error: {}", e),
+                    },
+                })
+                .collect(),
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl AddressBalancesService for QuorumClient {
+    fn is_asset_supported(&self, asset_id: String) -> bool {
+        self.primary().is_asset_supported(asset_id)
+    }
+
+    fn new_blocks(&self) -> BoxStream<'static, u64> {
+        self.primary().new_blocks()
+    }
+
+    async fn get_balance(&self, address: String) -> Result<Balance, AppError> {
+        self.pinned_balances(address, vec![], true)
+            .await?
+            .balances
+            .pop()
+            .ok_or_else(|| AppError::UpstreamResponse("native balance is missing".to_string()))
+    }
+
+    async fn get_assets_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.pinned_balances(address, asset_ids, false)
+            .await
+            .map(|pinned| pinned.balances)
+    }
+
+    async fn get_all_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.pinned_balances(address, asset_ids, true)
+            .await
+            .map(|pinned| pinned.balances)
+    }
+
+    async fn get_pinned_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        self.pinned_balances(address, asset_ids, native).await
+    }
+
+    /// Proofs are read from the primary provider and checked against the state root
+    /// of its block, whose hash has to be the one of the block at the same height
+    /// of at least `threshold` providers
+    async fn get_verified_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<VerifiedBalances, AppError> {
+        let verified = self
+            .primary()
+            .get_verified_balances(address, asset_ids, native)
+            .await?;
+        let height = verified.block.block_number;
+
+        let blocks = futures::future::join_all(
+            self.providers
+                .iter()
+                .skip(1)
+                .map(|(_, client)| client.get_block(Some(height))),
+        )
+        .await;
+
+        let agreed = self.agree(
+            std::iter::once(Ok(verified.block.clone()))
+                .chain(blocks)
+                .collect(),
+            |block| block.block_hash.clone(),
+        )?;
+
+        if agreed.block_hash != verified.block.block_hash {
+            return Err(AppError::InvalidProof(format!(
+                "block {} of {} is not the one of the quorum",
+                height, PRIMARY_PROVIDER
+            )));
+        }
+
+        Ok(verified)
+    }
+
+    fn supports_balances_at(&self) -> bool {
+        self.providers
+            .iter()
+            .all(|(_, client)| client.supports_balances_at())
+    }
+
+    /// Block at the `height` whose hash at least `threshold` providers agree on,
+    /// the latest one is the highest block at least `threshold` providers reached,
+    /// so the providers a block apart read the same block
+    async fn get_block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+        let height = match height {
+            Some(height) => height,
+            None => {
+                let heads = futures::future::join_all(
+                    self.providers
+                        .iter()
+                        .map(|(_, client)| client.get_block(None)),
+                )
+                .await;
+
+                let mut heights = heads
+                    .iter()
+                    .flatten()
+                    .map(|head| head.block_number)
+                    .collect::<Vec<_>>();
+                heights.sort_unstable_by(|a, b| b.cmp(a));
+                match heights.get(self.threshold - 1) {
+                    Some(height) => *height,
+                    None => return Err(self.not_reached(heads)),
+                }
+            }
+        };
+
+        let blocks = futures::future::join_all(
+            self.providers
+                .iter()
+                .map(|(_, client)| client.get_block(Some(height))),
+        )
+        .await;
+
+        self.agree(blocks, |block| block.block_hash.clone())
+    }
+
+    async fn get_balance_at(&self, address: String, block: BlockRef) -> Result<Balance, AppError> {
+        let answers = futures::future::join_all(
+            self.providers
+                .iter()
+                .map(|(_, client)| client.get_balance_at(address.clone(), block.clone())),
+        )
+        .await;
+
+        self.agree(answers, Clone::clone)
+    }
+
+    async fn get_assets_balances_at(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        block: BlockRef,
+    ) -> Result<Vec<Balance>, AppError> {
+        let answers = futures::future::join_all(self.providers.iter().map(|(_, client)| {
+            client.get_assets_balances_at(address.clone(), asset_ids.clone(), block.clone())
+        }))
+        .await;
+
+        self.agree(answers, Clone::clone)
+    }
+
+    async fn get_prices(&self, asset_ids: Vec<String>) -> Result<HashMap<String, Price>, AppError> {
+        self.primary().get_prices(asset_ids).await
+    }
+}
+
+pub fn format_answers(answers: &[ProviderAnswer]) -> String {
+    answers
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream::{self, StreamExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::service::BalanceKind;
+
+    /// Provider whose consecutive latest reads are at the `heads`, the last one repeated,
+    /// and whose blocks at past heights are of the `fork`
+    struct Node {
+        heads: Vec<BlockRef>,
+        reads: AtomicUsize,
+        amount: u64,
+        fork: &'static str,
+    }
+
+    fn node(heads: &[(u64, &str)], amount: u64) -> Node {
+        Node {
+            heads: heads
+                .iter()
+                .map(|(height, hash)| block(*height, hash))
+                .collect(),
+            reads: AtomicUsize::new(0),
+            amount,
+            fork: "canonical",
+        }
+    }
+
+    fn block(height: u64, hash: &str) -> BlockRef {
+        BlockRef {
+            block_number: height,
+            block_hash: Some(hash.to_string()),
+            block_timestamp: None,
+        }
+    }
+
+    impl Node {
+        fn head(&self) -> BlockRef {
+            let read = self.reads.fetch_add(1, Ordering::SeqCst);
+            self.heads[read.min(self.heads.len() - 1)].clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AddressBalancesService for Node {
+        fn is_asset_supported(&self, _asset_id: String) -> bool {
+            true
+        }
+
+        fn new_blocks(&self) -> BoxStream<'static, u64> {
+            stream::empty().boxed()
+        }
+
+        async fn get_balance(&self, _address: String) -> Result<Balance, AppError> {
+            Ok(Balance::single("WAVES", &BalanceKind::Wallet, self.amount))
+        }
+
+        async fn get_assets_balances(
+            &self,
+            _address: String,
+            _asset_ids: Vec<String>,
+        ) -> Result<Vec<Balance>, AppError> {
+            Ok(vec![])
+        }
+
+        async fn get_pinned_balances(
+            &self,
+            address: String,
+            _asset_ids: Vec<String>,
+            _native: bool,
+        ) -> Result<PinnedBalances, AppError> {
+            Ok(PinnedBalances {
+                block: self.head(),
+                balances: vec![self.get_balance(address).await?],
+            })
+        }
+
+        async fn get_verified_balances(
+            &self,
+            _address: String,
+            _asset_ids: Vec<String>,
+            _native: bool,
+        ) -> Result<VerifiedBalances, AppError> {
+            let head = self.head();
+            Ok(VerifiedBalances {
+                block: block(head.block_number, self.fork),
+                balances: vec![],
+            })
+        }
+
+        async fn get_block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+            match height {
+                Some(height) => Ok(block(height, self.fork)),
+                None => Ok(self.head()),
+            }
+        }
+    }
+
+    fn quorum(nodes: Vec<Node>, threshold: usize, liquid_blocks: bool) -> QuorumClient {
+        let providers = nodes
+            .into_iter()
+            .enumerate()
+            .map(|(i, node)| (format!("node-{}", i), Box::new(node) as Client))
+            .collect();
+        QuorumClient::try_new(providers, threshold, liquid_blocks).unwrap()
+    }
+
+    fn amount(pinned: &PinnedBalances) -> u64 {
+        pinned.balances[0].balances[0].amount
+    }
+
+    #[tokio::test]
+    async fn agrees_on_balances_of_threshold_providers() {
+        let nodes = || {
+            vec![
+                node(&[(20, "a")], 5),
+                node(&[(20, "a")], 6),
+                node(&[(20, "a")], 5),
+            ]
+        };
+
+        let pinned = quorum(nodes(), 2, false)
+            .get_pinned_balances("address".to_string(), vec![], true)
+            .await
+            .unwrap();
+        assert_eq!(amount(&pinned), 5);
+        assert_eq!(pinned.block, block(20, "a"));
+
+        let result = quorum(nodes(), 3, false)
+            .get_pinned_balances("address".to_string(), vec![], true)
+            .await;
+        match result {
+            Err(AppError::QuorumNotReached(answers)) => assert_eq!(answers.len(), 3),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_while_providers_are_at_different_blocks() {
+        let client = quorum(
+            vec![node(&[(20, "a"), (21, "b")], 5), node(&[(21, "b")], 5)],
+            2,
+            false,
+        );
+        let pinned = client
+            .get_pinned_balances("address".to_string(), vec![], true)
+            .await
+            .unwrap();
+        assert_eq!(pinned.block, block(21, "b"));
+
+        let client = quorum(
+            vec![node(&[(20, "a"), (20, "b")], 5), node(&[(20, "b")], 5)],
+            2,
+            false,
+        );
+        let pinned = client
+            .get_pinned_balances("address".to_string(), vec![], true)
+            .await
+            .unwrap();
+        assert_eq!(pinned.block, block(20, "b"));
+    }
+
+    #[tokio::test]
+    async fn fails_when_providers_stay_at_different_blocks() {
+        let quorum = quorum(vec![node(&[(20, "a")], 5), node(&[(20, "b")], 5)], 2, false);
+        let result = quorum
+            .get_pinned_balances("address".to_string(), vec![], true)
+            .await;
+        assert!(matches!(result, Err(AppError::QuorumNotReached(_))));
+    }
+
+    #[tokio::test]
+    async fn agrees_on_heights_of_liquid_blocks() {
+        let quorum = quorum(vec![node(&[(20, "a")], 5), node(&[(20, "b")], 5)], 2, true);
+        let pinned = quorum
+            .get_pinned_balances("address".to_string(), vec![], true)
+            .await
+            .unwrap();
+        assert_eq!(pinned.block, block(20, "a"));
+    }
+
+    #[tokio::test]
+    async fn reads_threshold_highest_head() {
+        let nodes = || {
+            vec![
+                node(&[(22, "c")], 5),
+                node(&[(20, "a")], 5),
+                node(&[(21, "b")], 5),
+            ]
+        };
+
+        let head = quorum(nodes(), 2, false).get_block(None).await.unwrap();
+        assert_eq!(head, block(21, "canonical"));
+
+        let head = quorum(nodes(), 3, false).get_block(None).await.unwrap();
+        assert_eq!(head, block(20, "canonical"));
+    }
+
+    #[tokio::test]
+    async fn rejects_proof_of_block_off_quorum() {
+        let fork = Node {
+            fork: "fork",
+            ..node(&[(20, "a")], 5)
+        };
+        let quorum = quorum(
+            vec![fork, node(&[(20, "a")], 5), node(&[(20, "a")], 5)],
+            2,
+            false,
+        );
+        let result = quorum
+            .get_verified_balances("address".to_string(), vec![], true)
+            .await;
+        assert!(matches!(result, Err(AppError::InvalidProof(_))));
+    }
+}
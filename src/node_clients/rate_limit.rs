@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::error::Error as AppError;
+
+/// Budgets of a provider, e.g. the requests/sec and compute units/sec of an Infura plan
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+    #[serde(default)]
+    pub requests_per_second: Option<u32>,
+    #[serde(default)]
+    pub compute_units_per_second: Option<u32>,
+    /// JSON-RPC method or REST route -> compute units of a request, e.g. `eth_call: 26`
+    #[serde(default)]
+    pub compute_units: HashMap<String, u32>,
+    /// Compute units of the requests missing in `compute_units`
+    #[serde(default = "default_compute_units")]
+    pub default_compute_units: u32,
+}
+
+/// Refills `rate` tokens per second up to one second worth of them
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.refilled_at = now;
+    }
+}
+
+/// Rejects the reads of a provider while its budgets are spent.
+///
+/// A read may take several requests, which are not known upfront, so the budgets are
+/// checked once before the read and charged by every request it makes, going into
+/// debt rather than failing a read halfway
+#[derive(Debug)]
+pub struct RateLimiter {
+    costs: HashMap<String, u32>,
+    default_cost: u32,
+    requests: Option<Mutex<Bucket>>,
+    compute_units: Option<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    /// Limiter of the `config`, letting everything through if it is absent
+    pub fn new(config: &Option<Config>) -> Self {
+        match config {
+            Some(config) => Self {
+                costs: config.compute_units.clone(),
+                default_cost: config.default_compute_units,
+                requests: config.requests_per_second.map(Bucket::new).map(Mutex::new),
+                compute_units: config
+                    .compute_units_per_second
+                    .map(Bucket::new)
+                    .map(Mutex::new),
+            },
+            None => Self {
+                costs: HashMap::new(),
+                default_cost: default_compute_units(),
+                requests: None,
+                compute_units: None,
+            },
+        }
+    }
+
+    /// Fails with `RateLimited` if a budget of `name` is spent
+    pub fn check(&self, name: &str) -> Result<(), AppError> {
+        let budgets = [
+            (&self.requests, "requests"),
+            (&self.compute_units, "compute units"),
+        ];
+
+        for (bucket, budget) in budgets {
+            if let Some(bucket) = bucket {
+                let mut bucket = bucket.lock().expect("rate limiter lock is poisoned");
+                bucket.refill();
+                if bucket.tokens <= 0.0 {
+                    return Err(AppError::RateLimited(format!(
+                        "{} {} budget is spent",
+                        name, budget
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Charges a request of the `method` to the budgets
+    pub fn charge(&self, method: &str) {
+        if let Some(requests) = &self.requests {
+            let mut requests = requests.lock().expect("rate limiter lock is poisoned");
+            requests.refill();
+            requests.tokens -= 1.0;
+        }
+
+        if let Some(compute_units) = &self.compute_units {
+            let cost = self.costs.get(method).copied().unwrap_or(self.default_cost);
+            let mut compute_units = compute_units.lock().expect("rate limiter lock is poisoned");
+            compute_units.refill();
+            compute_units.tokens -= cost as f64;
+        }
+    }
+}
+
+fn default_compute_units() -> u32 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn limiter(
+        requests_per_second: Option<u32>,
+        compute_units_per_second: Option<u32>,
+    ) -> RateLimiter {
+        RateLimiter::new(&Some(Config {
+            requests_per_second,
+            compute_units_per_second,
+            compute_units: HashMap::from([("eth_call".to_string(), 60)]),
+            default_compute_units: default_compute_units(),
+        }))
+    }
+
+    #[test]
+    fn lets_everything_through_without_budgets() {
+        let limiter = RateLimiter::new(&None);
+        for _ in 0..1000 {
+            limiter.charge("eth_call");
+        }
+        assert!(limiter.check("read").is_ok());
+    }
+
+    #[test]
+    fn goes_into_debt_of_the_requests_of_a_read() {
+        let limiter = limiter(None, Some(100));
+
+        // the second request of the read is charged although the budget has 40 units left
+        assert!(limiter.check("read").is_ok());
+        limiter.charge("eth_call");
+        limiter.charge("eth_call");
+
+        match limiter.check("read") {
+            Err(AppError::RateLimited(message)) => assert!(message.contains("compute units")),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn charges_default_compute_units_of_unlisted_methods() {
+        let limiter = limiter(None, Some(100));
+
+        for _ in 0..50 {
+            limiter.charge("eth_blockNumber");
+        }
+        assert!(limiter.check("read").is_ok());
+        limiter.charge("eth_call");
+        assert!(limiter.check("read").is_err());
+    }
+
+    #[test]
+    fn refills_the_debt_over_time() {
+        let limiter = limiter(Some(100), None);
+
+        for _ in 0..101 {
+            limiter.charge("eth_getBalance");
+        }
+        match limiter.check("read") {
+            Err(AppError::RateLimited(message)) => assert!(message.contains("requests")),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        // a token is refilled every 10ms
+        thread::sleep(Duration::from_millis(50));
+        assert!(limiter.check("read").is_ok());
+    }
+
+    #[test]
+    fn refills_up_to_one_second_of_tokens() {
+        let limiter = limiter(Some(100), None);
+
+        // 5 tokens over the full bucket are not kept
+        thread::sleep(Duration::from_millis(50));
+        for _ in 0..101 {
+            limiter.charge("eth_getBalance");
+        }
+        assert!(limiter.check("read").is_err());
+    }
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::node_clients::{bulkhead::Config as BulkheadConfig, upstream::Config as UpstreamConfig};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+    pub ss58_prefix: u16,
+    pub base_url: String,
+    pub supported_asset_ids: Option<Vec<String>>,
+    /// Timeouts, retries and circuit breaker of the node calls
+    #[serde(default)]
+    pub upstream: UpstreamConfig,
+    /// Concurrency limit of the reads of the chain
+    #[serde(default)]
+    pub bulkhead: BulkheadConfig,
+    /// Age in milliseconds up to which the last known balances are served
+    /// while the node fails, they are not kept if absent
+    #[serde(default)]
+    pub max_staleness: Option<u64>,
+}
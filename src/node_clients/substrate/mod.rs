@@ -0,0 +1,702 @@
+mod config;
+mod ss58;
+
+use codec::{Decode, Encode};
+use futures::stream::BoxStream;
+use reqwest::Client;
+use rustc_hex::{FromHex, ToHex};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sp_core_hashing::{blake2_128, twox_128};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::{
+    error::Error as AppError,
+    node_clients::{blocks, rate_limit::RateLimiter, upstream::Config as UpstreamConfig},
+    service::{
+        AddressBalancesService, Balance, BalanceAmount, BalanceKind, BlockRef, PinnedBalances,
+    },
+};
+
+pub use config::Config;
+
+static STATE_QUERY_STORAGE_AT_METHOD: &str = "state_queryStorageAt";
+static CHAIN_GET_HEADER_METHOD: &str = "chain_getHeader";
+static CHAIN_GET_BLOCK_HASH_METHOD: &str = "chain_getBlockHash";
+
+mod dtos {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, Serialize)]
+    pub struct JsonRpcRequest<'a, P> {
+        pub jsonrpc: &'static str,
+        pub id: u64,
+        pub method: &'a str,
+        pub params: P,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct JsonRpcError {
+        pub code: i64,
+        pub message: String,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct JsonRpcResponse<R> {
+        pub result: Option<R>,
+        pub error: Option<JsonRpcError>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct Header {
+        /// Hex encoded block number
+        pub number: String,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[allow(dead_code)]
+    pub struct StorageChangeSet {
+        pub block: String,
+        /// Pairs of hex encoded storage key and value, value is `None` for empty storage
+        pub changes: Vec<(String, Option<String>)>,
+    }
+}
+
+mod scale {
+    use codec::Decode;
+
+    /// Set in `AccountData::flags` once the account has been migrated to the
+    /// single `frozen` balance; before that the last two fields were
+    /// `misc_frozen` and `fee_frozen`
+    const IS_NEW_LOGIC: u128 = 0x80000000_00000000_00000000_00000000u128;
+
+    #[derive(Clone, Debug, Decode)]
+    pub struct AccountData {
+        pub free: u128,
+        pub reserved: u128,
+        pub frozen: u128,
+        pub flags: u128,
+    }
+
+    impl AccountData {
+        pub fn frozen(&self) -> u128 {
+            if self.flags & IS_NEW_LOGIC == IS_NEW_LOGIC {
+                self.frozen
+            } else {
+                self.frozen.max(self.flags)
+            }
+        }
+    }
+
+    /// `frame_system::AccountInfo`
+    #[derive(Clone, Debug, Decode)]
+    #[allow(dead_code)]
+    pub struct AccountInfo {
+        pub nonce: u32,
+        pub consumers: u32,
+        pub providers: u32,
+        pub sufficients: u32,
+        pub data: AccountData,
+    }
+
+    /// Leading part of `pallet_assets::AssetAccount`, the rest of the fields
+    /// differ between runtime versions and are not needed
+    #[derive(Clone, Debug, Decode)]
+    pub struct AssetAccount {
+        pub balance: u128,
+    }
+}
+
+#[derive(Clone)]
+pub struct NodeClient {
+    http_client: Client,
+    base_url: String,
+    native_token: String,
+    ss58_prefix: u16,
+    supported_asset_ids: Option<HashSet<String>>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl NodeClient {
+    pub fn try_new(
+        base_url: impl AsRef<str>,
+        native_token: impl AsRef<str>,
+        ss58_prefix: u16,
+        supported_asset_ids: &Option<&[impl AsRef<str>]>,
+        upstream: &UpstreamConfig,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> Result<Self, AppError> {
+        let http_client = upstream.http_client()?;
+
+        Ok(Self {
+            http_client,
+            base_url: base_url.as_ref().to_string(),
+            native_token: native_token.as_ref().to_string(),
+            ss58_prefix,
+            supported_asset_ids: supported_asset_ids.map(|supported_asset_ids| {
+                supported_asset_ids
+                    .to_owned()
+                    .iter()
+                    .map(|a| a.as_ref().to_string())
+                    .collect()
+            }),
+            rate_limiter,
+        })
+    }
+
+    async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, AppError> {
+        let request = dtos::JsonRpcRequest {
+            jsonrpc: "2.0",
+            id: 1,
+            method,
+            params,
+        };
+
+        self.rate_limiter.charge(method);
+        let response = self
+            .http_client
+            .post(&self.base_url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| AppError::Upstream(format!("Failed while calling {}, {}", method, e)))?;
+
+        if response.status() != 200 {
+            return Err(AppError::UpstreamResponse(format!(
+                "Failed to call {}: POST {} -> {}",
+                method,
+                self.base_url,
+                response.status()
+            )));
+        }
+
+        let json = response
+            .json::<dtos::JsonRpcResponse<R>>()
+            .await
+            .map_err(|e| AppError::UpstreamResponse(e.to_string()))?;
+
+        match (json.result, json.error) {
+            (_, Some(error)) => Err(AppError::UpstreamResponse(format!(
+                "{} -> {}: {}",
+                method, error.code, error.message
+            ))),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(AppError::UpstreamResponse(format!(
+                "{} -> empty result",
+                method
+            ))),
+        }
+    }
+
+    pub async fn block_height(&self) -> Result<u64, AppError> {
+        let header = self
+            .request::<_, dtos::Header>(CHAIN_GET_HEADER_METHOD, ())
+            .await?;
+
+        u64::from_str_radix(header.number.trim_start_matches("0x"), 16)
+            .map_err(|e| AppError::UpstreamResponse(format!("block number: {}", e)))
+    }
+
+    /// Block at `height`, the latest one if `None`, with the timestamp set by its `Timestamp.Now`
+    pub async fn block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+        let block_hash = match height {
+            Some(height) => self.block_hash(height).await?,
+            None => self
+                .request::<_, Option<String>>(CHAIN_GET_BLOCK_HASH_METHOD, ())
+                .await?
+                .ok_or_else(|| AppError::UpstreamResponse("latest block is missing".to_string()))?,
+        };
+
+        let header = self
+            .request::<_, dtos::Header>(CHAIN_GET_HEADER_METHOD, (&block_hash,))
+            .await?;
+        let block_number = u64::from_str_radix(header.number.trim_start_matches("0x"), 16)
+            .map_err(|e| AppError::UpstreamResponse(format!("block number: {}", e)))?;
+
+        let key = storage_map_key("Timestamp", "Now", &[]);
+        let block_timestamp = self
+            .storage(&[key], Some(&block_hash))
+            .await?
+            .pop()
+            .flatten()
+            .map(|value| decode_scale::<u64>(&value).map(|millis| millis / 1000))
+            .transpose()?;
+
+        Ok(BlockRef {
+            block_number,
+            block_hash: Some(block_hash),
+            block_timestamp,
+        })
+    }
+
+    /// Hash of the `block`, resolved by its height if it is not known yet
+    async fn resolve_hash(&self, block: BlockRef) -> Result<String, AppError> {
+        match block.block_hash {
+            Some(block_hash) => Ok(block_hash),
+            None => self.block_hash(block.block_number).await,
+        }
+    }
+
+    /// Hash of the block of `height` on the canonical chain
+    pub async fn block_hash(&self, height: u64) -> Result<String, AppError> {
+        self.request::<_, Option<String>>(CHAIN_GET_BLOCK_HASH_METHOD, (height,))
+            .await?
+            .ok_or(AppError::BlockNotFound(height))
+    }
+
+    /// Reads raw values of storage `keys` at the block of `at` hash, the best block if `None`
+    ///
+    /// Values are returned in the order of `keys`, `None` stands for empty storage
+    pub async fn storage(
+        &self,
+        keys: &[Vec<u8>],
+        at: Option<&str>,
+    ) -> Result<Vec<Option<Vec<u8>>>, AppError> {
+        let hex_keys = keys
+            .iter()
+            .map(|key| format!("0x{}", key.to_hex::<String>()))
+            .collect::<Vec<_>>();
+
+        let change_sets = self
+            .request::<_, Vec<dtos::StorageChangeSet>>(
+                STATE_QUERY_STORAGE_AT_METHOD,
+                (&hex_keys, at),
+            )
+            .await?;
+
+        let mut values = change_sets
+            .into_iter()
+            .flat_map(|change_set| change_set.changes)
+            .map(|(key, value)| (key.to_lowercase(), value))
+            .collect::<HashMap<_, _>>();
+
+        hex_keys
+            .iter()
+            .map(|key| match values.remove(key).flatten() {
+                Some(value) => decode_hex(&value).map(Some),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    pub async fn address_account_info(
+        &self,
+        account_id: &[u8; 32],
+        at: Option<&str>,
+    ) -> Result<Option<scale::AccountInfo>, AppError> {
+        let key = storage_map_key("System", "Account", &[&account_id[..]]);
+
+        let value = self.storage(&[key], at).await?.pop().flatten();
+
+        value
+            .map(|value| decode_scale::<scale::AccountInfo>(&value))
+            .transpose()
+    }
+
+    pub async fn address_assets_balances(
+        &self,
+        account_id: &[u8; 32],
+        asset_ids: &[u32],
+        at: Option<&str>,
+    ) -> Result<Vec<u128>, AppError> {
+        let keys = asset_ids
+            .iter()
+            .map(|asset_id| {
+                storage_map_key("Assets", "Account", &[&asset_id.encode(), &account_id[..]])
+            })
+            .collect::<Vec<_>>();
+
+        self.storage(&keys, at)
+            .await?
+            .into_iter()
+            .map(|value| match value {
+                Some(value) => decode_scale::<scale::AssetAccount>(&value).map(|a| a.balance),
+                None => Ok(0),
+            })
+            .collect()
+    }
+
+    async fn native_balance(&self, address: String, at: Option<&str>) -> Result<Balance, AppError> {
+        let account_id = ss58::decode(&address, self.ss58_prefix)?;
+
+        let (free, reserved, frozen) = match self.address_account_info(&account_id, at).await? {
+            Some(account_info) => (
+                account_info.data.free,
+                account_info.data.reserved,
+                account_info.data.frozen(),
+            ),
+            None => (0, 0, 0),
+        };
+
+        let balance = Balance {
+            asset_id: self.native_token.clone(),
+            caip_asset_id: None,
+            balances: vec![
+                BalanceAmount {
+                    kind: BalanceKind::Free,
+                    amount: to_amount(free)?,
+                },
+                BalanceAmount {
+                    kind: BalanceKind::Reserved,
+                    amount: to_amount(reserved)?,
+                },
+                BalanceAmount {
+                    kind: BalanceKind::Frozen,
+                    amount: to_amount(frozen)?,
+                },
+            ],
+            staleness: None,
+        };
+
+        Ok(balance)
+    }
+
+    async fn assets_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        at: Option<&str>,
+    ) -> Result<Vec<Balance>, AppError> {
+        if asset_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let account_id = ss58::decode(&address, self.ss58_prefix)?;
+
+        let parsed_asset_ids = asset_ids
+            .iter()
+            .map(|asset_id| {
+                asset_id
+                    .parse::<u32>()
+                    .map_err(|_| AppError::InvalidAssetId(asset_id.to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let balances = self
+            .address_assets_balances(&account_id, &parsed_asset_ids, at)
+            .await?;
+
+        asset_ids
+            .iter()
+            .zip(balances)
+            .map(|(asset_id, balance)| {
+                Ok(Balance::single(
+                    asset_id,
+                    &BalanceKind::Free,
+                    to_amount(balance)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl AddressBalancesService for NodeClient {
+    fn is_asset_supported(&self, asset_id: String) -> bool {
+        self.supported_asset_ids
+            .as_ref()
+            .is_some_and(|supported_asset_ids| supported_asset_ids.contains(&asset_id))
+    }
+
+    fn new_blocks(&self) -> BoxStream<'static, u64> {
+        let client = self.clone();
+        blocks::poll_heights(move || {
+            let client = client.clone();
+            async move { client.block_height().await }
+        })
+    }
+
+    async fn get_balance(&self, address: String) -> Result<crate::service::Balance, AppError> {
+        self.native_balance(address, None).await
+    }
+
+    async fn get_assets_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<crate::service::Balance>, AppError> {
+        self.assets_balances(address, asset_ids, None).await
+    }
+
+    async fn get_pinned_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        let block = self.block(None).await?;
+        let at = block.block_hash.as_deref();
+
+        let assets = self.assets_balances(address.clone(), asset_ids, at);
+        let balances = if native {
+            let native = self.native_balance(address, at);
+            let (native, assets) = futures::try_join!(native, assets)?;
+            std::iter::once(native).chain(assets).collect()
+        } else {
+            assets.await?
+        };
+
+        Ok(PinnedBalances { block, balances })
+    }
+
+    fn supports_balances_at(&self) -> bool {
+        true
+    }
+
+    async fn get_block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+        self.block(height).await
+    }
+
+    async fn get_balance_at(&self, address: String, block: BlockRef) -> Result<Balance, AppError> {
+        let block_hash = self.resolve_hash(block).await?;
+        self.native_balance(address, Some(&block_hash)).await
+    }
+
+    async fn get_assets_balances_at(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        block: BlockRef,
+    ) -> Result<Vec<Balance>, AppError> {
+        let block_hash = self.resolve_hash(block).await?;
+        self.assets_balances(address, asset_ids, Some(&block_hash))
+            .await
+    }
+}
+
+/// Builds a storage key of a map with `Blake2_128Concat` hashed keys
+fn storage_map_key(pallet: &str, storage: &str, keys: &[&[u8]]) -> Vec<u8> {
+    let mut key = [twox_128(pallet.as_bytes()), twox_128(storage.as_bytes())].concat();
+    for k in keys {
+        key.extend_from_slice(&blake2_128(k));
+        key.extend_from_slice(k);
+    }
+    key
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, AppError> {
+    Ok(value.trim_start_matches("0x").from_hex()?)
+}
+
+fn decode_scale<T: Decode>(mut value: &[u8]) -> Result<T, AppError> {
+    T::decode(&mut value).map_err(|e| AppError::ScaleDecode(e.to_string()))
+}
+
+fn to_amount(value: u128) -> Result<u64, AppError> {
+    u64::try_from(value).map_err(|_| AppError::AmountOverflow(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    /// Polkadot address of the well-known development account Alice
+    const ALICE: &str = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+
+    /// `System.Account` of Alice
+    const ALICE_ACCOUNT_KEY: &str = "0x26aa394eea5630e07c48ae0c9558cef7b99d880ec681799c0cf30e8886371da9de1e86a9a8c739864cf3cc5ec2bea59fd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+
+    /// `Assets.Account` of the asset 1984 (USDt) of Alice
+    const ALICE_ASSET_KEY: &str = "0x682a59d51ab9e48a8c8cc418ff9708d2b99d880ec681799c0cf30e8886371da9a319d0e87221ca1ee751c1529f201522c0070000de1e86a9a8c739864cf3cc5ec2bea59fd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+
+    /// `AccountInfo` with 1234.5 DOT free, 20 DOT reserved and 100 DOT frozen
+    const ACCOUNT_INFO: &str = "0x0500000001000000010000000000000000fa564b3a0b0000000000000000000000d0ed902e00000000000000000000000010a5d4e8000000000000000000000000000000000000000000000000000080";
+
+    /// `AccountInfo` before the migration to the single frozen balance,
+    /// with 30 DOT `misc_frozen` and 40 DOT `fee_frozen`
+    const LEGACY_ACCOUNT_INFO: &str = "0x0500000001000000010000000000000000fa564b3a0b000000000000000000000000000000000000000000000000000000b864d945000000000000000000000000a0db215d0000000000000000000000";
+
+    /// `AssetAccount` holding 25 USDt, followed by the fields which are not decoded
+    const ASSET_ACCOUNT: &str = "0x40787d01000000000000000000000000000000";
+
+    fn account_info(value: &str) -> scale::AccountInfo {
+        decode_scale(&decode_hex(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn builds_storage_keys() {
+        let alice = ss58::decode(ALICE, 0).unwrap();
+        let key = |key: Vec<u8>| format!("0x{}", key.to_hex::<String>());
+
+        assert_eq!(
+            key(storage_map_key("Timestamp", "Now", &[])),
+            "0xf0c365c3cf59d671eb72da0e7a4113c49f1f0515f462cdcf84e0f1d6045dfcbb"
+        );
+        assert_eq!(
+            key(storage_map_key("System", "Account", &[&alice[..]])),
+            ALICE_ACCOUNT_KEY
+        );
+        assert_eq!(
+            key(storage_map_key(
+                "Assets",
+                "Account",
+                &[&1984u32.encode(), &alice[..]]
+            )),
+            ALICE_ASSET_KEY
+        );
+    }
+
+    #[test]
+    fn decodes_account_data() {
+        let account_info = account_info(ACCOUNT_INFO);
+
+        assert_eq!(account_info.nonce, 5);
+        assert_eq!(account_info.data.free, 12_345_000_000_000);
+        assert_eq!(account_info.data.reserved, 200_000_000_000);
+        assert_eq!(account_info.data.frozen(), 1_000_000_000_000);
+    }
+
+    #[test]
+    fn decodes_frozen_balance_of_legacy_account_data() {
+        let account_info = account_info(LEGACY_ACCOUNT_INFO);
+
+        // the larger of `misc_frozen` and `fee_frozen`
+        assert_eq!(account_info.data.frozen(), 400_000_000_000);
+    }
+
+    #[test]
+    fn rejects_truncated_account_data() {
+        let value = decode_hex(&ACCOUNT_INFO[..ACCOUNT_INFO.len() - 2]).unwrap();
+
+        assert!(matches!(
+            decode_scale::<scale::AccountInfo>(&value),
+            Err(AppError::ScaleDecode(_))
+        ));
+    }
+
+    /// JSON-RPC node stand-in serving the storage of Alice
+    async fn node_stand_in(account_info: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, account_info));
+            }
+        });
+
+        url
+    }
+
+    async fn serve(socket: TcpStream, account_info: &'static str) {
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader);
+
+        loop {
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).await.unwrap();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+
+            let changes = request["params"][0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|key| {
+                    let value = match key.as_str().unwrap() {
+                        ALICE_ACCOUNT_KEY => Some(account_info),
+                        ALICE_ASSET_KEY => Some(ASSET_ACCOUNT),
+                        _ => None,
+                    };
+                    json!([key, value])
+                })
+                .collect::<Vec<_>>();
+            let result = json!([{"block": "0x00", "changes": changes}]);
+            let body = json!({"jsonrpc": "2.0", "id": request["id"], "result": result}).to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if writer.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn client(account_info: &'static str) -> NodeClient {
+        let url = node_stand_in(account_info).await;
+
+        NodeClient::try_new(
+            &url,
+            "DOT",
+            0,
+            &Some(&["1984", "1337"][..]),
+            &UpstreamConfig::default(),
+            Arc::new(RateLimiter::new(&None)),
+        )
+        .unwrap()
+    }
+
+    fn amounts(balance: &Balance) -> Vec<(BalanceKind, u64)> {
+        balance
+            .balances
+            .iter()
+            .map(|amount| (amount.kind.clone(), amount.amount))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reads_native_balance() {
+        let client = client(ACCOUNT_INFO).await;
+
+        let balance = client.get_balance(ALICE.to_string()).await.unwrap();
+        assert_eq!(balance.asset_id, "DOT");
+        assert_eq!(
+            amounts(&balance),
+            vec![
+                (BalanceKind::Free, 12_345_000_000_000),
+                (BalanceKind::Reserved, 200_000_000_000),
+                (BalanceKind::Frozen, 1_000_000_000_000),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_frozen_native_balance_of_legacy_account_data() {
+        let client = client(LEGACY_ACCOUNT_INFO).await;
+
+        let balance = client.get_balance(ALICE.to_string()).await.unwrap();
+        assert_eq!(amounts(&balance)[2], (BalanceKind::Frozen, 400_000_000_000));
+    }
+
+    #[tokio::test]
+    async fn reads_assets_balances() {
+        let client = client(ACCOUNT_INFO).await;
+
+        // the account of Alice of the asset 1337 is empty
+        let balances = client
+            .get_assets_balances(
+                ALICE.to_string(),
+                vec!["1984".to_string(), "1337".to_string()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(amounts(&balances[0]), vec![(BalanceKind::Free, 25_000_000)]);
+        assert_eq!(amounts(&balances[1]), vec![(BalanceKind::Free, 0)]);
+    }
+}
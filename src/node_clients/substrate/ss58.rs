@@ -0,0 +1,116 @@
+use sp_core_hashing::blake2_512;
+
+use crate::error::Error as AppError;
+
+static SS58_CHECKSUM_PREFIX: &[u8] = b"SS58PRE";
+
+const ACCOUNT_ID_LENGTH: usize = 32;
+const CHECKSUM_LENGTH: usize = 2;
+
+/// Decodes SS58 encoded `address` into a 32-byte account id
+///
+/// Fails if the checksum does not match or the address belongs to a network
+/// with a prefix other than `expected_prefix`
+pub fn decode(address: impl AsRef<str>, expected_prefix: u16) -> Result<[u8; 32], AppError> {
+    let address = address.as_ref();
+    let invalid = |reason: &str| AppError::InvalidAddress(format!("{}: {}", address, reason));
+
+    let data = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| invalid("not a base58 string"))?;
+
+    let (prefix_length, prefix) = match data.first() {
+        Some(b) if *b < 64 => (1, *b as u16),
+        Some(b) if *b < 128 && data.len() > 1 => {
+            let lower = (*b << 2) | (data[1] >> 6);
+            let upper = data[1] & 0b0011_1111;
+            (2, (lower as u16) | ((upper as u16) << 8))
+        }
+        _ => return Err(invalid("unsupported address format")),
+    };
+
+    if data.len() != prefix_length + ACCOUNT_ID_LENGTH + CHECKSUM_LENGTH {
+        return Err(invalid("unexpected length"));
+    }
+
+    let (payload, checksum) = data.split_at(prefix_length + ACCOUNT_ID_LENGTH);
+    let hash = blake2_512(&[SS58_CHECKSUM_PREFIX, payload].concat());
+    if checksum != &hash[..CHECKSUM_LENGTH] {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    if prefix != expected_prefix {
+        return Err(invalid(&format!(
+            "network prefix {} does not match expected {}",
+            prefix, expected_prefix
+        )));
+    }
+
+    let mut account_id = [0u8; ACCOUNT_ID_LENGTH];
+    account_id.copy_from_slice(&payload[prefix_length..]);
+
+    Ok(account_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Account id of the well-known development account Alice
+    const ALICE: [u8; 32] = [
+        0xd4, 0x35, 0x93, 0xc7, 0x15, 0xfd, 0xd3, 0x1c, 0x61, 0x14, 0x1a, 0xbd, 0x04, 0xa9, 0x9f,
+        0xd6, 0x82, 0x2c, 0x85, 0x58, 0x85, 0x4c, 0xcd, 0xe3, 0x9a, 0x56, 0x84, 0xe7, 0xa5, 0x6d,
+        0xa2, 0x7d,
+    ];
+
+    #[test]
+    fn decodes_addresses_of_single_byte_prefixes() {
+        let polkadot = decode("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5", 0).unwrap();
+        let kusama = decode("HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F", 2).unwrap();
+        let substrate = decode("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY", 42).unwrap();
+
+        assert_eq!(polkadot, ALICE);
+        assert_eq!(kusama, ALICE);
+        assert_eq!(substrate, ALICE);
+    }
+
+    #[test]
+    fn decodes_addresses_of_two_byte_prefixes() {
+        let account_id = decode("VdvKmYJfD4VXA9fzz1SbmCo2eYHSzUFbaDCZSuaNKJAe8YNg6", 1284).unwrap();
+
+        assert_eq!(account_id, ALICE);
+    }
+
+    #[test]
+    fn rejects_addresses_of_other_networks() {
+        let result = decode("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY", 0);
+
+        match result {
+            Err(AppError::InvalidAddress(message)) => {
+                assert!(message.contains("network prefix 42 does not match expected 0"))
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn rejects_addresses_with_wrong_checksum() {
+        // the last character of the Polkadot address of Alice is changed
+        let result = decode("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp6", 0);
+
+        match result {
+            Err(AppError::InvalidAddress(message)) => assert!(message.contains("checksum")),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_addresses() {
+        for address in ["", "0OIl", "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1Mgbj"] {
+            assert!(matches!(
+                decode(address, 0),
+                Err(AppError::InvalidAddress(_))
+            ));
+        }
+    }
+}
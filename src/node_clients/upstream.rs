@@ -0,0 +1,607 @@
+use futures::stream::BoxStream;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::{
+    error::Error as AppError,
+    node_clients::rate_limit::{Config as RateLimitConfig, RateLimiter},
+    service::{AddressBalancesService, Balance, BlockRef, PinnedBalances, VerifiedBalances},
+    valuation::Price,
+};
+
+static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+
+/// Timeouts, retries and circuit breaker of the calls to a node
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+    /// Timeout of establishing a connection in milliseconds
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    /// Timeout of a single HTTP request in milliseconds
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    /// Timeout of an attempt of a read, which may take several requests, in milliseconds
+    #[serde(default = "default_call_timeout")]
+    pub call_timeout: u64,
+    /// Retries of a read failed due to the node
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Upper bound of the first retry delay in milliseconds, doubled for every next one
+    #[serde(default = "default_initial_backoff")]
+    pub initial_backoff: u64,
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff: u64,
+    /// Consecutive failures opening the circuit
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Time the circuit stays open before a trial call in milliseconds
+    #[serde(default = "default_open_duration")]
+    pub open_duration: u64,
+    /// Budgets of the provider, reads are rejected while they are spent
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            connect_timeout: default_connect_timeout(),
+            request_timeout: default_request_timeout(),
+            call_timeout: default_call_timeout(),
+            retries: default_retries(),
+            initial_backoff: default_initial_backoff(),
+            max_backoff: default_max_backoff(),
+            failure_threshold: default_failure_threshold(),
+            open_duration: default_open_duration(),
+            rate_limit: None,
+        }
+    }
+}
+
+impl Config {
+    /// HTTP client with the connect and request timeouts
+    pub fn http_client(&self) -> Result<reqwest::Client, AppError> {
+        let client = reqwest::Client::builder()
+            .user_agent(APP_USER_AGENT)
+            .connect_timeout(Duration::from_millis(self.connect_timeout))
+            .timeout(Duration::from_millis(self.request_timeout))
+            .build()
+            .map_err(std::sync::Arc::new)?;
+
+        Ok(client)
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// Whether the trial call of the half-open circuit is in flight
+    trial: bool,
+}
+
+/// Fails calls fast after `failure_threshold` consecutive failures, once `open_duration`
+/// passes a single trial call is let through, which closes the circuit if it succeeds
+/// and opens it again if it fails
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<BreakerState>,
+}
+
+/// Call let through by the circuit, the trial one is ended when it is dropped,
+/// so a cancelled trial call does not keep the circuit half-open
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    trial: bool,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.trial {
+            let mut state = self
+                .breaker
+                .state
+                .lock()
+                .expect("circuit breaker lock is poisoned");
+            state.trial = false;
+        }
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_duration,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Lets the call through, or returns the time left until the circuit lets
+    /// a trial call through, zero while the trial call is in flight
+    pub fn acquire(&self) -> Result<Permit<'_>, Duration> {
+        let mut state = self.state.lock().expect("circuit breaker lock is poisoned");
+        let open_until = match state.open_until {
+            Some(open_until) => open_until,
+            None => {
+                return Ok(Permit {
+                    breaker: self,
+                    trial: false,
+                })
+            }
+        };
+
+        if let Some(open_for) = open_until.checked_duration_since(Instant::now()) {
+            return Err(open_for);
+        }
+        if state.trial {
+            return Err(Duration::ZERO);
+        }
+        state.trial = true;
+        Ok(Permit {
+            breaker: self,
+            trial: true,
+        })
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().expect("circuit breaker lock is poisoned");
+        *state = BreakerState::default();
+    }
+
+    /// Returns `true` if the failure opened the circuit
+    pub fn record_failure(&self) -> bool {
+        let mut state = self.state.lock().expect("circuit breaker lock is poisoned");
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+
+        // a failed trial call opens the half-open circuit at once
+        if state.open_until.is_some() || state.consecutive_failures >= self.failure_threshold {
+            state.open_until = Some(Instant::now() + self.open_duration);
+            return true;
+        }
+        false
+    }
+}
+
+/// Whether the call may succeed if repeated, i.e. the node is unreachable or slow
+/// rather than answered with an unexpected response
+///
+/// Failed reads of some of the tokens are not retried, since that would read
+/// all of them again
+fn is_transient(e: &AppError) -> bool {
+    matches!(
+        e,
+        AppError::Upstream(_) | AppError::EthersProvider(_) | AppError::UpstreamTimeout(_)
+    )
+}
+
+type Client = Box<dyn AddressBalancesService + Send + Sync>;
+
+/// Bounds every read of the node client by a timeout, retries the reads failed
+/// due to the node with jittered exponential backoff and fails them fast while
+/// the circuit of the node is open or its budgets are spent
+pub struct ResilientClient {
+    /// Name of the upstream in the errors, so the url with credentials is not exposed
+    name: String,
+    client: Client,
+    config: Config,
+    breaker: CircuitBreaker,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl ResilientClient {
+    /// The `rate_limiter` is the one the requests of the `client` are charged to
+    pub fn new(
+        name: impl AsRef<str>,
+        client: Client,
+        config: &Config,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> Self {
+        Self {
+            name: name.as_ref().to_string(),
+            client,
+            config: config.clone(),
+            breaker: CircuitBreaker::new(
+                config.failure_threshold,
+                Duration::from_millis(config.open_duration),
+            ),
+            rate_limiter,
+        }
+    }
+
+    /// Reads are idempotent, so every one is retried
+    async fn call<T, F, Fut>(&self, read: F) -> Result<T, AppError>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T, AppError>> + Send,
+        T: Send,
+    {
+        let mut attempt = 0;
+        loop {
+            let permit = self.breaker.acquire().map_err(|open_for| {
+                AppError::CircuitOpen(format!(
+                    "{} is unavailable for {}s",
+                    self.name,
+                    open_for.as_secs() + 1
+                ))
+            })?;
+            self.rate_limiter.check(&self.name)?;
+
+            let result =
+                match tokio::time::timeout(Duration::from_millis(self.config.call_timeout), read())
+                    .await
+                {
+                    Ok(result) => result,
+                    Err(_) => Err(AppError::UpstreamTimeout(format!(
+                        "{} did not answer in {}ms",
+                        self.name, self.config.call_timeout
+                    ))),
+                };
+
+            // the other errors tell nothing about the health of the node
+            let error = match result {
+                Ok(value) => {
+                    self.breaker.record_success();
+                    return Ok(value);
+                }
+                Err(e) if is_transient(&e) => e,
+                Err(e) => return Err(e),
+            };
+
+            if self.breaker.record_failure() {
+                warn!("circuit of {} is open: {}", self.name, error);
+            }
+            drop(permit);
+
+            if attempt >= self.config.retries {
+                return Err(error);
+            }
+            attempt += 1;
+
+            warn!("{} failed, retry {}: {}", self.name, attempt, error);
+            tokio::time::sleep(self.backoff(attempt)).await;
+        }
+    }
+
+    /// Random delay up to the exponential backoff of the `attempt`, so the retries
+    /// of concurrent calls do not hit the node at once
+    fn backoff(&self, attempt: u32) -> Duration {
+        let max = self
+            .config
+            .initial_backoff
+            .saturating_mul(2u64.saturating_pow(attempt - 1))
+            .min(self.config.max_backoff);
+
+        Duration::from_millis(rand::thread_rng().gen_range(0..=max))
+    }
+}
+
+#[async_trait::async_trait]
+impl AddressBalancesService for ResilientClient {
+    fn is_asset_supported(&self, asset_id: String) -> bool {
+        self.client.is_asset_supported(asset_id)
+    }
+
+    fn new_blocks(&self) -> BoxStream<'static, u64> {
+        self.client.new_blocks()
+    }
+
+    async fn get_balance(&self, address: String) -> Result<Balance, AppError> {
+        self.call(|| self.client.get_balance(address.clone())).await
+    }
+
+    async fn get_assets_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.call(|| {
+            self.client
+                .get_assets_balances(address.clone(), asset_ids.clone())
+        })
+        .await
+    }
+
+    async fn get_all_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.call(|| {
+            self.client
+                .get_all_balances(address.clone(), asset_ids.clone())
+        })
+        .await
+    }
+
+    async fn get_pinned_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        self.call(|| {
+            self.client
+                .get_pinned_balances(address.clone(), asset_ids.clone(), native)
+        })
+        .await
+    }
+
+    async fn get_verified_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<VerifiedBalances, AppError> {
+        self.call(|| {
+            self.client
+                .get_verified_balances(address.clone(), asset_ids.clone(), native)
+        })
+        .await
+    }
+
+    async fn get_block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+        self.call(|| self.client.get_block(height)).await
+    }
+
+    fn supports_balances_at(&self) -> bool {
+        self.client.supports_balances_at()
+    }
+
+    async fn get_balance_at(&self, address: String, block: BlockRef) -> Result<Balance, AppError> {
+        self.call(|| self.client.get_balance_at(address.clone(), block.clone()))
+            .await
+    }
+
+    async fn get_assets_balances_at(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        block: BlockRef,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.call(|| {
+            self.client
+                .get_assets_balances_at(address.clone(), asset_ids.clone(), block.clone())
+        })
+        .await
+    }
+
+    async fn get_prices(&self, asset_ids: Vec<String>) -> Result<HashMap<String, Price>, AppError> {
+        self.call(|| self.client.get_prices(asset_ids.clone()))
+            .await
+    }
+}
+
+fn default_connect_timeout() -> u64 {
+    3000
+}
+
+fn default_request_timeout() -> u64 {
+    10000
+}
+
+fn default_call_timeout() -> u64 {
+    30000
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_initial_backoff() -> u64 {
+    100
+}
+
+fn default_max_backoff() -> u64 {
+    2000
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_open_duration() -> u64 {
+    30000
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream::{self, StreamExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::service::BalanceKind;
+
+    const OPEN_DURATION: Duration = Duration::from_millis(50);
+
+    fn open_breaker() -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(2, OPEN_DURATION);
+        assert!(!breaker.record_failure());
+        assert!(breaker.acquire().is_ok());
+        assert!(breaker.record_failure());
+        breaker
+    }
+
+    #[test]
+    fn opens_circuit_after_consecutive_failures() {
+        let breaker = open_breaker();
+
+        match breaker.acquire() {
+            Err(open_for) => assert!(open_for > Duration::ZERO && open_for <= OPEN_DURATION),
+            Ok(_) => panic!("open circuit let a call through"),
+        };
+    }
+
+    #[test]
+    fn lets_single_trial_call_through_half_open_circuit() {
+        let breaker = open_breaker();
+        std::thread::sleep(OPEN_DURATION);
+
+        let trial = breaker.acquire().expect("trial call is let through");
+        assert!(matches!(breaker.acquire(), Err(Duration::ZERO)));
+
+        breaker.record_success();
+        drop(trial);
+        let permit = breaker
+            .acquire()
+            .expect("closed circuit lets calls through");
+        assert!(!permit.trial);
+    }
+
+    #[test]
+    fn opens_circuit_again_after_failed_trial_call() {
+        let breaker = open_breaker();
+        std::thread::sleep(OPEN_DURATION);
+
+        let trial = breaker.acquire().expect("trial call is let through");
+        assert!(breaker.record_failure());
+        drop(trial);
+
+        match breaker.acquire() {
+            Err(open_for) => assert!(open_for > Duration::ZERO),
+            Ok(_) => panic!("open circuit let a call through"),
+        };
+    }
+
+    #[test]
+    fn lets_another_trial_call_through_once_trial_permit_is_dropped() {
+        let breaker = open_breaker();
+        std::thread::sleep(OPEN_DURATION);
+
+        // e.g. the trial call was cancelled before its outcome was recorded
+        drop(breaker.acquire().expect("trial call is let through"));
+
+        let trial = breaker.acquire().expect("next trial call is let through");
+        assert!(trial.trial);
+    }
+
+    /// Node failing with the `errors`, one per call, and answering afterwards
+    struct Node {
+        errors: Mutex<Vec<AppError>>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl AddressBalancesService for Node {
+        fn is_asset_supported(&self, _asset_id: String) -> bool {
+            true
+        }
+
+        fn new_blocks(&self) -> BoxStream<'static, u64> {
+            stream::empty().boxed()
+        }
+
+        async fn get_balance(&self, _address: String) -> Result<Balance, AppError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let mut errors = self.errors.lock().unwrap();
+            if errors.is_empty() {
+                Ok(Balance::single("ETH", &BalanceKind::Wallet, 5))
+            } else {
+                Err(errors.remove(0))
+            }
+        }
+
+        async fn get_assets_balances(
+            &self,
+            _address: String,
+            _asset_ids: Vec<String>,
+        ) -> Result<Vec<Balance>, AppError> {
+            Ok(vec![])
+        }
+
+        async fn get_pinned_balances(
+            &self,
+            _address: String,
+            _asset_ids: Vec<String>,
+            _native: bool,
+        ) -> Result<PinnedBalances, AppError> {
+            Err(AppError::BalancesAtBlockNotSupported)
+        }
+    }
+
+    fn client(errors: Vec<AppError>, config: Config) -> (ResilientClient, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let node = Node {
+            errors: Mutex::new(errors),
+            calls: calls.clone(),
+        };
+        let client = ResilientClient::new(
+            "ethereum",
+            Box::new(node),
+            &config,
+            Arc::new(RateLimiter::new(&None)),
+        );
+        (client, calls)
+    }
+
+    fn config(retries: u32, failure_threshold: u32) -> Config {
+        Config {
+            retries,
+            initial_backoff: 1,
+            max_backoff: 1,
+            failure_threshold,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let errors = vec![
+            AppError::Upstream("connection refused".to_string()),
+            AppError::UpstreamTimeout("no answer".to_string()),
+        ];
+        let (client, calls) = client(errors, config(2, 5));
+
+        assert!(client.get_balance("address".to_string()).await.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_transient_error_once_retries_are_spent() {
+        let errors = vec![
+            AppError::Upstream("connection refused".to_string()),
+            AppError::Upstream("connection refused".to_string()),
+        ];
+        let (client, calls) = client(errors, config(1, 5));
+
+        let result = client.get_balance("address".to_string()).await;
+        assert!(matches!(result, Err(AppError::Upstream(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_other_errors() {
+        for error in [
+            AppError::UpstreamResponse("unexpected response".to_string()),
+            AppError::TokenBalances(vec![(
+                "0xbb".to_string(),
+                AppError::Upstream("connection refused".to_string()),
+            )]),
+        ] {
+            let (client, calls) = client(vec![error], config(2, 5));
+
+            assert!(client.get_balance("address".to_string()).await.is_err());
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_fast_while_circuit_is_open() {
+        let errors = vec![AppError::Upstream("connection refused".to_string())];
+        let (client, calls) = client(errors, config(0, 1));
+
+        let result = client.get_balance("address".to_string()).await;
+        assert!(matches!(result, Err(AppError::Upstream(_))));
+
+        let result = client.get_balance("address".to_string()).await;
+        assert!(matches!(result, Err(AppError::CircuitOpen(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,8 +1,55 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::node_clients::{
+    bulkhead::Config as BulkheadConfig, quorum::Config as QuorumConfig,
+    upstream::Config as UpstreamConfig,
+};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     pub chain_id: u16,
     pub base_url: String,
     pub supported_asset_ids: Option<Vec<String>>,
+    /// Account publishing USD prices as data entries
+    #[serde(default)]
+    pub price_oracle: Option<PriceOracle>,
+    /// Balances are returned only if enough providers agree on them
+    #[serde(default)]
+    pub quorum: Option<QuorumConfig>,
+    /// Timeouts, retries and circuit breaker of the node calls
+    #[serde(default)]
+    pub upstream: UpstreamConfig,
+    /// Concurrency limit of the reads of the chain
+    #[serde(default)]
+    pub bulkhead: BulkheadConfig,
+    /// Age in milliseconds up to which the last known balances are served
+    /// while the node fails, they are not kept if absent
+    #[serde(default)]
+    pub max_staleness: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PriceOracle {
+    pub address: String,
+    /// Decimals of the integer prices of the data entries
+    #[serde(default = "default_price_decimals")]
+    pub price_decimals: u8,
+    /// Asset id (or `WAVES`) -> data entries of its price
+    pub assets: HashMap<String, OracleAsset>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OracleAsset {
+    /// Key of the integer entry holding the price
+    pub key: String,
+    /// Key of the integer entry holding the unix time in seconds of the price
+    #[serde(default)]
+    pub timestamp_key: Option<String>,
+    /// Decimals of the asset amounts
+    pub decimals: u8,
+}
+
+fn default_price_decimals() -> u8 {
+    8
 }
@@ -1,23 +1,44 @@
 mod config;
 
+use futures::stream::BoxStream;
 use reqwest::Client;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use tracing::warn;
 
 use crate::{
     error::Error as AppError,
-    service::{AddressBalancesService, Balance, BalanceAmount, BalanceKind},
+    node_clients::{blocks, rate_limit::RateLimiter, upstream::Config as UpstreamConfig},
+    service::{
+        AddressBalancesService, Balance, BalanceAmount, BalanceKind, BlockRef, PinnedBalances,
+    },
+    valuation::{Currency, Price},
 };
 
-pub use config::Config;
-
-static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+pub use config::{Config, OracleAsset, PriceOracle};
 
 static WAVES_ASSET_ID: &str = "WAVES";
 
+/// Attempts to read all balances of an address while the last block stays the same
+const SAME_BLOCK_ATTEMPTS: usize = 3;
+
 mod dtos {
     use serde::Deserialize;
 
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct BlockHeightResponse {
+        pub height: u64,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct BlockHeaderResponse {
+        pub height: u64,
+        /// Block id
+        pub id: String,
+        /// Unix time in milliseconds
+        pub timestamp: u64,
+    }
+
     #[derive(Clone, Debug, Deserialize)]
     pub struct AddressBalanceDetailsResponse {
         pub address: String,
@@ -30,6 +51,7 @@ mod dtos {
     }
 
     #[derive(Clone, Debug, Deserialize)]
+    #[allow(dead_code)]
     pub struct AddressAssetBalance {
         #[serde(rename = "assetId")]
         pub asset_id: String,
@@ -37,30 +59,40 @@ mod dtos {
     }
 
     #[derive(Clone, Debug, Deserialize)]
+    #[allow(dead_code)]
     pub struct AddressAssetsBalancesResponse {
         pub address: String,
         pub balances: Vec<AddressAssetBalance>,
     }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct DataEntry {
+        pub key: String,
+        pub value: serde_json::Value,
+    }
 }
 
+#[derive(Clone)]
 pub struct NodeClient {
     http_client: Client,
     base_url: String,
     supported_asset_ids: Option<HashSet<String>>,
+    price_oracle: Option<PriceOracle>,
+    rate_limiter: Arc<RateLimiter>,
 }
 
 impl NodeClient {
     pub fn try_new(
         base_url: impl AsRef<str>,
         supported_asset_ids: &Option<&[impl AsRef<str>]>,
+        price_oracle: &Option<PriceOracle>,
+        upstream: &UpstreamConfig,
+        rate_limiter: Arc<RateLimiter>,
     ) -> Result<Self, AppError> {
-        let http_client = reqwest::Client::builder()
-            .user_agent(APP_USER_AGENT)
-            .build()
-            .map_err(|e| Arc::new(e))?;
+        let http_client = upstream.http_client()?;
 
         Ok(Self {
-            http_client: http_client,
+            http_client,
             base_url: base_url.as_ref().to_string(),
             supported_asset_ids: supported_asset_ids.map(|supported_asset_ids| {
                 supported_asset_ids
@@ -69,9 +101,114 @@ impl NodeClient {
                     .map(|a| a.as_ref().to_string())
                     .collect()
             }),
+            price_oracle: price_oracle.clone(),
+            rate_limiter,
         })
     }
 
+    pub async fn block_height(&self) -> Result<u64, AppError> {
+        let url = format!("{}/blocks/height", self.base_url);
+        self.rate_limiter.charge("blocks/height");
+        let response = self.http_client.get(&url).send().await.map_err(|e| {
+            AppError::Upstream(format!("Failed while fetching block height, {}", e))
+        })?;
+
+        if response.status() == 200 {
+            let json = response
+                .json::<dtos::BlockHeightResponse>()
+                .await
+                .map_err(|e| AppError::UpstreamResponse(e.to_string()))?;
+
+            Ok(json.height)
+        } else {
+            Err(AppError::UpstreamResponse(format!(
+                "Failed to get block height: GET {} -> {}",
+                url,
+                response.status()
+            )))
+        }
+    }
+
+    pub async fn last_block_header(&self) -> Result<dtos::BlockHeaderResponse, AppError> {
+        let url = format!("{}/blocks/headers/last", self.base_url);
+        self.rate_limiter.charge("blocks/headers/last");
+        let response = self.http_client.get(&url).send().await.map_err(|e| {
+            AppError::Upstream(format!("Failed while fetching last block header, {}", e))
+        })?;
+
+        if response.status() == 200 {
+            let json = response
+                .json::<dtos::BlockHeaderResponse>()
+                .await
+                .map_err(|e| AppError::UpstreamResponse(e.to_string()))?;
+
+            Ok(json)
+        } else {
+            Err(AppError::UpstreamResponse(format!(
+                "Failed to get last block header: GET {} -> {}",
+                url,
+                response.status()
+            )))
+        }
+    }
+
+    /// Reads the native balance, if `native`, and balances of `asset_ids` concurrently
+    /// while the last block stays the same
+    ///
+    /// The node serves only the latest balances, so the last block is compared
+    /// before and after the reads, by its id too as microblocks change the balances
+    /// and the id of the last block without changing its height
+    async fn same_block_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        let mut attempt = 1;
+        loop {
+            let header = self.last_block_header().await?;
+
+            let assets = async {
+                if asset_ids.is_empty() {
+                    Ok(vec![])
+                } else {
+                    self.get_assets_balances(address.clone(), asset_ids.clone())
+                        .await
+                }
+            };
+            let balances = if native {
+                let native = self.get_balance(address.clone());
+                let (native, assets) = futures::try_join!(native, assets)?;
+                std::iter::once(native).chain(assets).collect()
+            } else {
+                assets.await?
+            };
+
+            let last = self.last_block_header().await?;
+            if last.height == header.height && last.id == header.id {
+                let block = BlockRef {
+                    block_number: header.height,
+                    block_hash: Some(header.id),
+                    block_timestamp: Some(header.timestamp / 1000),
+                };
+                return Ok(PinnedBalances { block, balances });
+            }
+
+            // the balances may be of either block, so they are not pinned to any
+            if attempt == SAME_BLOCK_ATTEMPTS {
+                return Err(AppError::Upstream(format!(
+                    "last block of waves changed on each of {} reads of balances, last from {} at {} to {} at {}",
+                    SAME_BLOCK_ATTEMPTS, header.id, header.height, last.id, last.height
+                )));
+            }
+            warn!(
+                "last block of waves changed from {} at {} to {} at {} while reading balances",
+                header.id, header.height, last.id, last.height
+            );
+            attempt += 1;
+        }
+    }
+
     pub async fn address_balance_details(
         &self,
         address: impl AsRef<str> + Send,
@@ -81,6 +218,7 @@ impl NodeClient {
             self.base_url,
             address.as_ref()
         );
+        self.rate_limiter.charge("addresses/balance/details");
         let response = self.http_client.get(&url).send().await.map_err(|e| {
             AppError::Upstream(format!(
                 "Failed while fetching address balance details, {}",
@@ -120,6 +258,7 @@ impl NodeClient {
             address.as_ref(),
             asset_ids
         );
+        self.rate_limiter.charge("assets/balance");
         let response = self.http_client.get(&url).send().await.map_err(|e| {
             AppError::Upstream(format!(
                 "Failed while fetching address regular balance, {}",
@@ -146,6 +285,99 @@ impl NodeClient {
             )))
         }
     }
+
+    pub async fn address_data(
+        &self,
+        address: impl AsRef<str> + Send,
+        keys: &[impl AsRef<str>],
+    ) -> Result<Vec<dtos::DataEntry>, AppError> {
+        let keys = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(keys.iter().map(|key| ("key", key.as_ref())))
+            .finish();
+        let url = format!(
+            "{}/addresses/data/{}?{}",
+            self.base_url,
+            address.as_ref(),
+            keys
+        );
+        self.rate_limiter.charge("addresses/data");
+        let response = self.http_client.get(&url).send().await.map_err(|e| {
+            AppError::Upstream(format!("Failed while fetching address data, {}", e))
+        })?;
+
+        if response.status() == 200 {
+            let json = response
+                .json::<Vec<dtos::DataEntry>>()
+                .await
+                .map_err(|e| AppError::UpstreamResponse(e.to_string()))?;
+
+            Ok(json)
+        } else {
+            Err(AppError::UpstreamResponse(format!(
+                "Failed to get address data: GET {} -> {}",
+                url,
+                response.status()
+            )))
+        }
+    }
+
+    /// Reads the prices of `asset_ids` from the data entries of the oracle account
+    pub async fn prices(&self, asset_ids: &[String]) -> Result<HashMap<String, Price>, AppError> {
+        let oracle = match &self.price_oracle {
+            Some(oracle) => oracle,
+            None => return Ok(HashMap::new()),
+        };
+
+        let assets = asset_ids
+            .iter()
+            .filter_map(|asset_id| {
+                oracle
+                    .assets
+                    .get(asset_id)
+                    .map(|asset| (asset_id.clone(), asset))
+            })
+            .collect::<HashMap<_, _>>();
+
+        if assets.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let mut keys = assets
+            .values()
+            .flat_map(|asset| std::iter::once(&asset.key).chain(asset.timestamp_key.as_ref()))
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+
+        let entries = self
+            .address_data(&oracle.address, &keys)
+            .await?
+            .into_iter()
+            .filter_map(|entry| entry.value.as_u64().map(|value| (entry.key, value)))
+            .collect::<HashMap<_, _>>();
+
+        Ok(assets
+            .into_iter()
+            .filter_map(|(asset_id, asset)| {
+                let answer = entries.get(&asset.key).copied().filter(|p| *p > 0)?;
+                let updated_at = asset
+                    .timestamp_key
+                    .as_ref()
+                    .and_then(|key| entries.get(key).copied());
+
+                Some((
+                    asset_id,
+                    Price {
+                        currency: Currency::Usd,
+                        answer: answer as u128,
+                        decimals: oracle.price_decimals as u32,
+                        asset_decimals: asset.decimals as u32,
+                        updated_at,
+                    },
+                ))
+            })
+            .collect())
+    }
 }
 
 #[async_trait::async_trait]
@@ -153,9 +385,15 @@ impl AddressBalancesService for NodeClient {
     fn is_asset_supported(&self, asset_id: String) -> bool {
         self.supported_asset_ids
             .as_ref()
-            .map_or(false, |supported_asset_ids| {
-                supported_asset_ids.contains(&asset_id)
-            })
+            .is_some_and(|supported_asset_ids| supported_asset_ids.contains(&asset_id))
+    }
+
+    fn new_blocks(&self) -> BoxStream<'static, u64> {
+        let client = self.clone();
+        blocks::poll_heights(move || {
+            let client = client.clone();
+            async move { client.block_height().await }
+        })
     }
 
     async fn get_balance(&self, address: String) -> Result<crate::service::Balance, AppError> {
@@ -163,6 +401,7 @@ impl AddressBalancesService for NodeClient {
 
         let balance = Balance {
             asset_id: WAVES_ASSET_ID.to_string(),
+            caip_asset_id: None,
             balances: vec![
                 BalanceAmount {
                     kind: BalanceKind::Wallet,
@@ -177,6 +416,7 @@ impl AddressBalancesService for NodeClient {
                     amount: balance_details.effective,
                 },
             ],
+            staleness: None,
         };
 
         Ok(balance)
@@ -197,4 +437,133 @@ impl AddressBalancesService for NodeClient {
 
         Ok(balances)
     }
+
+    async fn get_all_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        self.same_block_balances(address, asset_ids, true)
+            .await
+            .map(|pinned| pinned.balances)
+    }
+
+    async fn get_pinned_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        self.same_block_balances(address, asset_ids, native).await
+    }
+
+    async fn get_prices(&self, asset_ids: Vec<String>) -> Result<HashMap<String, Price>, AppError> {
+        self.prices(&asset_ids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    const ADDRESS: &str = "3P5Bfd58PPfNvBM2Hy8QfbcDqMeNtzg7KfP";
+
+    type Answer = Arc<dyn Fn(&str) -> Value + Send + Sync>;
+
+    /// Node stand-in answering the GET requests with the results of `answer` for their paths
+    async fn node_stand_in(answer: Answer) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, answer.clone()));
+            }
+        });
+
+        url
+    }
+
+    async fn serve(socket: TcpStream, answer: Answer) {
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader);
+
+        loop {
+            let mut path = None;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                if path.is_none() {
+                    path = line.split(' ').nth(1).map(ToString::to_string);
+                }
+            }
+
+            let body = answer(&path.unwrap_or_default()).to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if writer.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Client of a node whose last block has the id of `last_block` for each read of its header
+    async fn client(last_block: fn(usize) -> &'static str) -> NodeClient {
+        let reads = AtomicUsize::new(0);
+        let url = node_stand_in(Arc::new(move |path| match path {
+            "/blocks/headers/last" => json!({
+                "height": 20,
+                "id": last_block(reads.fetch_add(1, Ordering::SeqCst)),
+                "timestamp": 1_660_000_000_000u64,
+            }),
+            _ => json!({"address": ADDRESS, "regular": 5, "available": 4, "effective": 3}),
+        }))
+        .await;
+
+        NodeClient::try_new(
+            &url,
+            &None::<&[String]>,
+            &None,
+            &UpstreamConfig::default(),
+            Arc::new(RateLimiter::new(&None)),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rereads_balances_changed_by_microblock() {
+        // the microblock after the first read of the header changes the id of the last block
+        let client = client(|read| if read == 0 { "key" } else { "micro" }).await;
+
+        let pinned = client
+            .get_pinned_balances(ADDRESS.to_string(), vec![], true)
+            .await
+            .unwrap();
+        assert_eq!(pinned.block.block_number, 20);
+        assert_eq!(pinned.block.block_hash.as_deref(), Some("micro"));
+        assert_eq!(pinned.balances[0].balances[0].amount, 5);
+    }
+
+    #[tokio::test]
+    async fn fails_when_last_block_keeps_changing() {
+        let client = client(|read| if read % 2 == 0 { "key" } else { "micro" }).await;
+
+        let result = client
+            .get_pinned_balances(ADDRESS.to_string(), vec![], true)
+            .await;
+        assert!(matches!(result, Err(AppError::Upstream(_))));
+    }
 }
@@ -0,0 +1,228 @@
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::error::Error as AppError;
+use crate::registry::{AggregatedAsset, Registry};
+use crate::service::{Balance, BalanceAmount, BalancesService};
+use crate::subscriptions::Subscription;
+use crate::valuation::{self, format_decimal, Currency, ValuedBalance, VALUE_DECIMALS};
+
+/// Number of addresses read concurrently
+const CONCURRENCY: usize = 16;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AddressBalances {
+    pub address: String,
+    pub balances: Vec<Balance>,
+}
+
+/// Balances of the addresses of a single chain
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ChainSection {
+    /// CAIP-2 id of the chain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<AddressBalances>,
+    /// Balances of the addresses summed up by asset and kind
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub totals: Vec<ValuedBalance>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_value: Option<String>,
+    #[serde(skip)]
+    total: u128,
+    /// Set instead of the balances if any of them could not be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Portfolio {
+    pub chains: BTreeMap<String, ChainSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+    /// Sum of the values of the chains read successfully
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_value: Option<String>,
+    /// Totals of the chains summed up by canonical asset of the registry
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub assets: Vec<AggregatedAsset>,
+}
+
+/// Reads balances of `addresses` of every chain concurrently
+///
+/// A failure of a chain is reported in its section and does not affect the others
+pub async fn portfolio(
+    service: &(dyn BalancesService + Send + Sync),
+    registry: &Registry,
+    addresses: BTreeMap<String, Vec<String>>,
+    asset_ids: &BTreeMap<String, Vec<String>>,
+    currency: Option<Currency>,
+) -> Result<Portfolio, AppError> {
+    let sections = stream::iter(addresses)
+        .map(|(chain, addresses)| {
+            let asset_ids = asset_ids.get(&chain).cloned().unwrap_or_default();
+            async move {
+                let section = section(service, chain.clone(), addresses, asset_ids, currency)
+                    .await
+                    .unwrap_or_else(|e| ChainSection {
+                        error: Some(e.to_string()),
+                        ..Default::default()
+                    });
+                (chain, section)
+            }
+        })
+        .buffer_unordered(CONCURRENCY)
+        .collect::<BTreeMap<_, _>>()
+        .await;
+
+    let total_value = currency.map(|_| {
+        let total = sections
+            .values()
+            .map(|section| section.total)
+            .fold(0u128, u128::saturating_add);
+        format_decimal(total, VALUE_DECIMALS)
+    });
+
+    let totals = sections
+        .iter()
+        .filter_map(|(chain, section)| {
+            service
+                .caip()
+                .parse_chain(chain)
+                .ok()
+                .map(|chain| (chain, section))
+        })
+        .collect::<Vec<_>>();
+    let assets = registry.aggregate(totals.iter().flat_map(|(chain, section)| {
+        section
+            .totals
+            .iter()
+            .map(move |total| (chain, &total.balance))
+    }))?;
+
+    Ok(Portfolio {
+        chains: sections,
+        currency,
+        total_value,
+        assets,
+    })
+}
+
+async fn section(
+    service: &(dyn BalancesService + Send + Sync),
+    chain: String,
+    addresses: Vec<String>,
+    asset_ids: Vec<String>,
+    currency: Option<Currency>,
+) -> Result<ChainSection, AppError> {
+    let caip = service.caip();
+    let chain = caip.parse_chain(&chain)?;
+
+    let mut asset_ids = asset_ids
+        .iter()
+        .map(|asset_id| caip.parse_asset(&chain, asset_id))
+        .collect::<Result<Vec<_>, _>>()?;
+    asset_ids.sort();
+    asset_ids.dedup();
+
+    let not_supported = service.check_assets_support(chain.clone(), asset_ids.clone())?;
+    if !not_supported.is_empty() {
+        return Err(AppError::InvalidAssetId(not_supported.join(",")));
+    }
+
+    let mut addresses = addresses
+        .iter()
+        .map(|address| caip.parse_account(&chain, address))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut seen = HashSet::new();
+    addresses.retain(|address| seen.insert(address.clone()));
+
+    let results = stream::iter(addresses)
+        .map(|address| {
+            let subscription = Subscription {
+                chain: chain.clone(),
+                address,
+                asset_ids: asset_ids.clone(),
+            };
+            async move {
+                subscription
+                    .fetch(service)
+                    .await
+                    .map(|balances| AddressBalances {
+                        address: subscription.address,
+                        balances,
+                    })
+            }
+        })
+        .buffered(CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    let totals = sum(results.iter().flat_map(|address| &address.balances))?;
+
+    let (totals, total_value, total) = match currency {
+        Some(currency) => {
+            let valued = valuation::value(service, chain.clone(), totals, currency).await?;
+            (valued.balances, Some(valued.total_value), valued.total)
+        }
+        None => (
+            totals
+                .into_iter()
+                .map(|balance| ValuedBalance {
+                    balance,
+                    valuation: None,
+                })
+                .collect(),
+            None,
+            0,
+        ),
+    };
+
+    Ok(ChainSection {
+        chain_id: caip.chain_id(&chain),
+        addresses: results,
+        totals,
+        total_value,
+        total,
+        error: None,
+    })
+}
+
+/// Sums up amounts of the same asset and kind, keeping the order of first appearance
+fn sum<'a>(balances: impl IntoIterator<Item = &'a Balance>) -> Result<Vec<Balance>, AppError> {
+    let mut totals: Vec<Balance> = vec![];
+
+    for balance in balances {
+        let total = match totals.iter_mut().find(|t| t.asset_id == balance.asset_id) {
+            Some(total) => total,
+            None => {
+                totals.push(Balance {
+                    asset_id: balance.asset_id.clone(),
+                    caip_asset_id: balance.caip_asset_id.clone(),
+                    balances: vec![],
+                    staleness: None,
+                });
+                totals.last_mut().expect("total was just pushed")
+            }
+        };
+
+        for amount in &balance.balances {
+            match total.balances.iter_mut().find(|t| t.kind == amount.kind) {
+                Some(sum) => {
+                    sum.amount = sum.amount.checked_add(amount.amount).ok_or_else(|| {
+                        AppError::AmountOverflow(format!("{} + {}", sum.amount, amount.amount))
+                    })?;
+                }
+                None => total
+                    .balances
+                    .push(BalanceAmount::new(&amount.kind, amount.amount)),
+            }
+        }
+    }
+
+    Ok(totals)
+}
@@ -0,0 +1,198 @@
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+use crate::service::{find_amount, Balance, BalanceKind, BalancesService};
+
+/// Number of addresses read concurrently
+const CONCURRENCY: usize = 16;
+
+/// Balance the ledger expects an address to hold
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Expectation {
+    pub chain: Chain,
+    pub address: String,
+    pub asset_id: String,
+    /// Kind of the balance to compare, the first reported one if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<BalanceKind>,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Match {
+    #[serde(flatten)]
+    pub expectation: Expectation,
+    pub actual: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Mismatch {
+    #[serde(flatten)]
+    pub expectation: Expectation,
+    pub actual: u64,
+    /// `actual - amount`
+    pub delta: i128,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Failure {
+    #[serde(flatten)]
+    pub expectation: Expectation,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    pub total: usize,
+    pub matched: usize,
+    pub mismatched: usize,
+    pub failed: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Report {
+    pub summary: Summary,
+    pub matches: Vec<Match>,
+    pub mismatches: Vec<Mismatch>,
+    pub failures: Vec<Failure>,
+}
+
+/// Compares `expectations` with the actual balances
+///
+/// Balances differing by at most the tolerance of the asset in `tolerances`
+/// are considered matching
+pub async fn reconcile(
+    service: &(dyn BalancesService + Send + Sync),
+    expectations: Vec<Expectation>,
+    tolerances: &BTreeMap<String, u64>,
+) -> Report {
+    let mut by_address: HashMap<(Chain, String), Vec<(usize, Expectation)>> = HashMap::new();
+    for (idx, expectation) in expectations.into_iter().enumerate() {
+        by_address
+            .entry((expectation.chain.clone(), expectation.address.clone()))
+            .or_default()
+            .push((idx, expectation));
+    }
+
+    let results = stream::iter(by_address)
+        .map(|((chain, address), expectations)| async move {
+            let asset_ids = expectations
+                .iter()
+                .map(|(_, expectation)| expectation.asset_id.clone())
+                .collect::<Vec<_>>();
+            let balances = Arc::new(fetch(service, chain, address, asset_ids).await);
+            expectations
+                .into_iter()
+                .map(move |(idx, expectation)| (idx, expectation, balances.clone()))
+                .collect::<Vec<_>>()
+        })
+        .buffer_unordered(CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    // the report follows the order of the expectations
+    let mut results = results.into_iter().flatten().collect::<Vec<_>>();
+    results.sort_by_key(|(idx, _, _)| *idx);
+
+    let mut report = Report::default();
+    for (_, expectation, balances) in results {
+        report.add(expectation, &balances, tolerances);
+    }
+
+    report.summary = Summary {
+        total: report.matches.len() + report.mismatches.len() + report.failures.len(),
+        matched: report.matches.len(),
+        mismatched: report.mismatches.len(),
+        failed: report.failures.len(),
+    };
+
+    report
+}
+
+/// Reads balances of the supported `asset_ids` together with the native balance,
+/// which stands for any other requested asset
+async fn fetch(
+    service: &(dyn BalancesService + Send + Sync),
+    chain: Chain,
+    address: String,
+    mut asset_ids: Vec<String>,
+) -> Result<Vec<Balance>, AppError> {
+    asset_ids.sort();
+    asset_ids.dedup();
+
+    let not_supported = service.check_assets_support(chain.clone(), asset_ids.clone())?;
+    let supported = asset_ids
+        .into_iter()
+        .filter(|asset_id| !not_supported.contains(asset_id))
+        .collect::<Vec<_>>();
+
+    let mut balances = vec![];
+    if !not_supported.is_empty() {
+        balances.push(service.get_balance(chain.clone(), address.clone()).await?);
+    }
+    if !supported.is_empty() {
+        balances.extend(
+            service
+                .get_assets_balances(chain, address, supported)
+                .await?,
+        );
+    }
+
+    Ok(balances)
+}
+
+impl Report {
+    fn add(
+        &mut self,
+        expectation: Expectation,
+        balances: &Result<Vec<Balance>, AppError>,
+        tolerances: &BTreeMap<String, u64>,
+    ) {
+        let balances = match balances {
+            Ok(balances) => balances,
+            Err(e) => {
+                self.failures.push(Failure {
+                    expectation,
+                    error: e.to_string(),
+                });
+                return;
+            }
+        };
+
+        let actual = find_amount(balances, &expectation.asset_id, expectation.kind.as_ref());
+
+        let actual = match actual {
+            Some(actual) => actual,
+            None => {
+                self.failures.push(Failure {
+                    error: format!("Balance of asset {} is not available", expectation.asset_id),
+                    expectation,
+                });
+                return;
+            }
+        };
+
+        let delta = i128::from(actual) - i128::from(expectation.amount);
+        let tolerance = tolerances
+            .get(&expectation.asset_id)
+            .copied()
+            .unwrap_or_default();
+
+        if delta.unsigned_abs() <= u128::from(tolerance) {
+            self.matches.push(Match {
+                expectation,
+                actual,
+            });
+        } else {
+            self.mismatches.push(Mismatch {
+                expectation,
+                actual,
+                delta,
+            });
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+use crate::service::Balance;
+use crate::valuation::rescale;
+
+/// Asset known under different ids on different chains, e.g. USDT
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CanonicalAsset {
+    /// Decimals of the aggregated amounts
+    pub decimals: u8,
+    pub chains: Vec<ChainAsset>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChainAsset {
+    pub chain: Chain,
+    /// Either a supported asset or the native token of the chain
+    pub asset_id: String,
+    /// Decimals of the amounts on the chain
+    pub decimals: u8,
+}
+
+/// Amount of a canonical asset held on a chain
+#[derive(Clone, Debug, Serialize)]
+pub struct ChainAmount {
+    pub chain: Chain,
+    pub asset_id: String,
+    /// Amount in the decimals of the chain
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+/// Sum of the amounts of a canonical asset on all chains
+#[derive(Clone, Debug, Serialize)]
+pub struct AggregatedAsset {
+    pub asset: String,
+    /// Amount in the canonical decimals
+    pub amount: u128,
+    pub decimals: u8,
+    pub chains: Vec<ChainAmount>,
+}
+
+/// Maps chain specific asset ids to canonical assets
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    assets: BTreeMap<String, CanonicalAsset>,
+    /// (chain, asset id) -> (canonical asset, decimals on the chain)
+    ids: HashMap<(Chain, String), (String, u8)>,
+}
+
+impl Registry {
+    pub fn try_new(assets: &BTreeMap<String, CanonicalAsset>) -> Result<Self, AppError> {
+        let mut ids = HashMap::new();
+
+        for (key, asset) in assets {
+            for chain_asset in &asset.chains {
+                let id = (chain_asset.chain.clone(), chain_asset.asset_id.clone());
+                if let Some((other, _)) = ids.insert(id, (key.clone(), chain_asset.decimals)) {
+                    return Err(AppError::InvalidAssetRegistry(format!(
+                        "asset {} on {} belongs to both {} and {}",
+                        chain_asset.asset_id, chain_asset.chain, other, key
+                    )));
+                }
+            }
+        }
+
+        Ok(Self {
+            assets: assets.clone(),
+            ids,
+        })
+    }
+
+    /// Canonical asset of `asset_id` on the `chain` and the decimals of the chain amounts
+    pub fn lookup(&self, chain: &Chain, asset_id: &str) -> Option<(&str, u8)> {
+        self.ids
+            .get(&(chain.clone(), asset_id.to_string()))
+            .map(|(key, decimals)| (key.as_str(), *decimals))
+    }
+
+    /// Sums up the first reported kind of the registered assets of `balances`
+    /// normalized to the canonical decimals, unregistered assets are skipped
+    pub fn aggregate<'a>(
+        &self,
+        balances: impl IntoIterator<Item = (&'a Chain, &'a Balance)>,
+    ) -> Result<Vec<AggregatedAsset>, AppError> {
+        let mut aggregated: BTreeMap<&str, AggregatedAsset> = BTreeMap::new();
+
+        for (chain, balance) in balances {
+            let (key, decimals) = match self.lookup(chain, &balance.asset_id) {
+                Some(found) => found,
+                None => continue,
+            };
+            let amount = match balance.balances.first() {
+                Some(amount) => amount.amount,
+                None => continue,
+            };
+
+            let canonical_decimals = self.assets[key].decimals;
+            let overflow = || AppError::AmountOverflow(format!("{} {}", amount, key));
+            let normalized = rescale(amount as u128, decimals as u32, canonical_decimals as u32)
+                .ok_or_else(overflow)?;
+
+            let entry = aggregated.entry(key).or_insert_with(|| AggregatedAsset {
+                asset: key.to_string(),
+                amount: 0,
+                decimals: canonical_decimals,
+                chains: vec![],
+            });
+            entry.amount = entry.amount.checked_add(normalized).ok_or_else(overflow)?;
+            entry.chains.push(ChainAmount {
+                chain: chain.clone(),
+                asset_id: balance.asset_id.clone(),
+                amount,
+                decimals,
+            });
+        }
+
+        Ok(aggregated.into_values().collect())
+    }
+}
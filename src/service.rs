@@ -1,25 +1,435 @@
-use serde::Serialize;
-use std::collections::HashMap;
+use futures::stream::{BoxStream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::warn;
 
+use crate::cache::Cache;
+use crate::caip::Caip;
 use crate::error::Error as AppError;
+use crate::last_known::LastKnown;
 use crate::node_clients::Chain;
+use crate::single_flight::SingleFlight;
+use crate::valuation::Price;
+
+type ChainClient = Arc<dyn AddressBalancesService + Send + Sync>;
 
 pub struct Service {
-    chain_clients: HashMap<Chain, Box<dyn AddressBalancesService + Send + Sync>>,
+    chain_clients: HashMap<Chain, ChainClient>,
+    /// Latest block of every chain
+    blocks: HashMap<Chain, BlockWatch>,
+    caip: Caip,
+    /// Identical lookups in flight, they share a single upstream call
+    balances_flights: SingleFlight<Lookup, Vec<Balance>>,
+    pinned_flights: SingleFlight<Lookup, PinnedBalances>,
+    verified_flights: SingleFlight<Lookup, VerifiedBalances>,
+    /// Balances served while the nodes fail, see `max_staleness` of the chains
+    last_known: Arc<LastKnown>,
+    /// Lookups of the stale balances being read again
+    revalidating: Arc<Mutex<HashSet<Lookup>>>,
+    cache: Option<Arc<Cache>>,
+}
+
+/// Read of the balances of the `asset_ids` of the `address`,
+/// at the latest block if `height` is `None`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Lookup {
+    read: Read,
+    chain: Chain,
+    address: String,
+    asset_ids: Vec<String>,
+    height: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Read {
+    Native,
+    Assets,
+    All,
+    Pinned { native: bool },
+    Verified { native: bool },
+}
+
+/// Read of the balances at the latest block which does not return the block,
+/// the last known balances are served in place of these only
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Unpinned {
+    Native,
+    Assets,
+    All,
+}
+
+impl From<Unpinned> for Read {
+    fn from(read: Unpinned) -> Self {
+        match read {
+            Unpinned::Native => Self::Native,
+            Unpinned::Assets => Self::Assets,
+            Unpinned::All => Self::All,
+        }
+    }
+}
+
+impl Read {
+    /// Whether the read returns the block it is of
+    fn is_pinned(&self) -> bool {
+        matches!(self, Self::Pinned { .. } | Self::Verified { .. })
+    }
+
+    /// Whether the native balance comes first in the read balances
+    fn has_native(&self) -> bool {
+        matches!(self, Self::Native | Self::All)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Assets => "assets",
+            Self::All => "all",
+            Self::Pinned { native: false } => "pinned",
+            Self::Pinned { native: true } => "pinned-all",
+            Self::Verified { native: false } => "verified",
+            Self::Verified { native: true } => "verified-all",
+        }
+    }
+}
+
+impl Lookup {
+    fn new(read: Read, chain: &Chain, address: &str, asset_ids: &[String]) -> Self {
+        Self {
+            read,
+            chain: chain.clone(),
+            address: address.to_string(),
+            asset_ids: asset_ids.to_vec(),
+            height: None,
+        }
+    }
+
+    fn at(self, height: u64) -> Self {
+        Self {
+            height: Some(height),
+            ..self
+        }
+    }
+}
+
+/// Heights of the new blocks of a chain, polled only while they have subscribers,
+/// since every poll over HTTP is charged to the budgets of the providers
+struct BlockWatch {
+    chain_client: ChainClient,
+    sender: Arc<watch::Sender<u64>>,
+    /// Whether the poller is running, it stops once the last receiver is dropped
+    polling: Arc<Mutex<bool>>,
+    /// Latest block the reads returned, known while nobody subscribes
+    read_height: AtomicU64,
+}
+
+impl BlockWatch {
+    fn new(chain_client: ChainClient) -> Self {
+        Self {
+            chain_client,
+            sender: Arc::new(watch::channel(0).0),
+            polling: Arc::new(Mutex::new(false)),
+            read_height: AtomicU64::new(0),
+        }
+    }
+
+    /// Height of the latest block seen by the poller or the reads, `0` until the first one
+    fn latest(&self) -> u64 {
+        (*self.sender.borrow()).max(self.read_height.load(Ordering::Relaxed))
+    }
+
+    fn read_at(&self, height: u64) {
+        self.read_height.fetch_max(height, Ordering::Relaxed);
+    }
+
+    /// Subscribes to the heights, starting the poller for the first subscriber
+    fn subscribe(&self) -> watch::Receiver<u64> {
+        let mut polling = self.polling.lock().expect("block watch lock is poisoned");
+        let receiver = self.sender.subscribe();
+
+        if !*polling {
+            *polling = true;
+            tokio::spawn(poll_blocks(
+                self.chain_client.new_blocks(),
+                self.sender.clone(),
+                self.polling.clone(),
+            ));
+        }
+
+        receiver
+    }
+}
+
+async fn poll_blocks(
+    mut new_blocks: BoxStream<'static, u64>,
+    sender: Arc<watch::Sender<u64>>,
+    polling: Arc<Mutex<bool>>,
+) {
+    loop {
+        tokio::select! {
+            _ = sender.closed() => {
+                // a subscriber may have come after the last one was dropped
+                let mut polling = polling.lock().expect("block watch lock is poisoned");
+                if sender.receiver_count() == 0 {
+                    *polling = false;
+                    return;
+                }
+            }
+            height = new_blocks.next() => match height {
+                Some(height) => {
+                    sender.send_replace(height);
+                }
+                None => {
+                    *polling.lock().expect("block watch lock is poisoned") = false;
+                    return;
+                }
+            },
+        }
+    }
 }
 
 impl Service {
-    pub fn new(chain_clients: HashMap<Chain, Box<dyn AddressBalancesService + Send + Sync>>) -> Self {
+    /// Creates the service, new blocks of a chain are watched once subscribed to
+    ///
+    /// Last known balances of the chains in `max_staleness` are served up to
+    /// that age when their nodes fail
+    pub fn new(
+        chain_clients: HashMap<Chain, Box<dyn AddressBalancesService + Send + Sync>>,
+        caip: Caip,
+        max_staleness: HashMap<Chain, Duration>,
+        cache: Option<Arc<Cache>>,
+    ) -> Self {
+        let chain_clients = chain_clients
+            .into_iter()
+            .map(|(chain, chain_client)| (chain, ChainClient::from(chain_client)))
+            .collect::<HashMap<_, _>>();
+
+        let blocks = chain_clients
+            .iter()
+            .map(|(chain, chain_client)| (chain.clone(), BlockWatch::new(chain_client.clone())))
+            .collect();
+
         Self {
-            chain_clients: chain_clients
-                .into_iter()
-                .map(|(k, v)| (k, v))
-                .collect(),
+            chain_clients,
+            blocks,
+            caip,
+            balances_flights: SingleFlight::new(),
+            pinned_flights: SingleFlight::new(),
+            verified_flights: SingleFlight::new(),
+            last_known: Arc::new(LastKnown::new(max_staleness)),
+            revalidating: Arc::new(Mutex::new(HashSet::new())),
+            cache,
+        }
+    }
+
+    /// Returns the cached result of the `lookup`, or joins the identical lookup
+    /// in flight, or runs the `read` and caches its result otherwise
+    ///
+    /// Only the results of a known block are cached, under the block they were read at,
+    /// the ones at the latest block are looked up at the latest block seen
+    async fn read<T>(
+        &self,
+        flights: &SingleFlight<Lookup, T>,
+        lookup: Lookup,
+        read: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError>
+    where
+        T: Clone + Serialize + DeserializeOwned + AtBlock,
+    {
+        let cache = match &self.cache {
+            Some(cache) if lookup.height.is_some() || lookup.read.is_pinned() => cache,
+            _ => return flights.run(lookup, read).await,
+        };
+        let blocks = self.blocks.get(&lookup.chain);
+
+        let (block, ttl) = match lookup.height {
+            Some(height) => (height, cache.historical_ttl),
+            None => (blocks.map_or(0, BlockWatch::latest), cache.balances_ttl),
+        };
+        // the latest block is unknown until the first one is seen
+        if block > 0 {
+            if let Some(value) = cache.get(&balances_key(cache, &lookup, block)).await {
+                return Ok(value);
+            }
+        }
+
+        let value = flights.run(lookup.clone(), read).await?;
+        let block = match (lookup.height, value.block_number()) {
+            (Some(height), _) => height,
+            (None, Some(block)) => {
+                if let Some(blocks) = blocks {
+                    blocks.read_at(block);
+                }
+                block
+            }
+            (None, None) => return Ok(value),
+        };
+        cache
+            .set(&balances_key(cache, &lookup, block), &value, ttl)
+            .await;
+        Ok(value)
+    }
+
+    /// Records the balances read at the latest block, or falls back to the last known
+    /// ones if the node is unavailable and reads them again in the background
+    fn or_last_known(
+        &self,
+        read: Unpinned,
+        lookup: Lookup,
+        result: Result<Vec<Balance>, AppError>,
+    ) -> Result<Vec<Balance>, AppError> {
+        if !self.last_known.is_enabled(&lookup.chain) {
+            return result;
+        }
+
+        match result {
+            Ok(balances) => {
+                self.last_known.record(
+                    &lookup.chain,
+                    &lookup.address,
+                    lookup.read.has_native(),
+                    &balances,
+                );
+                Ok(balances)
+            }
+            Err(e) if is_unavailable(&e) => {
+                let stale = self.last_known.get(
+                    &lookup.chain,
+                    &lookup.address,
+                    lookup.read.has_native(),
+                    &lookup.asset_ids,
+                );
+                match stale {
+                    Some(stale) => {
+                        warn!(
+                            "serving last known {} balances of {}: {}",
+                            lookup.chain, lookup.address, e
+                        );
+                        self.revalidate(read, lookup);
+                        Ok(stale)
+                    }
+                    None => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads the balances of the `lookup` in the background to replace the stale ones,
+    /// once at a time per lookup
+    fn revalidate(&self, read: Unpinned, lookup: Lookup) {
+        let chain_client = match self.chain_clients.get(&lookup.chain) {
+            Some(chain_client) => chain_client.clone(),
+            None => return,
+        };
+
+        let mut revalidating = self
+            .revalidating
+            .lock()
+            .expect("revalidating lock is poisoned");
+        if !revalidating.insert(lookup.clone()) {
+            return;
         }
+        drop(revalidating);
+
+        let last_known = self.last_known.clone();
+        let revalidating = self.revalidating.clone();
+        tokio::spawn(async move {
+            let address = lookup.address.clone();
+            let asset_ids = lookup.asset_ids.clone();
+            let result = match read {
+                Unpinned::Native => chain_client.get_balance(address).await.map(|b| vec![b]),
+                Unpinned::Assets => chain_client.get_assets_balances(address, asset_ids).await,
+                Unpinned::All => chain_client.get_all_balances(address, asset_ids).await,
+            };
+
+            match result {
+                Ok(balances) => last_known.record(
+                    &lookup.chain,
+                    &lookup.address,
+                    lookup.read.has_native(),
+                    &balances,
+                ),
+                Err(e) => warn!(
+                    "failed to revalidate {} balances of {}: {}",
+                    lookup.chain, lookup.address, e
+                ),
+            }
+
+            revalidating
+                .lock()
+                .expect("revalidating lock is poisoned")
+                .remove(&lookup);
+        });
+    }
+
+    /// Sets CAIP-19 ids of the balances read from the `chain`
+    fn with_caip_ids(&self, chain: &Chain, mut balances: Vec<Balance>) -> Vec<Balance> {
+        for balance in &mut balances {
+            balance.caip_asset_id = self.caip.asset_id(chain, &balance.asset_id);
+        }
+        balances
+    }
+
+    fn with_caip_id(&self, chain: &Chain, balance: Balance) -> Balance {
+        self.with_caip_ids(chain, vec![balance])
+            .pop()
+            .expect("single balance")
     }
 }
 
-#[derive(Clone, Debug, Serialize)]
+fn balances_key(cache: &Cache, lookup: &Lookup, block: u64) -> String {
+    let key = format!(
+        "{}:{}:{}",
+        lookup.address,
+        lookup.read.name(),
+        lookup.asset_ids.join(",")
+    );
+    cache.balances_key(&lookup.chain, block, &key)
+}
+
+/// Block the result of a read is of, if the read tells it
+trait AtBlock {
+    fn block_number(&self) -> Option<u64>;
+}
+
+impl AtBlock for Vec<Balance> {
+    fn block_number(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl AtBlock for PinnedBalances {
+    fn block_number(&self) -> Option<u64> {
+        Some(self.block.block_number)
+    }
+}
+
+impl AtBlock for VerifiedBalances {
+    fn block_number(&self) -> Option<u64> {
+        Some(self.block.block_number)
+    }
+}
+
+/// Whether the node could not answer, rather than answered with an error
+fn is_unavailable(e: &AppError) -> bool {
+    match e {
+        AppError::Upstream(_)
+        | AppError::EthersProvider(_)
+        | AppError::UpstreamTimeout(_)
+        | AppError::CircuitOpen(_)
+        | AppError::RateLimited(_)
+        | AppError::Saturated(_) => true,
+        AppError::TokenBalances(failures) => failures.iter().any(|(_, e)| is_unavailable(e)),
+        _ => false,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum BalanceKind {
     /// Common
@@ -28,9 +438,14 @@ pub enum BalanceKind {
     /// Only for WAVES
     Available,
     Effective,
+
+    /// Only for Substrate
+    Free,
+    Reserved,
+    Frozen,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct BalanceAmount {
     pub kind: BalanceKind,
     pub amount: u64,
@@ -45,21 +460,88 @@ impl BalanceAmount {
     }
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Balance {
     pub asset_id: String,
+    /// CAIP-19 id of the asset, if the chain has CAIP identifiers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caip_asset_id: Option<String>,
     pub balances: Vec<BalanceAmount>,
+    /// Present if the balance is the last known one, served while the node fails
+    #[serde(default, flatten, skip_serializing_if = "Option::is_none")]
+    pub staleness: Option<Staleness>,
 }
 
 impl Balance {
     pub fn single(asset_id: impl AsRef<str>, kind: &BalanceKind, amount: u64) -> Self {
         Self {
             asset_id: asset_id.as_ref().to_string(),
+            caip_asset_id: None,
             balances: vec![BalanceAmount::new(kind, amount)],
+            staleness: None,
         }
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Staleness {
+    pub stale: bool,
+    /// Seconds since the balance was read
+    pub age: u64,
+}
+
+/// Block balances were read at
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BlockRef {
+    pub block_number: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<String>,
+    /// Unix time in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_timestamp: Option<u64>,
+}
+
+/// Balances read at the same block
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PinnedBalances {
+    pub block: BlockRef,
+    pub balances: Vec<Balance>,
+}
+
+/// Balance proven by a Merkle-Patricia proof against the state root of the block
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VerifiedBalance {
+    #[serde(flatten)]
+    pub balance: Balance,
+    /// `false` if the balance could not be proven, e.g. of a token with unknown storage layout
+    pub verified: bool,
+}
+
+/// Balances read at the same block with their proofs checked
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VerifiedBalances {
+    pub block: BlockRef,
+    pub balances: Vec<VerifiedBalance>,
+}
+
+/// Amount of the `kind` balance of `asset_id`, of the first reported kind if `None`
+pub fn find_amount(
+    balances: &[Balance],
+    asset_id: &str,
+    kind: Option<&BalanceKind>,
+) -> Option<u64> {
+    let balance = balances.iter().find(|b| b.asset_id == asset_id)?;
+
+    match kind {
+        Some(kind) => balance
+            .balances
+            .iter()
+            .find(|amount| &amount.kind == kind)
+            .map(|amount| amount.amount),
+        None => balance.balances.first().map(|amount| amount.amount),
+    }
+}
+
 #[async_trait::async_trait]
 pub trait BalancesService {
     /// Checks whether provided `asset_ids` are supported by the service
@@ -79,6 +561,70 @@ pub trait BalancesService {
         address: String,
         asset_ids: Vec<String>,
     ) -> Result<Vec<Balance>, AppError>;
+
+    /// Reads the native balance followed by balances of `asset_ids` at the same block
+    async fn get_all_balances(
+        &self,
+        chain: Chain,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError>;
+
+    /// Reads the native balance, if `native`, followed by balances of `asset_ids`
+    /// at the latest block and returns the block
+    async fn get_pinned_balances(
+        &self,
+        chain: Chain,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError>;
+
+    /// Same as `get_pinned_balances`, but proves the balances by the state root of the block
+    async fn get_verified_balances(
+        &self,
+        chain: Chain,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<VerifiedBalances, AppError>;
+
+    /// Subscribes to the height of the latest block of the `chain`
+    fn subscribe_blocks(&self, chain: Chain) -> Result<watch::Receiver<u64>, AppError>;
+
+    /// Whether balances at past blocks of the `chain` are available
+    fn supports_balances_at(&self, chain: Chain) -> Result<bool, AppError>;
+
+    /// Block of the `chain` at `height`, the latest one if `None`
+    async fn get_block(&self, chain: Chain, height: Option<u64>) -> Result<BlockRef, AppError>;
+
+    /// Native balance at the `block`, which is resolved once by `get_block`
+    /// for the reads of many addresses
+    async fn get_balance_at(
+        &self,
+        chain: Chain,
+        address: String,
+        block: BlockRef,
+    ) -> Result<Balance, AppError>;
+
+    async fn get_assets_balances_at(
+        &self,
+        chain: Chain,
+        address: String,
+        asset_ids: Vec<String>,
+        block: BlockRef,
+    ) -> Result<Vec<Balance>, AppError>;
+
+    /// Prices of `asset_ids` read from the oracles of the `chain`,
+    /// assets without a configured oracle are omitted
+    async fn get_prices(
+        &self,
+        chain: Chain,
+        asset_ids: Vec<String>,
+    ) -> Result<HashMap<String, Price>, AppError>;
+
+    /// CAIP identifiers of the configured chains
+    fn caip(&self) -> &Caip;
 }
 
 #[async_trait::async_trait]
@@ -108,7 +654,15 @@ impl BalancesService for Service {
             .get(&chain)
             .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
 
-        chain_client.get_balance(address).await
+        let lookup = Lookup::new(Unpinned::Native.into(), &chain, &address, &[]);
+        let result = self
+            .read(&self.balances_flights, lookup.clone(), async {
+                chain_client.get_balance(address).await.map(|b| vec![b])
+            })
+            .await;
+        let balances = self.or_last_known(Unpinned::Native, lookup, result)?;
+
+        Ok(self.with_caip_ids(&chain, balances).remove(0))
     }
 
     async fn get_assets_balances(
@@ -122,7 +676,207 @@ impl BalancesService for Service {
             .get(&chain)
             .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
 
-        chain_client.get_assets_balances(address, asset_ids).await
+        let lookup = Lookup::new(Unpinned::Assets.into(), &chain, &address, &asset_ids);
+        let result = self
+            .read(
+                &self.balances_flights,
+                lookup.clone(),
+                chain_client.get_assets_balances(address, asset_ids),
+            )
+            .await;
+        let balances = self.or_last_known(Unpinned::Assets, lookup, result)?;
+
+        Ok(self.with_caip_ids(&chain, balances))
+    }
+
+    async fn get_all_balances(
+        &self,
+        chain: Chain,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        let chain_client = self
+            .chain_clients
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        let lookup = Lookup::new(Unpinned::All.into(), &chain, &address, &asset_ids);
+        let result = self
+            .read(
+                &self.balances_flights,
+                lookup.clone(),
+                chain_client.get_all_balances(address, asset_ids),
+            )
+            .await;
+        let balances = self.or_last_known(Unpinned::All, lookup, result)?;
+
+        Ok(self.with_caip_ids(&chain, balances))
+    }
+
+    async fn get_pinned_balances(
+        &self,
+        chain: Chain,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError> {
+        let chain_client = self
+            .chain_clients
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        let lookup = Lookup::new(Read::Pinned { native }, &chain, &address, &asset_ids);
+        let pinned = self
+            .read(
+                &self.pinned_flights,
+                lookup,
+                chain_client.get_pinned_balances(address, asset_ids, native),
+            )
+            .await?;
+
+        Ok(PinnedBalances {
+            balances: self.with_caip_ids(&chain, pinned.balances),
+            ..pinned
+        })
+    }
+
+    async fn get_verified_balances(
+        &self,
+        chain: Chain,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<VerifiedBalances, AppError> {
+        let chain_client = self
+            .chain_clients
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        let lookup = Lookup::new(Read::Verified { native }, &chain, &address, &asset_ids);
+        let verified = self
+            .read(
+                &self.verified_flights,
+                lookup,
+                chain_client.get_verified_balances(address, asset_ids, native),
+            )
+            .await?;
+
+        let balances = verified
+            .balances
+            .into_iter()
+            .map(|verified| VerifiedBalance {
+                balance: self.with_caip_id(&chain, verified.balance),
+                ..verified
+            })
+            .collect();
+
+        Ok(VerifiedBalances {
+            balances,
+            ..verified
+        })
+    }
+
+    fn subscribe_blocks(&self, chain: Chain) -> Result<watch::Receiver<u64>, AppError> {
+        let blocks = self
+            .blocks
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        Ok(blocks.subscribe())
+    }
+
+    fn supports_balances_at(&self, chain: Chain) -> Result<bool, AppError> {
+        let chain_client = self
+            .chain_clients
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        Ok(chain_client.supports_balances_at())
+    }
+
+    async fn get_block(&self, chain: Chain, height: Option<u64>) -> Result<BlockRef, AppError> {
+        let chain_client = self
+            .chain_clients
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        chain_client.get_block(height).await
+    }
+
+    async fn get_balance_at(
+        &self,
+        chain: Chain,
+        address: String,
+        block: BlockRef,
+    ) -> Result<Balance, AppError> {
+        let chain_client = self
+            .chain_clients
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        let lookup = Lookup::new(Read::Native, &chain, &address, &[]).at(block.block_number);
+        let balances = self
+            .read(&self.balances_flights, lookup, async {
+                chain_client
+                    .get_balance_at(address, block)
+                    .await
+                    .map(|b| vec![b])
+            })
+            .await?;
+
+        Ok(self.with_caip_ids(&chain, balances).remove(0))
+    }
+
+    async fn get_assets_balances_at(
+        &self,
+        chain: Chain,
+        address: String,
+        asset_ids: Vec<String>,
+        block: BlockRef,
+    ) -> Result<Vec<Balance>, AppError> {
+        let chain_client = self
+            .chain_clients
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        let lookup = Lookup::new(Read::Assets, &chain, &address, &asset_ids).at(block.block_number);
+        let balances = self
+            .read(
+                &self.balances_flights,
+                lookup,
+                chain_client.get_assets_balances_at(address, asset_ids, block),
+            )
+            .await?;
+
+        Ok(self.with_caip_ids(&chain, balances))
+    }
+
+    async fn get_prices(
+        &self,
+        chain: Chain,
+        asset_ids: Vec<String>,
+    ) -> Result<HashMap<String, Price>, AppError> {
+        let chain_client = self
+            .chain_clients
+            .get(&chain)
+            .ok_or(AppError::NodeClientWasNotProvided(chain.clone().into()))?;
+
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return chain_client.get_prices(asset_ids).await,
+        };
+
+        let key = cache.prices_key(&chain, &asset_ids);
+        if let Some(prices) = cache.get(&key).await {
+            return Ok(prices);
+        }
+        let prices = chain_client.get_prices(asset_ids).await?;
+        cache.set(&key, &prices, cache.prices_ttl).await;
+        Ok(prices)
+    }
+
+    fn caip(&self) -> &Caip {
+        &self.caip
     }
 }
 
@@ -130,6 +884,9 @@ impl BalancesService for Service {
 pub trait AddressBalancesService {
     fn is_asset_supported(&self, asset_id: String) -> bool;
 
+    /// Stream of heights of new blocks observed by the node
+    fn new_blocks(&self) -> BoxStream<'static, u64>;
+
     async fn get_balance(&self, address: String) -> Result<Balance, AppError>;
 
     async fn get_assets_balances(
@@ -137,4 +894,79 @@ pub trait AddressBalancesService {
         address: String,
         asset_ids: Vec<String>,
     ) -> Result<Vec<Balance>, AppError>;
+
+    /// Native balance followed by balances of `asset_ids`, read concurrently
+    /// unless the node client can read them at the same block
+    async fn get_all_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+    ) -> Result<Vec<Balance>, AppError> {
+        let native = self.get_balance(address.clone());
+
+        if asset_ids.is_empty() {
+            return native.await.map(|balance| vec![balance]);
+        }
+
+        let assets = self.get_assets_balances(address, asset_ids);
+
+        let (native, assets) = futures::try_join!(native, assets)?;
+
+        Ok(std::iter::once(native).chain(assets).collect())
+    }
+
+    /// Native balance, if `native`, followed by balances of `asset_ids`
+    /// read at the same latest block
+    async fn get_pinned_balances(
+        &self,
+        address: String,
+        asset_ids: Vec<String>,
+        native: bool,
+    ) -> Result<PinnedBalances, AppError>;
+
+    /// Balances read at the latest block, proven by the state root of the block
+    async fn get_verified_balances(
+        &self,
+        _address: String,
+        _asset_ids: Vec<String>,
+        _native: bool,
+    ) -> Result<VerifiedBalances, AppError> {
+        Err(AppError::VerificationNotSupported)
+    }
+
+    /// Block at `height`, the latest one if `None`
+    async fn get_block(&self, _height: Option<u64>) -> Result<BlockRef, AppError> {
+        Err(AppError::BalancesAtBlockNotSupported)
+    }
+
+    /// Whether the node serves balances at past blocks
+    fn supports_balances_at(&self) -> bool {
+        false
+    }
+
+    /// Native balance at the `block`, its hash is resolved by the height if absent
+    async fn get_balance_at(
+        &self,
+        _address: String,
+        _block: BlockRef,
+    ) -> Result<Balance, AppError> {
+        Err(AppError::BalancesAtBlockNotSupported)
+    }
+
+    async fn get_assets_balances_at(
+        &self,
+        _address: String,
+        _asset_ids: Vec<String>,
+        _block: BlockRef,
+    ) -> Result<Vec<Balance>, AppError> {
+        Err(AppError::BalancesAtBlockNotSupported)
+    }
+
+    /// Prices of the `asset_ids` which have a price oracle configured
+    async fn get_prices(
+        &self,
+        _asset_ids: Vec<String>,
+    ) -> Result<HashMap<String, Price>, AppError> {
+        Ok(HashMap::new())
+    }
 }
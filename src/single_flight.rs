@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+use crate::error::Error as AppError;
+
+type Outcome<V> = Option<Result<V, AppError>>;
+
+/// Runs concurrent calls with the same key once, the callers arriving while
+/// the call is in flight share its result
+pub struct SingleFlight<K, V> {
+    calls: Mutex<HashMap<K, watch::Receiver<Outcome<V>>>>,
+}
+
+/// Ends the flight when the leading call is done or cancelled
+struct Flight<'a, K: Eq + Hash, V> {
+    calls: &'a Mutex<HashMap<K, watch::Receiver<Outcome<V>>>>,
+    key: K,
+}
+
+impl<K: Eq + Hash, V> Drop for Flight<'_, K, V> {
+    fn drop(&mut self) {
+        self.calls
+            .lock()
+            .expect("single flight lock is poisoned")
+            .remove(&self.key);
+    }
+}
+
+impl<K, V> SingleFlight<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    pub fn new() -> Self {
+        Self {
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Joins the call in flight with the `key`, or runs the `call` otherwise
+    pub async fn run(
+        &self,
+        key: K,
+        call: impl Future<Output = Result<V, AppError>>,
+    ) -> Result<V, AppError> {
+        let joined = {
+            let mut calls = self.calls.lock().expect("single flight lock is poisoned");
+            match calls.get(&key) {
+                Some(outcome) => Err(outcome.clone()),
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    calls.insert(key.clone(), rx);
+                    Ok(tx)
+                }
+            }
+        };
+
+        match joined {
+            Ok(tx) => {
+                let _flight = Flight {
+                    calls: &self.calls,
+                    key,
+                };
+                let result = call.await;
+                tx.send_replace(Some(result.clone()));
+                result
+            }
+            Err(mut outcome) => loop {
+                if let Some(result) = outcome.borrow().clone() {
+                    return result;
+                }
+                // the leading call was cancelled, e.g. its client disconnected
+                if outcome.changed().await.is_err() {
+                    return call.await;
+                }
+            },
+        }
+    }
+}
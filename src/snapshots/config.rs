@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::config::redact_url;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// `sqlite://` or, with the `postgres` feature, `postgres://` connection url
+    pub database_url: String,
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+    /// Maximum number of addresses of a single snapshot
+    #[serde(default = "default_max_addresses")]
+    pub max_addresses: usize,
+    /// Maximum number of queued and running jobs
+    #[serde(default = "default_max_pending_jobs")]
+    pub max_pending_jobs: usize,
+    /// Number of addresses read concurrently
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Maximum number of addresses read per second
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: u32,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("database_url", &redact_url(&self.database_url))
+            .field("max_connections", &self.max_connections)
+            .field("max_addresses", &self.max_addresses)
+            .field("max_pending_jobs", &self.max_pending_jobs)
+            .field("concurrency", &self.concurrency)
+            .field("requests_per_second", &self.requests_per_second)
+            .finish()
+    }
+}
+
+fn default_max_connections() -> u32 {
+    5
+}
+
+fn default_max_addresses() -> usize {
+    100_000
+}
+
+fn default_max_pending_jobs() -> usize {
+    16
+}
+
+fn default_concurrency() -> usize {
+    8
+}
+
+fn default_requests_per_second() -> u32 {
+    20
+}
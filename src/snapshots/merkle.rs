@@ -0,0 +1,243 @@
+use ethabi::Token;
+use ethers_core::types::{Address, U256};
+use ethers_core::utils::keccak256;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use super::Entry;
+use crate::error::Error as AppError;
+use crate::service::{find_amount, BalanceKind};
+
+/// How an (address, amount) pair is hashed into a leaf
+/// Trees kept by the cache of the recently requested ones
+const CACHED_TREES: usize = 16;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeafEncoding {
+    /// `keccak256(bytes.concat(keccak256(abi.encode(account, amount))))` as of
+    /// OpenZeppelin `StandardMerkleTree`
+    #[default]
+    Standard,
+    /// `keccak256(abi.encodePacked(account, amount))`
+    Packed,
+}
+
+impl LeafEncoding {
+    pub fn leaf(&self, address: Address, amount: U256) -> [u8; 32] {
+        match self {
+            Self::Standard => keccak256(keccak256(ethabi::encode(&[
+                Token::Address(address),
+                Token::Uint(amount),
+            ]))),
+            Self::Packed => {
+                let mut amount_bytes = [0u8; 32];
+                amount.to_big_endian(&mut amount_bytes);
+                keccak256([address.as_bytes(), &amount_bytes[..]].concat())
+            }
+        }
+    }
+}
+
+/// Merkle tree built as OpenZeppelin `StandardMerkleTree` does, so its root and proofs
+/// are the ones of `StandardMerkleTree.of` with the same leaves and verifiable with `MerkleProof`
+pub struct MerkleTree {
+    /// Nodes in the array representation, the root first and the leaves sorted by hash
+    /// at the end in reverse order, children of the node `i` are at `2i + 1` and `2i + 2`
+    nodes: Vec<[u8; 32]>,
+    /// Leaves sorted by hash
+    leaves: Vec<[u8; 32]>,
+}
+
+impl MerkleTree {
+    pub fn new(mut leaves: Vec<[u8; 32]>) -> Self {
+        leaves.sort_unstable();
+
+        let len = (2 * leaves.len()).saturating_sub(1);
+        let mut nodes = vec![[0u8; 32]; len];
+        for (i, leaf) in leaves.iter().enumerate() {
+            nodes[len - 1 - i] = *leaf;
+        }
+        for i in (0..len.saturating_sub(leaves.len())).rev() {
+            nodes[i] = hash_pair(&nodes[2 * i + 1], &nodes[2 * i + 2]);
+        }
+
+        Self { nodes, leaves }
+    }
+
+    /// Root of the tree, zero hash if there are no leaves
+    pub fn root(&self) -> [u8; 32] {
+        self.nodes.first().copied().unwrap_or_default()
+    }
+
+    pub fn proof(&self, leaf: &[u8; 32]) -> Option<Vec<[u8; 32]>> {
+        let position = self.leaves.binary_search(leaf).ok()?;
+        let mut idx = self.nodes.len() - 1 - position;
+
+        let mut proof = vec![];
+        while idx > 0 {
+            let sibling = if idx % 2 == 1 { idx + 1 } else { idx - 1 };
+            proof.push(self.nodes[sibling]);
+            idx = (idx - 1) / 2;
+        }
+
+        Some(proof)
+    }
+}
+
+/// Parameters the tree of a snapshot is built with
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TreeKey {
+    pub snapshot_id: String,
+    pub asset_id: Option<String>,
+    pub kind: Option<BalanceKind>,
+    pub encoding: LeafEncoding,
+}
+
+/// Claims of a completed snapshot and their tree
+pub struct ClaimsTree {
+    pub asset_id: String,
+    pub claims: Vec<Claim>,
+    pub tree: MerkleTree,
+}
+
+/// Trees of the recently requested snapshots, completed snapshots do not change,
+/// so the tree is not built again for the proof of every claim
+#[derive(Default)]
+pub struct TreeCache {
+    trees: Mutex<HashMap<TreeKey, Arc<ClaimsTree>>>,
+}
+
+impl TreeCache {
+    pub fn get(&self, key: &TreeKey) -> Option<Arc<ClaimsTree>> {
+        let trees = self.trees.lock().expect("tree cache lock is poisoned");
+        trees.get(key).cloned()
+    }
+
+    /// Caches the `tree`, evicting another one if the cache is full
+    pub fn insert(&self, key: TreeKey, tree: ClaimsTree) -> Arc<ClaimsTree> {
+        let tree = Arc::new(tree);
+        let mut trees = self.trees.lock().expect("tree cache lock is poisoned");
+
+        if trees.len() >= CACHED_TREES && !trees.contains_key(&key) {
+            let evicted = trees.keys().next().cloned();
+            if let Some(evicted) = evicted {
+                trees.remove(&evicted);
+            }
+        }
+        trees.insert(key, tree.clone());
+        tree
+    }
+}
+
+fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    if a <= b {
+        keccak256([&a[..], &b[..]].concat())
+    } else {
+        keccak256([&b[..], &a[..]].concat())
+    }
+}
+
+/// Amount of an asset held by an address in a snapshot
+#[derive(Clone, Debug)]
+pub struct Claim {
+    pub address: Address,
+    pub amount: u64,
+    pub leaf: [u8; 32],
+}
+
+/// Takes the `kind` (the first reported one if `None`) balance of `asset_id`
+/// of every address with a non-zero amount
+///
+/// Addresses occurring several times in the snapshot are taken once
+pub fn claims(
+    entries: &[Entry],
+    asset_id: &str,
+    kind: Option<&BalanceKind>,
+    encoding: &LeafEncoding,
+) -> Result<Vec<Claim>, AppError> {
+    let mut claims: Vec<Claim> = Vec::with_capacity(entries.len());
+    let mut seen = std::collections::HashSet::new();
+
+    for entry in entries {
+        let amount = entry
+            .balances
+            .as_deref()
+            .and_then(|balances| find_amount(balances, asset_id, kind))
+            .unwrap_or_default();
+
+        if amount == 0 {
+            continue;
+        }
+
+        let address = Address::from_str(&entry.address)
+            .map_err(|_| AppError::InvalidAddress(entry.address.clone()))?;
+        if !seen.insert(address) {
+            continue;
+        }
+
+        claims.push(Claim {
+            address,
+            amount,
+            leaf: encoding.leaf(address, U256::from(amount)),
+        });
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: u8) -> Vec<[u8; 32]> {
+        (1..=count).map(|i| keccak256([i])).collect()
+    }
+
+    /// Root as of `MerkleProof.processProof`
+    fn process_proof(leaf: &[u8; 32], proof: &[[u8; 32]]) -> [u8; 32] {
+        proof
+            .iter()
+            .fold(*leaf, |node, sibling| hash_pair(&node, sibling))
+    }
+
+    #[test]
+    fn builds_tree_of_standard_merkle_tree() {
+        let mut sorted = leaves(5);
+        sorted.sort_unstable();
+        let [l0, l1, l2, l3, l4] = sorted[..] else {
+            unreachable!()
+        };
+
+        // the leaves are at the nodes 8 to 4 in the array of 9 nodes
+        let n3 = hash_pair(&l1, &l0);
+        let n2 = hash_pair(&l3, &l2);
+        let n1 = hash_pair(&n3, &l4);
+        let root = hash_pair(&n1, &n2);
+
+        let tree = MerkleTree::new(leaves(5));
+        assert_eq!(tree.root(), root);
+        assert_eq!(tree.proof(&l4), Some(vec![n3, n2]));
+        assert_eq!(tree.proof(&l0), Some(vec![l1, l4, n2]));
+    }
+
+    #[test]
+    fn proves_every_leaf() {
+        for count in 1..=9 {
+            let tree = MerkleTree::new(leaves(count));
+            for leaf in leaves(count) {
+                let proof = tree.proof(&leaf).unwrap();
+                assert_eq!(process_proof(&leaf, &proof), tree.root());
+            }
+        }
+    }
+
+    #[test]
+    fn has_zero_root_without_leaves() {
+        let tree = MerkleTree::new(vec![]);
+        assert_eq!(tree.root(), [0u8; 32]);
+        assert_eq!(tree.proof(&[1u8; 32]), None);
+    }
+}
@@ -0,0 +1,81 @@
+mod config;
+pub mod merkle;
+mod runner;
+mod store;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+use crate::service::Balance;
+
+pub use config::Config;
+pub use runner::Snapshots;
+pub use store::{SnapshotStore, SqlSnapshotStore};
+
+/// What to read: balances of the `addresses` at the block of `height`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Spec {
+    pub chain: Chain,
+    /// Whether to read balances of the native token
+    #[serde(default)]
+    pub native: bool,
+    #[serde(default)]
+    pub asset_ids: Vec<String>,
+    pub addresses: Vec<String>,
+    /// Balances of every address are read at its latest block while the job runs
+    /// if `None`, which is the case of the chains without balances at past blocks
+    #[serde(default, alias = "block")]
+    pub height: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Snapshot job with the progress of reading its addresses
+#[derive(Clone, Debug, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub chain: Chain,
+    pub native: bool,
+    pub asset_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+    /// Whether all the balances are read at the block of `height`, the ones of
+    /// different addresses may be of different blocks otherwise
+    pub block_consistent: bool,
+    pub status: JobStatus,
+    pub total: u64,
+    pub processed: u64,
+    pub failed: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Balances of a single address, or the reason they could not be read
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balances: Option<Vec<Balance>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Entry {
+    pub fn failed(address: String, error: AppError) -> Self {
+        Self {
+            address,
+            balances: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
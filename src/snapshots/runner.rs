@@ -0,0 +1,416 @@
+use futures::stream::{self, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+
+use super::merkle::TreeCache;
+use super::{Config, Entry, Job, JobStatus, SnapshotStore, Spec};
+use crate::error::Error as AppError;
+use crate::service::{Balance, BalancesService, BlockRef};
+
+/// Number of entries stored at once
+const BATCH_SIZE: usize = 100;
+
+/// Accepts snapshot jobs and runs them one by one in background
+pub struct Snapshots {
+    config: Config,
+    store: Arc<dyn SnapshotStore + Send + Sync>,
+    queue: mpsc::UnboundedSender<String>,
+    trees: TreeCache,
+}
+
+impl Snapshots {
+    /// Starts the background runner, resuming jobs interrupted by the previous shutdown
+    pub async fn start(
+        config: &Config,
+        service: Arc<Box<dyn BalancesService + Send + Sync>>,
+        store: Arc<dyn SnapshotStore + Send + Sync>,
+    ) -> Result<Self, AppError> {
+        let (queue, jobs) = mpsc::unbounded_channel();
+
+        let pending = store.pending().await?;
+        if !pending.is_empty() {
+            info!("resuming {} snapshot jobs", pending.len());
+        }
+        for id in pending {
+            queue
+                .send(id)
+                .expect("snapshot jobs receiver is not dropped yet");
+        }
+
+        tokio::spawn(run(config.clone(), service, store.clone(), jobs));
+
+        Ok(Self {
+            config: config.clone(),
+            store,
+            queue,
+            trees: TreeCache::default(),
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Merkle trees of the completed snapshots
+    pub fn trees(&self) -> &TreeCache {
+        &self.trees
+    }
+
+    pub async fn submit(&self, spec: Spec) -> Result<Job, AppError> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let job = self.store.insert(&id, &spec).await?;
+
+        self.queue
+            .send(id)
+            .map_err(|_| AppError::Upstream("snapshot runner is stopped".to_string()))?;
+
+        Ok(job)
+    }
+
+    /// Number of queued and running jobs
+    pub async fn pending(&self) -> Result<usize, AppError> {
+        self.store.pending().await.map(|pending| pending.len())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Job>, AppError> {
+        self.store.get(id).await
+    }
+
+    pub async fn entries(&self, id: &str) -> Result<Vec<Entry>, AppError> {
+        self.store.entries(id).await
+    }
+}
+
+async fn run(
+    config: Config,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    store: Arc<dyn SnapshotStore + Send + Sync>,
+    mut jobs: mpsc::UnboundedReceiver<String>,
+) {
+    while let Some(id) = jobs.recv().await {
+        let (status, error) = match run_job(&config, &service, store.as_ref(), &id).await {
+            Ok(()) => (JobStatus::Completed, None),
+            Err(e) => {
+                error!("snapshot job {} failed: {}", id, e);
+                (JobStatus::Failed, Some(e.to_string()))
+            }
+        };
+
+        if let Err(e) = store.set_status(&id, status, error).await {
+            error!("{}", e);
+        }
+    }
+}
+
+async fn run_job(
+    config: &Config,
+    service: &Arc<Box<dyn BalancesService + Send + Sync>>,
+    store: &(dyn SnapshotStore + Send + Sync),
+    id: &str,
+) -> Result<(), AppError> {
+    let spec = match store.spec(id).await? {
+        Some(spec) => spec,
+        None => return Ok(()),
+    };
+
+    store.set_status(id, JobStatus::Running, None).await?;
+
+    // the block is resolved once for the reads of all the addresses
+    let block = match spec.height {
+        Some(height) => Some(service.get_block(spec.chain.clone(), Some(height)).await?),
+        None => None,
+    };
+
+    // addresses read before the restart are skipped
+    let processed = store.processed(id).await?;
+    let pending = spec
+        .addresses
+        .iter()
+        .enumerate()
+        .map(|(idx, address)| (idx as u64, address.clone()))
+        .filter(|(idx, _)| !processed.contains(idx))
+        .collect::<Vec<_>>();
+
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(
+        1.0 / f64::from(config.requests_per_second.max(1)),
+    ));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let ticks = stream::unfold(interval, |mut interval| async move {
+        interval.tick().await;
+        Some(((), interval))
+    })
+    .boxed();
+
+    let mut batches = stream::iter(pending)
+        .zip(ticks)
+        .map(|((idx, address), ())| {
+            let (spec, block) = (&spec, &block);
+            async move {
+                let entry = read(service.as_ref().as_ref(), spec, block.as_ref(), address).await;
+                (idx, entry)
+            }
+        })
+        .buffer_unordered(config.concurrency.max(1))
+        .ready_chunks(BATCH_SIZE);
+
+    while let Some(entries) = batches.next().await {
+        store.record(id, &entries).await?;
+    }
+
+    Ok(())
+}
+
+async fn read(
+    service: &(dyn BalancesService + Send + Sync),
+    spec: &Spec,
+    block: Option<&BlockRef>,
+    address: String,
+) -> Entry {
+    let balances = match block {
+        Some(block) => read_at(service, spec, block, &address).await,
+        // every address is read at its latest block, pinned reads are never
+        // served from the last known balances
+        None => service
+            .get_pinned_balances(
+                spec.chain.clone(),
+                address.clone(),
+                spec.asset_ids.clone(),
+                spec.native,
+            )
+            .await
+            .map(|pinned| pinned.balances),
+    };
+
+    match balances {
+        Ok(balances) => Entry {
+            address,
+            balances: Some(balances),
+            error: None,
+        },
+        Err(e) => Entry::failed(address, e),
+    }
+}
+
+async fn read_at(
+    service: &(dyn BalancesService + Send + Sync),
+    spec: &Spec,
+    block: &BlockRef,
+    address: &str,
+) -> Result<Vec<Balance>, AppError> {
+    let mut balances = vec![];
+
+    if spec.native {
+        let balance = service
+            .get_balance_at(spec.chain.clone(), address.to_string(), block.clone())
+            .await?;
+        balances.push(balance);
+    }
+
+    if !spec.asset_ids.is_empty() {
+        let assets_balances = service
+            .get_assets_balances_at(
+                spec.chain.clone(),
+                address.to_string(),
+                spec.asset_ids.clone(),
+                block.clone(),
+            )
+            .await?;
+        balances.extend(assets_balances);
+    }
+
+    Ok(balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream::BoxStream;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::caip::Caip;
+    use crate::node_clients::Chain;
+    use crate::service::{AddressBalancesService, BalanceKind, PinnedBalances, Service};
+    use crate::snapshots::SqlSnapshotStore;
+
+    const TOKEN: &str = "0x00000000000000000000000000000000000000bb";
+
+    /// Node whose `whale` address holds more than `u64::MAX`
+    struct Node {
+        balances_at: bool,
+    }
+
+    fn amount(address: &str) -> Result<u64, AppError> {
+        match address {
+            "whale" => Err(AppError::AmountOverflow("18446744073709551616".to_string())),
+            address => Ok(address.len() as u64),
+        }
+    }
+
+    fn block(height: u64) -> BlockRef {
+        BlockRef {
+            block_number: height,
+            block_hash: Some(format!("0x{:064x}", height)),
+            block_timestamp: None,
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AddressBalancesService for Node {
+        fn is_asset_supported(&self, _asset_id: String) -> bool {
+            true
+        }
+
+        fn new_blocks(&self) -> BoxStream<'static, u64> {
+            stream::empty().boxed()
+        }
+
+        async fn get_balance(&self, _address: String) -> Result<Balance, AppError> {
+            Err(AppError::Upstream("latest reads may be stale".to_string()))
+        }
+
+        async fn get_assets_balances(
+            &self,
+            _address: String,
+            _asset_ids: Vec<String>,
+        ) -> Result<Vec<Balance>, AppError> {
+            Err(AppError::Upstream("latest reads may be stale".to_string()))
+        }
+
+        async fn get_pinned_balances(
+            &self,
+            address: String,
+            asset_ids: Vec<String>,
+            native: bool,
+        ) -> Result<PinnedBalances, AppError> {
+            let amount = amount(&address)?;
+            let balances = native
+                .then(|| "ETH".to_string())
+                .into_iter()
+                .chain(asset_ids)
+                .map(|asset_id| Balance::single(asset_id, &BalanceKind::Wallet, amount))
+                .collect();
+
+            Ok(PinnedBalances {
+                block: block(20),
+                balances,
+            })
+        }
+
+        fn supports_balances_at(&self) -> bool {
+            self.balances_at
+        }
+
+        async fn get_block(&self, height: Option<u64>) -> Result<BlockRef, AppError> {
+            Ok(block(height.unwrap_or(20)))
+        }
+
+        async fn get_balance_at(
+            &self,
+            address: String,
+            _block: BlockRef,
+        ) -> Result<Balance, AppError> {
+            Ok(Balance::single(
+                "ETH",
+                &BalanceKind::Wallet,
+                amount(&address)?,
+            ))
+        }
+
+        async fn get_assets_balances_at(
+            &self,
+            address: String,
+            asset_ids: Vec<String>,
+            _block: BlockRef,
+        ) -> Result<Vec<Balance>, AppError> {
+            let amount = amount(&address)?;
+            Ok(asset_ids
+                .into_iter()
+                .map(|asset_id| Balance::single(asset_id, &BalanceKind::Wallet, amount))
+                .collect())
+        }
+    }
+
+    async fn snapshots(balances_at: bool) -> Snapshots {
+        let config: Config = serde_json::from_value(json!({
+            "database_url": "sqlite::memory:",
+            "max_connections": 1,
+            "requests_per_second": 1000,
+        }))
+        .unwrap();
+        let store = Arc::new(SqlSnapshotStore::connect(&config).await.unwrap());
+
+        let node: Box<dyn AddressBalancesService + Send + Sync> = Box::new(Node { balances_at });
+        let service: Box<dyn BalancesService + Send + Sync> = Box::new(Service::new(
+            HashMap::from([(Chain::Ethereum, node)]),
+            Caip::default(),
+            HashMap::new(),
+            None,
+        ));
+
+        Snapshots::start(&config, Arc::new(service), store)
+            .await
+            .unwrap()
+    }
+
+    async fn run(snapshots: &Snapshots, height: Option<u64>) -> Job {
+        let job = snapshots
+            .submit(Spec {
+                chain: Chain::Ethereum,
+                native: true,
+                asset_ids: vec![TOKEN.to_string()],
+                addresses: vec!["0xa".to_string(), "whale".to_string(), "0xbc".to_string()],
+                height,
+            })
+            .await
+            .unwrap();
+
+        for _ in 0..100 {
+            let job = snapshots.get(&job.id).await.unwrap().unwrap();
+            if matches!(job.status, JobStatus::Completed | JobStatus::Failed) {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("snapshot job {} is not done", job.id);
+    }
+
+    #[tokio::test]
+    async fn records_overflowing_balance_as_entry_error() {
+        let snapshots = snapshots(true).await;
+        let job = run(&snapshots, Some(10)).await;
+
+        assert_eq!(job.status, JobStatus::Completed);
+        assert!(job.block_consistent);
+        assert_eq!((job.processed, job.failed), (3, 1));
+
+        let entries = snapshots.entries(&job.id).await.unwrap();
+        let whale = entries
+            .iter()
+            .find(|entry| entry.address == "whale")
+            .unwrap();
+        assert!(whale.balances.is_none());
+        assert!(whale.error.as_deref().unwrap().contains("AmountOverflow"));
+
+        let other = entries
+            .iter()
+            .find(|entry| entry.address == "0xbc")
+            .unwrap();
+        let balances = other.balances.as_ref().unwrap();
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[1].balances[0].amount, 4);
+    }
+
+    #[tokio::test]
+    async fn reads_pinned_balances_without_balances_at_past_blocks() {
+        let snapshots = snapshots(false).await;
+        let job = run(&snapshots, None).await;
+
+        assert_eq!(job.status, JobStatus::Completed);
+        assert!(!job.block_consistent);
+        assert_eq!((job.processed, job.failed), (3, 1));
+    }
+}
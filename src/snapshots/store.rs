@@ -0,0 +1,294 @@
+use sqlx::any::{AnyPool, AnyPoolOptions, AnyRow};
+use sqlx::Row;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::{Config, Entry, Job, JobStatus, Spec};
+use crate::clock::now;
+use crate::error::Error as AppError;
+
+static MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS snapshot_jobs (
+        id TEXT PRIMARY KEY,
+        spec TEXT NOT NULL,
+        status TEXT NOT NULL,
+        error TEXT,
+        created_at BIGINT NOT NULL,
+        updated_at BIGINT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS snapshot_entries (
+        job_id TEXT NOT NULL,
+        idx BIGINT NOT NULL,
+        address TEXT NOT NULL,
+        balances TEXT,
+        error TEXT,
+        PRIMARY KEY (job_id, idx)
+    )",
+];
+
+#[async_trait::async_trait]
+pub trait SnapshotStore {
+    async fn insert(&self, id: &str, spec: &Spec) -> Result<Job, AppError>;
+
+    async fn get(&self, id: &str) -> Result<Option<Job>, AppError>;
+
+    async fn spec(&self, id: &str) -> Result<Option<Spec>, AppError>;
+
+    /// Ids of queued and running jobs, oldest first
+    async fn pending(&self) -> Result<Vec<String>, AppError>;
+
+    async fn set_status(
+        &self,
+        id: &str,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> Result<(), AppError>;
+
+    /// Stores entries of the job by their index in the address list
+    async fn record(&self, id: &str, entries: &[(u64, Entry)]) -> Result<(), AppError>;
+
+    /// Indexes of the addresses already read by the job
+    async fn processed(&self, id: &str) -> Result<HashSet<u64>, AppError>;
+
+    /// Entries of the job in the order of the address list
+    async fn entries(&self, id: &str) -> Result<Vec<Entry>, AppError>;
+}
+
+/// Snapshot store on top of SQLite or Postgres
+pub struct SqlSnapshotStore {
+    pool: AnyPool,
+}
+
+impl SqlSnapshotStore {
+    /// Connects to the database and creates missing tables
+    pub async fn connect(config: &Config) -> Result<Self, AppError> {
+        let pool = AnyPoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect(&config.database_url)
+            .await
+            .map_err(Arc::new)?;
+
+        for migration in MIGRATIONS {
+            sqlx::query(migration)
+                .execute(&pool)
+                .await
+                .map_err(Arc::new)?;
+        }
+
+        Ok(Self { pool })
+    }
+
+    fn job(row: &AnyRow) -> Result<Job, AppError> {
+        let spec = from_json::<Spec>(&row.try_get::<String, _>("spec").map_err(Arc::new)?)?;
+
+        Ok(Job {
+            id: row.try_get("id").map_err(Arc::new)?,
+            chain: spec.chain,
+            native: spec.native,
+            asset_ids: spec.asset_ids,
+            height: spec.height,
+            block_consistent: spec.height.is_some(),
+            status: status_from_string(row.try_get("status").map_err(Arc::new)?)?,
+            total: spec.addresses.len() as u64,
+            processed: row.try_get::<i64, _>("processed").map_err(Arc::new)? as u64,
+            failed: row.try_get::<i64, _>("failed").map_err(Arc::new)? as u64,
+            error: row.try_get("error").map_err(Arc::new)?,
+            created_at: row.try_get::<i64, _>("created_at").map_err(Arc::new)? as u64,
+            updated_at: row.try_get::<i64, _>("updated_at").map_err(Arc::new)? as u64,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl SnapshotStore for SqlSnapshotStore {
+    async fn insert(&self, id: &str, spec: &Spec) -> Result<Job, AppError> {
+        let created_at = to_i64(now())?;
+
+        sqlx::query(
+            "INSERT INTO snapshot_jobs (id, spec, status, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(id)
+        .bind(to_json(spec)?)
+        .bind(status_to_string(&JobStatus::Queued))
+        .bind(created_at)
+        .bind(created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(Arc::new)?;
+
+        Ok(Job {
+            id: id.to_string(),
+            chain: spec.chain.clone(),
+            native: spec.native,
+            asset_ids: spec.asset_ids.clone(),
+            height: spec.height,
+            block_consistent: spec.height.is_some(),
+            status: JobStatus::Queued,
+            total: spec.addresses.len() as u64,
+            processed: 0,
+            failed: 0,
+            error: None,
+            created_at: created_at as u64,
+            updated_at: created_at as u64,
+        })
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Job>, AppError> {
+        let row = sqlx::query(
+            "SELECT j.id, j.spec, j.status, j.error, j.created_at, j.updated_at,
+                (SELECT COUNT(*) FROM snapshot_entries e WHERE e.job_id = j.id) AS processed,
+                (SELECT COUNT(*) FROM snapshot_entries e
+                    WHERE e.job_id = j.id AND e.error IS NOT NULL) AS failed
+            FROM snapshot_jobs j
+            WHERE j.id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Arc::new)?;
+
+        row.as_ref().map(Self::job).transpose()
+    }
+
+    async fn spec(&self, id: &str) -> Result<Option<Spec>, AppError> {
+        let spec = sqlx::query("SELECT spec FROM snapshot_jobs WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Arc::new)?;
+
+        spec.map(|row| from_json(&row.try_get::<String, _>("spec").map_err(Arc::new)?))
+            .transpose()
+    }
+
+    async fn pending(&self) -> Result<Vec<String>, AppError> {
+        let rows = sqlx::query(
+            "SELECT id FROM snapshot_jobs WHERE status IN ($1, $2) ORDER BY created_at",
+        )
+        .bind(status_to_string(&JobStatus::Queued))
+        .bind(status_to_string(&JobStatus::Running))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Arc::new)?;
+
+        rows.iter()
+            .map(|row| Ok(row.try_get("id").map_err(Arc::new)?))
+            .collect()
+    }
+
+    async fn set_status(
+        &self,
+        id: &str,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE snapshot_jobs SET status = $1, error = $2, updated_at = $3 WHERE id = $4",
+        )
+        .bind(status_to_string(&status))
+        .bind(error)
+        .bind(to_i64(now())?)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(Arc::new)?;
+
+        Ok(())
+    }
+
+    async fn record(&self, id: &str, entries: &[(u64, Entry)]) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await.map_err(Arc::new)?;
+
+        for (idx, entry) in entries {
+            let balances = entry.balances.as_ref().map(to_json).transpose()?;
+
+            sqlx::query(
+                "INSERT INTO snapshot_entries (job_id, idx, address, balances, error)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(id)
+            .bind(to_i64(*idx)?)
+            .bind(&entry.address)
+            .bind(balances)
+            .bind(&entry.error)
+            .execute(&mut tx)
+            .await
+            .map_err(Arc::new)?;
+        }
+
+        sqlx::query("UPDATE snapshot_jobs SET updated_at = $1 WHERE id = $2")
+            .bind(to_i64(now())?)
+            .bind(id)
+            .execute(&mut tx)
+            .await
+            .map_err(Arc::new)?;
+
+        tx.commit().await.map_err(Arc::new)?;
+
+        Ok(())
+    }
+
+    async fn processed(&self, id: &str) -> Result<HashSet<u64>, AppError> {
+        let rows = sqlx::query("SELECT idx FROM snapshot_entries WHERE job_id = $1")
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Arc::new)?;
+
+        rows.iter()
+            .map(|row| Ok(row.try_get::<i64, _>("idx").map_err(Arc::new)? as u64))
+            .collect()
+    }
+
+    async fn entries(&self, id: &str) -> Result<Vec<Entry>, AppError> {
+        let rows = sqlx::query(
+            "SELECT address, balances, error FROM snapshot_entries
+            WHERE job_id = $1
+            ORDER BY idx",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Arc::new)?;
+
+        rows.iter()
+            .map(|row| {
+                let balances = row
+                    .try_get::<Option<String>, _>("balances")
+                    .map_err(Arc::new)?;
+
+                Ok(Entry {
+                    address: row.try_get("address").map_err(Arc::new)?,
+                    balances: balances.as_deref().map(from_json).transpose()?,
+                    error: row.try_get("error").map_err(Arc::new)?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn status_to_string(status: &JobStatus) -> String {
+    match serde_json::to_value(status) {
+        Ok(serde_json::Value::String(status)) => status,
+        _ => unreachable!("JobStatus is serialized as a string"),
+    }
+}
+
+fn status_from_string(status: String) -> Result<JobStatus, AppError> {
+    serde_json::from_value(serde_json::Value::String(status))
+        .map_err(|e| AppError::Serialization(e.to_string()))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, AppError> {
+    serde_json::to_string(value).map_err(|e| AppError::Serialization(e.to_string()))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, AppError> {
+    serde_json::from_str(value).map_err(|e| AppError::Serialization(e.to_string()))
+}
+
+fn to_i64(value: u64) -> Result<i64, AppError> {
+    i64::try_from(value).map_err(|_| AppError::AmountOverflow(value.to_string()))
+}
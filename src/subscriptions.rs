@@ -0,0 +1,68 @@
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::watch;
+
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+use crate::service::{Balance, BalancesService};
+
+/// Balances of an address a client wants to be notified about
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Subscription {
+    pub chain: Chain,
+    pub address: String,
+    #[serde(default)]
+    pub asset_ids: Vec<String>,
+}
+
+impl Subscription {
+    /// Reads native balance of the address followed by balances of the subscribed assets
+    pub async fn fetch(
+        &self,
+        service: &(dyn BalancesService + Send + Sync),
+    ) -> Result<Vec<Balance>, AppError> {
+        service
+            .get_all_balances(
+                self.chain.clone(),
+                self.address.clone(),
+                self.asset_ids.clone(),
+            )
+            .await
+    }
+}
+
+/// Remembers balances already delivered to a subscriber
+#[derive(Debug, Default)]
+pub struct ChangeTracker {
+    last: HashMap<String, Balance>,
+}
+
+impl ChangeTracker {
+    /// Returns balances which differ from the previously seen ones
+    pub fn changed(&mut self, balances: Vec<Balance>) -> Vec<Balance> {
+        balances
+            .into_iter()
+            .filter(|balance| {
+                if self.last.get(&balance.asset_id) == Some(balance) {
+                    return false;
+                }
+                self.last.insert(balance.asset_id.clone(), balance.clone());
+                true
+            })
+            .collect()
+    }
+}
+
+/// Stream of heights of blocks produced after the subscription to `blocks`
+///
+/// Heights of blocks produced while the consumer is busy are skipped, only
+/// the newest one matters
+pub fn blocks_stream(blocks: watch::Receiver<u64>) -> BoxStream<'static, u64> {
+    stream::unfold(blocks, |mut blocks| async move {
+        blocks.changed().await.ok()?;
+        let height = *blocks.borrow();
+        Some((height, blocks))
+    })
+    .boxed()
+}
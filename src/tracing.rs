@@ -9,12 +9,12 @@ use tracing_subscriber::Registry;
 use crate::error::Error as AppError;
 
 pub fn init_tracing() -> Result<(), AppError> {
-    LogTracer::init().map_err(|e| Arc::new(e))?;
+    LogTracer::init().map_err(Arc::new)?;
 
     let env_filter = EnvFilter::from_default_env();
     let fmt_layer = fmt::Layer::default();
     let subscriber = Registry::default().with(env_filter).with(fmt_layer);
-    set_global_default(subscriber).map_err(|e| Arc::new(e))?;
+    set_global_default(subscriber).map_err(Arc::new)?;
 
     Ok(())
 }
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+use crate::service::{Balance, BalancesService};
+
+/// Decimals of the reported values and the portfolio total
+pub const VALUE_DECIMALS: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Currency {
+    #[serde(rename = "USD", alias = "usd")]
+    Usd,
+}
+
+/// Price of a whole unit of an asset read from an on-chain oracle
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Price {
+    pub currency: Currency,
+    /// Price scaled by `10^decimals`
+    pub answer: u128,
+    pub decimals: u32,
+    /// Decimals of the asset amounts
+    pub asset_decimals: u32,
+    /// Unix time in seconds the price was last updated at
+    pub updated_at: Option<u64>,
+}
+
+impl Price {
+    /// Value of `amount` of the asset, scaled by `10^VALUE_DECIMALS`
+    pub fn value_of(&self, amount: u64) -> Result<u128, AppError> {
+        let overflow = || AppError::AmountOverflow(format!("{} * {}", amount, self.answer));
+
+        let value = (amount as u128)
+            .checked_mul(self.answer)
+            .ok_or_else(overflow)?;
+
+        rescale(value, self.decimals + self.asset_decimals, VALUE_DECIMALS).ok_or_else(overflow)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Valuation {
+    pub currency: Currency,
+    pub price: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_timestamp: Option<u64>,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ValuedBalance {
+    #[serde(flatten)]
+    pub balance: Balance,
+    /// Missing if there is no price oracle configured for the asset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valuation: Option<Valuation>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Portfolio {
+    pub balances: Vec<ValuedBalance>,
+    pub currency: Currency,
+    /// Sum of the values of the priced balances
+    pub total_value: String,
+    /// `total_value` scaled by `10^VALUE_DECIMALS`
+    #[serde(skip)]
+    pub total: u128,
+}
+
+/// Values `balances` of the `chain` by the prices of the chain oracles,
+/// the first reported kind of every balance is valued
+pub async fn value(
+    service: &(dyn BalancesService + Send + Sync),
+    chain: Chain,
+    balances: Vec<Balance>,
+    currency: Currency,
+) -> Result<Portfolio, AppError> {
+    let asset_ids = balances
+        .iter()
+        .map(|balance| balance.asset_id.clone())
+        .collect();
+
+    let prices: HashMap<String, Price> = service.get_prices(chain, asset_ids).await?;
+
+    let mut total = 0u128;
+    let balances = balances
+        .into_iter()
+        .map(|balance| {
+            let valuation = match (
+                prices
+                    .get(&balance.asset_id)
+                    .filter(|price| price.currency == currency),
+                balance.balances.first(),
+            ) {
+                (Some(price), Some(amount)) => {
+                    let value = price.value_of(amount.amount)?;
+                    total = total.checked_add(value).ok_or_else(|| {
+                        AppError::AmountOverflow(format!("{} + {}", total, value))
+                    })?;
+
+                    Some(Valuation {
+                        currency,
+                        price: format_decimal(price.answer, price.decimals),
+                        price_timestamp: price.updated_at,
+                        value: format_decimal(value, VALUE_DECIMALS),
+                    })
+                }
+                _ => None,
+            };
+
+            Ok(ValuedBalance { balance, valuation })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    Ok(Portfolio {
+        balances,
+        currency,
+        total_value: format_decimal(total, VALUE_DECIMALS),
+        total,
+    })
+}
+
+/// Changes the scale of `value` from `10^from` to `10^to`, truncating extra digits
+pub fn rescale(value: u128, from: u32, to: u32) -> Option<u128> {
+    if from >= to {
+        10u128.checked_pow(from - to).map(|d| value / d)
+    } else {
+        10u128
+            .checked_pow(to - from)
+            .and_then(|m| value.checked_mul(m))
+    }
+}
+
+/// Formats `value` scaled by `10^decimals` as a decimal number, e.g. `1234.5600`
+pub fn format_decimal(value: u128, decimals: u32) -> String {
+    let digits = value.to_string();
+    let decimals = decimals as usize;
+
+    if decimals == 0 {
+        return digits;
+    }
+
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (int, frac) = digits.split_at(digits.len() - decimals);
+    format!("{}.{}", int, frac)
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use url::{Host, Url};
+
+use super::Config;
+use crate::error::Error as AppError;
+
+/// Rejects callback urls which lead into the network of the service, e.g. to
+/// `http://169.254.169.254/` or `http://localhost:6379/`, unless their host is allowed
+#[derive(Clone, Debug)]
+pub struct CallbackPolicy {
+    allowed_hosts: HashSet<String>,
+}
+
+/// Callback url let through by the policy
+#[derive(Clone, Debug)]
+pub struct Callback {
+    pub url: Url,
+    /// Domain of the url and the checked address it resolved to, which the request
+    /// connects to instead of resolving the domain again to a possibly different one
+    pub pinned: Option<(String, SocketAddr)>,
+}
+
+impl CallbackPolicy {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            allowed_hosts: config
+                .allowed_hosts
+                .iter()
+                .map(|host| host.to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Parses the `callback_url` and checks every address its host resolves to,
+    /// so it is checked again before each delivery in case the DNS records changed
+    pub async fn check(&self, callback_url: &str) -> Result<Callback, AppError> {
+        let url = Url::parse(callback_url)
+            .ok()
+            .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+            .ok_or_else(|| {
+                AppError::InvalidCallbackUrl("must be an absolute http(s) url".to_string())
+            })?;
+
+        let host = url
+            .host()
+            .ok_or_else(|| AppError::InvalidCallbackUrl("has no host".to_string()))?;
+        if self
+            .allowed_hosts
+            .contains(&host.to_string().to_lowercase())
+        {
+            return Ok(Callback { url, pinned: None });
+        }
+
+        let port = url.port_or_known_default().unwrap_or(80);
+        let (domain, addresses) = match host {
+            Host::Ipv4(ip) => (None, vec![SocketAddr::new(IpAddr::V4(ip), port)]),
+            Host::Ipv6(ip) => (None, vec![SocketAddr::new(IpAddr::V6(ip), port)]),
+            Host::Domain(domain) => {
+                let addresses = tokio::net::lookup_host((domain, port))
+                    .await
+                    .map_err(|e| {
+                        AppError::InvalidCallbackUrl(format!("{} is not resolved: {}", domain, e))
+                    })?
+                    .collect::<Vec<_>>();
+                (Some(domain.to_string()), addresses)
+            }
+        };
+
+        if let Some(address) = addresses.iter().find(|address| !is_public(&address.ip())) {
+            return Err(AppError::InvalidCallbackUrl(format!(
+                "{} is not a public address",
+                address.ip()
+            )));
+        }
+
+        let pinned = match (domain, addresses.first()) {
+            (Some(domain), Some(address)) => Some((domain, *address)),
+            (Some(domain), None) => {
+                return Err(AppError::InvalidCallbackUrl(format!(
+                    "{} is not resolved to any address",
+                    domain
+                )))
+            }
+            (None, _) => None,
+        };
+        Ok(Callback { url, pinned })
+    }
+}
+
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(&ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "this network" 0.0.0.0/8, shared address space 100.64.0.0/10,
+        // IETF protocol assignments 192.0.0.0/24 and reserved 240.0.0.0/4
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || a >= 240)
+}
+
+fn is_public_v6(ip: &Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // unique local fc00::/7, link-local fe80::/10, site-local fec0::/10
+        // and documentation 2001:db8::/32
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed_hosts: &[&str]) -> CallbackPolicy {
+        CallbackPolicy {
+            allowed_hosts: allowed_hosts.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_domains_resolved_to_internal_addresses() {
+        let result = policy(&[]).check("http://localhost:6379/").await;
+
+        match result {
+            Err(AppError::InvalidCallbackUrl(message)) => {
+                assert!(message.contains("is not a public address"))
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_internal_addresses() {
+        for url in [
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.1/",
+            "http://[::1]:8080/",
+            "http://[::ffff:127.0.0.1]/",
+        ] {
+            assert!(policy(&[]).check(url).await.is_err(), "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_pin_addresses_which_are_not_resolved() {
+        let callback = policy(&[]).check("https://1.1.1.1/hook").await.unwrap();
+        assert!(callback.pinned.is_none());
+
+        let callback = policy(&["localhost"])
+            .check("http://localhost:8080/hook")
+            .await
+            .unwrap();
+        assert!(callback.pinned.is_none());
+    }
+}
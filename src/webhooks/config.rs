@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// Key of HMAC-SHA256 signatures of the payloads
+    pub secret: String,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled for every next one
+    #[serde(default = "default_initial_backoff")]
+    pub initial_backoff: u64,
+    /// Callback request timeout in milliseconds
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    /// Hosts of the callbacks allowed to resolve to loopback, link-local or private
+    /// addresses, e.g. of receivers in the same network as the service
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("secret", &"***")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("request_timeout", &self.request_timeout)
+            .field("allowed_hosts", &self.allowed_hosts)
+            .finish()
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+fn default_initial_backoff() -> u64 {
+    1000
+}
+
+fn default_request_timeout() -> u64 {
+    5000
+}
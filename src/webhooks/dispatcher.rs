@@ -0,0 +1,202 @@
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use rustc_hex::ToHex;
+use sha2::Sha256;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use super::{
+    Callback, CallbackPolicy, Config, Delivery, DeliveryStatus, Notification, Watch, WatchlistStore,
+};
+use crate::clock::now;
+use crate::error::Error as AppError;
+
+static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+
+pub static SIGNATURE_HEADER: &str = "X-Signature-256";
+
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Posts notifications to callback urls of the watches
+pub struct Dispatcher {
+    /// Client of the callbacks whose host is not resolved by the policy
+    http_client: Client,
+    config: Config,
+    callbacks: CallbackPolicy,
+    store: Arc<dyn WatchlistStore + Send + Sync>,
+}
+
+impl Dispatcher {
+    pub fn try_new(
+        config: &Config,
+        store: Arc<dyn WatchlistStore + Send + Sync>,
+    ) -> Result<Self, AppError> {
+        Ok(Self {
+            http_client: http_client(config, None)?,
+            config: config.clone(),
+            callbacks: CallbackPolicy::new(config),
+            store,
+        })
+    }
+
+    /// Delivers `notification` in background, retrying failed attempts with exponential backoff
+    pub fn dispatch(self: &Arc<Self>, watch: &Watch, notification: Notification) {
+        tokio::spawn(
+            self.clone()
+                .deliver(watch.callback_url.clone(), notification),
+        );
+    }
+
+    async fn deliver(self: Arc<Self>, callback_url: String, notification: Notification) {
+        let body = serde_json::to_vec(&notification).expect("Failed to serialize Notification");
+        let signature = format!("sha256={}", sign(&self.config.secret, &body));
+
+        for attempt in 1..=self.config.max_attempts {
+            let (response_status, error, forbidden) =
+                match self.callbacks.check(&callback_url).await {
+                    Ok(callback) => {
+                        let result = match self.http_client_of(&callback) {
+                            Ok(http_client) => http_client
+                                .post(callback.url)
+                                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                                .header(SIGNATURE_HEADER, &signature)
+                                .body(body.clone())
+                                .send()
+                                .await
+                                .map_err(|e| e.to_string()),
+                            Err(e) => Err(e.to_string()),
+                        };
+
+                        match result {
+                            Ok(response) if response.status().is_success() => {
+                                (Some(response.status().as_u16()), None, false)
+                            }
+                            Ok(response) => (
+                                Some(response.status().as_u16()),
+                                Some(format!("Unexpected response status {}", response.status())),
+                                false,
+                            ),
+                            Err(e) => (None, Some(e), false),
+                        }
+                    }
+                    Err(e) => (None, Some(e.to_string()), true),
+                };
+
+            let status = match &error {
+                None => DeliveryStatus::Delivered,
+                Some(_) if attempt < self.config.max_attempts && !forbidden => {
+                    DeliveryStatus::Retrying
+                }
+                Some(_) => DeliveryStatus::Failed,
+            };
+
+            if let Some(error) = &error {
+                warn!(
+                    "failed to deliver notification {} to {}, attempt {}: {}",
+                    notification.id, callback_url, attempt, error
+                );
+            }
+
+            let delivery = Delivery {
+                notification_id: notification.id.clone(),
+                watch_id: notification.watch_id.clone(),
+                event: notification.event.name().to_string(),
+                attempt,
+                status: status.clone(),
+                response_status,
+                error,
+                timestamp: now(),
+            };
+            if let Err(e) = self.store.record_delivery(delivery).await {
+                error!("{}", e);
+            }
+
+            if status != DeliveryStatus::Retrying {
+                return;
+            }
+
+            tokio::time::sleep(self.backoff(attempt)).await;
+        }
+    }
+
+    /// Client connecting to the address the policy checked, so a DNS record changed
+    /// after the check cannot lead the request into the network of the service
+    fn http_client_of(&self, callback: &Callback) -> Result<Client, AppError> {
+        match &callback.pinned {
+            Some(pinned) => http_client(&self.config, Some(pinned)),
+            None => Ok(self.http_client.clone()),
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(self.config.initial_backoff)
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(MAX_BACKOFF)
+    }
+}
+
+fn http_client(config: &Config, pinned: Option<&(String, SocketAddr)>) -> Result<Client, AppError> {
+    // redirects could lead to the addresses rejected by the callback policy
+    let builder = reqwest::Client::builder()
+        .user_agent(APP_USER_AGENT)
+        .timeout(Duration::from_millis(config.request_timeout))
+        .redirect(reqwest::redirect::Policy::none());
+
+    let builder = match pinned {
+        Some((domain, address)) => builder.resolve(domain, *address),
+        None => builder,
+    };
+
+    Ok(builder.build().map_err(Arc::new)?)
+}
+
+/// Hex encoded HMAC-SHA256 of `payload`
+pub fn sign(secret: impl AsRef<[u8]>, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_ref()).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac.finalize().into_bytes().to_hex()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn connects_to_pinned_address_without_resolving_domain() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let config = Config {
+            secret: "secret".to_string(),
+            max_attempts: 1,
+            initial_backoff: 1,
+            request_timeout: 5000,
+            allowed_hosts: vec![],
+        };
+        // the domain is not resolvable, so the request reaches the address only if pinned
+        let pinned = ("receiver.invalid".to_string(), address);
+        let response = http_client(&config, Some(&pinned))
+            .unwrap()
+            .post(format!("http://receiver.invalid:{}/hook", address.port()))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 204);
+    }
+}
@@ -0,0 +1,201 @@
+mod callback;
+mod config;
+mod dispatcher;
+mod poller;
+mod store;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::now;
+use crate::node_clients::Chain;
+use crate::service::{find_amount, Balance, BalanceKind};
+use crate::subscriptions::Subscription;
+
+pub use callback::{Callback, CallbackPolicy};
+pub use config::Config;
+pub use dispatcher::{sign, Dispatcher, SIGNATURE_HEADER};
+pub use poller::spawn_pollers;
+pub use store::{InMemoryWatchlistStore, WatchlistStore};
+
+/// Address which balances are reported to `callback_url`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Watch {
+    pub id: String,
+    pub chain: Chain,
+    pub address: String,
+    pub asset_ids: Vec<String>,
+    pub callback_url: String,
+    /// Whether to notify about every balance change and not only about threshold crossings
+    pub notify_on_change: bool,
+    pub thresholds: Vec<Threshold>,
+    pub created_at: u64,
+}
+
+impl Watch {
+    pub fn subscription(&self) -> Subscription {
+        Subscription {
+            chain: self.chain.clone(),
+            address: self.address.clone(),
+            asset_ids: self.asset_ids.clone(),
+        }
+    }
+
+    /// Events caused by the change of watched balances from `previous` to `current`
+    pub fn evaluate(&self, previous: &[Balance], current: &[Balance]) -> Vec<Event> {
+        let mut events = vec![];
+
+        if self.notify_on_change && previous != current {
+            events.push(Event::BalanceChanged {
+                previous: previous.to_vec(),
+                balances: current.to_vec(),
+            });
+        }
+
+        events.extend(
+            self.thresholds
+                .iter()
+                .flat_map(|threshold| threshold.crossings(previous, current)),
+        );
+
+        events
+    }
+}
+
+/// Limits of an asset balance, crossing any of them in either direction is reported
+///
+/// `asset_id` is either one of the watched assets or the native token of the chain
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Threshold {
+    pub asset_id: String,
+    /// Kind of the balance to compare, the first reported one if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<BalanceKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<u64>,
+}
+
+impl Threshold {
+    pub fn amount(&self, balances: &[Balance]) -> Option<u64> {
+        find_amount(balances, &self.asset_id, self.kind.as_ref())
+    }
+
+    fn crossings(&self, previous: &[Balance], current: &[Balance]) -> Vec<Event> {
+        let (previous_amount, amount) = match (self.amount(previous), self.amount(current)) {
+            (Some(previous_amount), Some(amount)) => (previous_amount, amount),
+            _ => return vec![],
+        };
+
+        let crossing = |threshold: u64, direction: Direction| Event::ThresholdCrossed {
+            asset_id: self.asset_id.clone(),
+            threshold,
+            direction,
+            previous_amount,
+            amount,
+        };
+
+        let mut events = vec![];
+
+        if let Some(below) = self.below {
+            if previous_amount >= below && amount < below {
+                events.push(crossing(below, Direction::Below));
+            } else if previous_amount < below && amount >= below {
+                events.push(crossing(below, Direction::Above));
+            }
+        }
+
+        if let Some(above) = self.above {
+            if previous_amount <= above && amount > above {
+                events.push(crossing(above, Direction::Above));
+            } else if previous_amount > above && amount <= above {
+                events.push(crossing(above, Direction::Below));
+            }
+        }
+
+        events
+    }
+}
+
+/// Side of the threshold the balance has moved to
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Below,
+    Above,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    BalanceChanged {
+        previous: Vec<Balance>,
+        balances: Vec<Balance>,
+    },
+    ThresholdCrossed {
+        asset_id: String,
+        threshold: u64,
+        direction: Direction,
+        previous_amount: u64,
+        amount: u64,
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BalanceChanged { .. } => "balance_changed",
+            Self::ThresholdCrossed { .. } => "threshold_crossed",
+        }
+    }
+}
+
+/// Payload posted to the callback url
+#[derive(Clone, Debug, Serialize)]
+pub struct Notification {
+    pub id: String,
+    pub watch_id: String,
+    pub chain: Chain,
+    pub address: String,
+    pub height: u64,
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl Notification {
+    pub fn new(watch: &Watch, height: u64, event: Event) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            watch_id: watch.id.clone(),
+            chain: watch.chain.clone(),
+            address: watch.address.clone(),
+            height,
+            timestamp: now(),
+            event,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Delivered,
+    Retrying,
+    Failed,
+}
+
+/// Single attempt to deliver a notification
+#[derive(Clone, Debug, Serialize)]
+pub struct Delivery {
+    pub notification_id: String,
+    pub watch_id: String,
+    pub event: String,
+    pub attempt: u32,
+    pub status: DeliveryStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
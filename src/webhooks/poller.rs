@@ -0,0 +1,85 @@
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{error, warn};
+
+use super::{Dispatcher, Notification, WatchlistStore};
+use crate::node_clients::Chain;
+use crate::service::{Balance, BalancesService};
+use crate::subscriptions;
+
+/// Starts a background poller per chain, which re-reads balances of the
+/// watched addresses on every new block and dispatches resulting notifications
+pub fn spawn_pollers(
+    chains: impl IntoIterator<Item = Chain>,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    store: Arc<dyn WatchlistStore + Send + Sync>,
+    dispatcher: Arc<Dispatcher>,
+) {
+    for chain in chains {
+        tokio::spawn(poll(
+            chain,
+            service.clone(),
+            store.clone(),
+            dispatcher.clone(),
+        ));
+    }
+}
+
+async fn poll(
+    chain: Chain,
+    service: Arc<Box<dyn BalancesService + Send + Sync>>,
+    store: Arc<dyn WatchlistStore + Send + Sync>,
+    dispatcher: Arc<Dispatcher>,
+) {
+    let blocks = match service.subscribe_blocks(chain.clone()) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let mut blocks = subscriptions::blocks_stream(blocks);
+
+    // watch id -> balances observed at the previous block
+    let mut last_balances: HashMap<String, Vec<Balance>> = HashMap::new();
+
+    while let Some(height) = blocks.next().await {
+        let watches = match store.list_by_chain(&chain).await {
+            Ok(watches) => watches,
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        };
+
+        last_balances.retain(|id, _| watches.iter().any(|watch| &watch.id == id));
+
+        let subscriptions = watches
+            .iter()
+            .map(|watch| watch.subscription())
+            .collect::<Vec<_>>();
+        let results = futures::future::join_all(
+            subscriptions
+                .iter()
+                .map(|subscription| subscription.fetch(service.as_ref().as_ref())),
+        )
+        .await;
+
+        for (watch, result) in watches.iter().zip(results) {
+            let balances = match result {
+                Ok(balances) => balances,
+                Err(e) => {
+                    warn!("failed to read balances of watch {}: {}", watch.id, e);
+                    continue;
+                }
+            };
+
+            if let Some(previous) = last_balances.insert(watch.id.clone(), balances.clone()) {
+                for event in watch.evaluate(&previous, &balances) {
+                    dispatcher.dispatch(watch, Notification::new(watch, height, event));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+
+use super::{Delivery, Watch};
+use crate::error::Error as AppError;
+use crate::node_clients::Chain;
+
+const MAX_DELIVERIES_PER_WATCH: usize = 100;
+
+#[async_trait::async_trait]
+pub trait WatchlistStore {
+    async fn insert(&self, watch: Watch) -> Result<(), AppError>;
+
+    /// Removes the watch together with its delivery log
+    async fn remove(&self, id: &str) -> Result<Option<Watch>, AppError>;
+
+    async fn get(&self, id: &str) -> Result<Option<Watch>, AppError>;
+
+    async fn list(&self) -> Result<Vec<Watch>, AppError>;
+
+    async fn list_by_chain(&self, chain: &Chain) -> Result<Vec<Watch>, AppError>;
+
+    /// Appends the delivery to the log of its watch, unless the watch is removed
+    async fn record_delivery(&self, delivery: Delivery) -> Result<(), AppError>;
+
+    /// Returns the most recent delivery attempts of the watch, newest first
+    async fn deliveries(&self, watch_id: &str) -> Result<Vec<Delivery>, AppError>;
+}
+
+/// Keeps the watchlist in memory, so it does not survive restarts
+#[derive(Debug, Default)]
+pub struct InMemoryWatchlistStore {
+    watches: RwLock<HashMap<String, Watch>>,
+    deliveries: RwLock<HashMap<String, VecDeque<Delivery>>>,
+}
+
+#[async_trait::async_trait]
+impl WatchlistStore for InMemoryWatchlistStore {
+    async fn insert(&self, watch: Watch) -> Result<(), AppError> {
+        self.watches
+            .write()
+            .expect("watches lock is poisoned")
+            .insert(watch.id.clone(), watch);
+        Ok(())
+    }
+
+    async fn remove(&self, id: &str) -> Result<Option<Watch>, AppError> {
+        // both locks are held, so no delivery is recorded in between
+        let mut deliveries = self
+            .deliveries
+            .write()
+            .expect("deliveries lock is poisoned");
+        deliveries.remove(id);
+        Ok(self
+            .watches
+            .write()
+            .expect("watches lock is poisoned")
+            .remove(id))
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Watch>, AppError> {
+        Ok(self
+            .watches
+            .read()
+            .expect("watches lock is poisoned")
+            .get(id)
+            .cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<Watch>, AppError> {
+        Ok(self
+            .watches
+            .read()
+            .expect("watches lock is poisoned")
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn list_by_chain(&self, chain: &Chain) -> Result<Vec<Watch>, AppError> {
+        Ok(self
+            .watches
+            .read()
+            .expect("watches lock is poisoned")
+            .values()
+            .filter(|watch| &watch.chain == chain)
+            .cloned()
+            .collect())
+    }
+
+    async fn record_delivery(&self, delivery: Delivery) -> Result<(), AppError> {
+        let mut deliveries = self
+            .deliveries
+            .write()
+            .expect("deliveries lock is poisoned");
+
+        // deliveries in flight when the watch was removed are not recorded
+        let exists = self
+            .watches
+            .read()
+            .expect("watches lock is poisoned")
+            .contains_key(&delivery.watch_id);
+        if !exists {
+            return Ok(());
+        }
+
+        let log = deliveries.entry(delivery.watch_id.clone()).or_default();
+        log.push_front(delivery);
+        log.truncate(MAX_DELIVERIES_PER_WATCH);
+        Ok(())
+    }
+
+    async fn deliveries(&self, watch_id: &str) -> Result<Vec<Delivery>, AppError> {
+        Ok(self
+            .deliveries
+            .read()
+            .expect("deliveries lock is poisoned")
+            .get(watch_id)
+            .map(|log| log.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+}